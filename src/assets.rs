@@ -0,0 +1,270 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::Result;
+use anyhow::{anyhow, Context};
+
+const GAME_DIR_NAME: &str = "Last Call BBS";
+const CONTENT_DIR_NAME: &str = "Content";
+
+const GAME_DIR_ENV: &str = "LAST_CALL_BBS_DIR";
+const OUTPUT_DIR_ENV: &str = "DUNGEON_CRAWLER_OUT";
+const CONFIG_FILE: &str = "dungeon-crawler.conf";
+
+// Steam roots relative to the home directory, and a few absolute ones. Each root has a
+// steamapps/libraryfolders.vdf listing any extra library folders.
+const STEAM_HOME_ROOTS: &[&str] = &[
+    ".local/share/Steam",
+    ".steam/steam",
+    ".steam/root",
+    ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+    "Library/Application Support/Steam",
+];
+const STEAM_ABSOLUTE_ROOTS: &[&str] = &[r"C:\Program Files (x86)\Steam", r"C:\Program Files\Steam"];
+
+// Where things live once the textures have been decoded, relative to the output directory
+const SPRITES_DIR: &str = "tokyo";
+
+// Settings that can come from the command line, the environment, or the config file. Earlier
+// sources win.
+#[derive(Debug, Default, Clone)]
+pub struct AssetOverrides {
+    pub game_dir: Option<PathBuf>,
+    pub output_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetLocator {
+    game_dir: Option<PathBuf>,
+    output_dir: PathBuf,
+}
+
+static GLOBAL: OnceLock<Result<AssetLocator, String>> = OnceLock::new();
+
+impl AssetLocator {
+    pub fn new(game_dir: Option<PathBuf>, output_dir: PathBuf) -> Self {
+        Self {
+            game_dir,
+            output_dir,
+        }
+    }
+
+    // Resolve the install and output directories. The install directory is only searched for
+    // if nothing was configured, and it's not an error for it to be missing until something
+    // actually needs the game files. A config file that's there but can't be used is an error.
+    pub fn resolve(cli: AssetOverrides) -> Result<Self> {
+        let config = read_config(Path::new(CONFIG_FILE))?;
+        let from_env = |key| env::var_os(key).map(PathBuf::from);
+
+        let game_dir = cli
+            .game_dir
+            .or_else(|| from_env(GAME_DIR_ENV))
+            .or(config.game_dir)
+            .or_else(discover_game_dir);
+        let output_dir = cli
+            .output_dir
+            .or_else(|| from_env(OUTPUT_DIR_ENV))
+            .or(config.output_dir)
+            .unwrap_or_else(|| PathBuf::from("."));
+
+        Ok(Self::new(game_dir, output_dir))
+    }
+
+    // Make this the locator returned by global(). Has no effect if one was already set.
+    pub fn install(self) -> Result<&'static AssetLocator> {
+        GLOBAL
+            .get_or_init(|| Ok(self))
+            .as_ref()
+            .map_err(|e| anyhow!("{e}"))
+    }
+
+    // For code too far from main to be handed a locator. Falls back to resolving from the
+    // environment and config file if main didn't install one.
+    pub fn global() -> Result<&'static AssetLocator> {
+        GLOBAL
+            .get_or_init(|| Self::resolve(AssetOverrides::default()).map_err(|e| e.to_string()))
+            .as_ref()
+            .map_err(|e| anyhow!("{e}"))
+    }
+
+    pub fn game_dir(&self) -> Result<&Path> {
+        self.game_dir.as_deref().ok_or_else(|| {
+            anyhow!(
+                "Couldn't find the {GAME_DIR_NAME} install. Pass --game-dir, set \
+                 {GAME_DIR_ENV}, or add game_dir to {CONFIG_FILE}"
+            )
+        })
+    }
+
+    // The packed game assets, e.g. .../Last Call BBS/Content
+    pub fn content_dir(&self) -> Result<PathBuf> {
+        Ok(self.game_dir()?.join(CONTENT_DIR_NAME))
+    }
+
+    pub fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    // Where a file from the install directory ends up once decoded. The layout under the
+    // install directory is kept, so Content/Packed/... maps to <output>/Content/Packed/...
+    pub fn decoded_path(&self, game_file: &Path) -> Result<PathBuf> {
+        let relative = game_file.strip_prefix(self.game_dir()?)?;
+        Ok(self.output_dir.join(relative))
+    }
+
+    // A decoded texture by its path relative to the output directory
+    pub fn decoded(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.output_dir.join(relative)
+    }
+
+    // The hand sorted sprite folders (one folder of frames per monster, fonts, ...)
+    pub fn sprites(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.output_dir.join(SPRITES_DIR).join(relative)
+    }
+}
+
+// Very small key = value format, # starts a comment. No file is the same as an empty one.
+fn read_config(path: &Path) -> Result<AssetOverrides> {
+    let mut config = AssetOverrides::default();
+    let text = match fs::read_to_string(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(config),
+        text => text.with_context(|| format!("Couldn't read {}", path.display()))?,
+    };
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = PathBuf::from(value.trim());
+        match key.trim() {
+            "game_dir" => config.game_dir = Some(value),
+            "output_dir" => config.output_dir = Some(value),
+            key => return Err(anyhow!("Unknown key '{key}' in {}", path.display())),
+        }
+    }
+    Ok(config)
+}
+
+fn steam_roots() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+    let mut roots: Vec<PathBuf> = match home {
+        Some(home) => STEAM_HOME_ROOTS
+            .iter()
+            .map(|root| Path::new(&home).join(root))
+            .collect(),
+        None => Vec::new(),
+    };
+    roots.extend(STEAM_ABSOLUTE_ROOTS.iter().map(PathBuf::from));
+    roots
+}
+
+// Look through every Steam root and the library folders each one knows about
+pub fn discover_game_dir() -> Option<PathBuf> {
+    let mut libraries = Vec::new();
+    for root in steam_roots() {
+        let vdf = root.join("steamapps").join("libraryfolders.vdf");
+        if let Ok(text) = fs::read_to_string(vdf) {
+            libraries.extend(parse_library_folders(&text));
+        }
+        libraries.push(root);
+    }
+
+    libraries
+        .into_iter()
+        .map(|library| library.join("steamapps").join("common").join(GAME_DIR_NAME))
+        .find(|dir| dir.join(CONTENT_DIR_NAME).is_dir())
+}
+
+// Pull the library paths out of a libraryfolders.vdf. Every library is a block with a
+// "path" key, e.g.
+//     "0"
+//     {
+//         "path"      "/home/aaron/.local/share/Steam"
+//         ...
+// Older versions of the file map the library index straight to its path instead.
+pub fn parse_library_folders(vdf: &str) -> Vec<PathBuf> {
+    let is_number = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    vdf.lines()
+        .filter_map(|line| {
+            let mut quoted = line.split('"').skip(1).step_by(2);
+            match (quoted.next(), quoted.next()) {
+                (Some(key), Some(path))
+                    if key == "path" || (is_number(key) && !is_number(path)) =>
+                {
+                    Some(PathBuf::from(path.replace(r"\\", r"\")))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn library_folders() {
+        let vdf = r#"
+"libraryfolders"
+{
+	"0"
+	{
+		"path"		"/home/aaron/.local/share/Steam"
+		"label"		""
+		"apps"
+		{
+			"228980"		"470157737"
+		}
+	}
+	"1"
+	{
+		"path"		"D:\\SteamLibrary"
+	}
+}"#;
+        assert_eq!(
+            parse_library_folders(vdf),
+            [
+                PathBuf::from("/home/aaron/.local/share/Steam"),
+                PathBuf::from(r"D:\SteamLibrary")
+            ]
+        );
+    }
+
+    #[test]
+    fn config_file() {
+        let path = env::temp_dir().join(format!("assets_config_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let config = read_config(&path).unwrap();
+        assert_eq!((config.game_dir, config.output_dir), (None, None));
+
+        fs::write(&path, "# where things go\noutput_dir = out # decoded\n").unwrap();
+        let config = read_config(&path).unwrap();
+        assert_eq!(config.output_dir, Some(PathBuf::from("out")));
+
+        fs::write(&path, "colour = blue\n").unwrap();
+        assert!(read_config(&path).is_err());
+        fs::remove_file(&path).unwrap();
+
+        // Anything other than a missing file gets passed up
+        assert!(read_config(&env::temp_dir()).is_err());
+    }
+
+    #[test]
+    fn library_folders_legacy() {
+        let vdf = r#"
+"LibraryFolders"
+{
+	"TimeNextStatsReport"		"1612345678"
+	"ContentStatsID"		"-1234567890"
+	"1"		"/mnt/games/SteamLibrary"
+}"#;
+        assert_eq!(
+            parse_library_folders(vdf),
+            [PathBuf::from("/mnt/games/SteamLibrary")]
+        );
+    }
+}
@@ -0,0 +1,271 @@
+#[cfg(feature = "automation")]
+use std::thread;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+#[cfg(feature = "automation")]
+use enigo::{Button, Coordinate, Direction, Enigo, Keyboard, Mouse, Settings};
+use image::RgbaImage;
+#[cfg(feature = "automation")]
+use xcap::Window;
+
+#[cfg(feature = "automation")]
+use crate::timing::TimingProfile;
+
+// Something that can show us the game window. Coordinates elsewhere are relative to the top
+// left corner of what this captures.
+pub trait ScreenSource {
+    fn capture(&mut self) -> Result<RgbaImage>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    // A character with control held down
+    Ctrl(char),
+    Enter,
+    Escape,
+    Backspace,
+}
+
+// Something that can click on the game window, at the same coordinates as ScreenSource
+pub trait InputSink {
+    fn click(&mut self, x: u32, y: u32) -> Result<()>;
+
+    fn right_click(&mut self, x: u32, y: u32) -> Result<()> {
+        let _ = (x, y);
+        Err(anyhow!("Right clicking isn't supported"))
+    }
+
+    fn key(&mut self, key: Key) -> Result<()> {
+        let _ = key;
+        Err(anyhow!("Typing isn't supported"))
+    }
+
+    // Press at one point and release at another
+    fn drag(&mut self, from: (u32, u32), to: (u32, u32)) -> Result<()> {
+        let _ = (from, to);
+        Err(anyhow!("Dragging isn't supported"))
+    }
+
+    // How long to give the game to take in each input
+    fn set_delay(&mut self, _delay: Duration) {}
+}
+
+// The real game window
+#[cfg(feature = "automation")]
+pub struct XcapScreen {
+    window: Window,
+}
+
+#[cfg(feature = "automation")]
+impl XcapScreen {
+    pub fn find(title: &str) -> Result<Self> {
+        let windows = Window::all()?;
+        let window = windows
+            .iter()
+            .find(|win| win.title() == title)
+            .with_context(|| format!("Failed to find {title} Window"))?
+            .clone();
+        Ok(Self { window })
+    }
+
+    // Screen position of the window, for turning window coordinates into mouse coordinates
+    pub fn position(&self) -> (u32, u32) {
+        (self.window.x() as u32, self.window.y() as u32)
+    }
+}
+
+#[cfg(feature = "automation")]
+impl ScreenSource for XcapScreen {
+    fn capture(&mut self) -> Result<RgbaImage> {
+        Ok(self.window.capture_image()?)
+    }
+}
+
+// The real mouse
+#[cfg(feature = "automation")]
+pub struct EnigoInput {
+    enigo: Enigo,
+    win_offset: (u32, u32),
+    delay: Duration,
+}
+
+#[cfg(feature = "automation")]
+impl EnigoInput {
+    pub fn new(win_offset: (u32, u32)) -> Result<Self> {
+        let settings = Settings {
+            linux_delay: 0,
+            ..Default::default()
+        };
+        Ok(Self {
+            enigo: Enigo::new(&settings)?,
+            win_offset,
+            delay: TimingProfile::default().click_delay(),
+        })
+    }
+
+    fn click_button(&mut self, x: u32, y: u32, button: Button) -> Result<()> {
+        self.move_to(x, y)?;
+        thread::sleep(self.delay / 2);
+        self.enigo.button(button, Direction::Click)?;
+        thread::sleep(self.delay / 2);
+        Ok(())
+    }
+
+    fn move_to(&mut self, x: u32, y: u32) -> Result<()> {
+        let cx = (x + self.win_offset.0) as i32;
+        let cy = (y + self.win_offset.1) as i32;
+        self.enigo.move_mouse(cx, cy, Coordinate::Abs)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "automation")]
+impl InputSink for EnigoInput {
+    fn click(&mut self, x: u32, y: u32) -> Result<()> {
+        self.click_button(x, y, Button::Left)
+    }
+
+    fn right_click(&mut self, x: u32, y: u32) -> Result<()> {
+        self.click_button(x, y, Button::Right)
+    }
+
+    fn key(&mut self, key: Key) -> Result<()> {
+        use enigo::Key as K;
+        match key {
+            Key::Char(c) => self.enigo.key(K::Unicode(c), Direction::Click)?,
+            Key::Ctrl(c) => {
+                self.enigo.key(K::Control, Direction::Press)?;
+                self.enigo.key(K::Unicode(c), Direction::Click)?;
+                self.enigo.key(K::Control, Direction::Release)?;
+            }
+            Key::Enter => self.enigo.key(K::Return, Direction::Click)?,
+            Key::Escape => self.enigo.key(K::Escape, Direction::Click)?,
+            Key::Backspace => self.enigo.key(K::Backspace, Direction::Click)?,
+        }
+        thread::sleep(self.delay);
+        Ok(())
+    }
+
+    // Moves in a few steps so the game sees the cursor pass over everything in between
+    fn drag(&mut self, from: (u32, u32), to: (u32, u32)) -> Result<()> {
+        const STEPS: i64 = 8;
+        self.move_to(from.0, from.1)?;
+        thread::sleep(self.delay / 2);
+        self.enigo.button(Button::Left, Direction::Press)?;
+        for i in 1..=STEPS {
+            let lerp = |a: u32, b: u32| (a as i64 + (b as i64 - a as i64) * i / STEPS) as u32;
+            self.move_to(lerp(from.0, to.0), lerp(from.1, to.1))?;
+            thread::sleep(self.delay / STEPS as u32);
+        }
+        self.enigo.button(Button::Left, Direction::Release)?;
+        thread::sleep(self.delay / 2);
+        Ok(())
+    }
+
+    fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+}
+
+// Plays back a sequence of screenshots, one per capture. The last one is repeated once the
+// sequence runs out.
+pub struct ReplayScreen {
+    frames: Vec<RgbaImage>,
+    next: usize,
+}
+
+impl ReplayScreen {
+    pub fn new(frames: Vec<RgbaImage>) -> Result<Self> {
+        if frames.is_empty() {
+            return Err(anyhow!("Nothing to replay"));
+        }
+        Ok(Self { frames, next: 0 })
+    }
+
+    pub fn from_files(paths: &[PathBuf]) -> Result<Self> {
+        let frames = paths
+            .iter()
+            .map(|path| {
+                image::open(path)
+                    .map(|img| img.to_rgba8())
+                    .with_context(|| format!("Failed to open {}", path.display()))
+            })
+            .collect::<Result<_>>()?;
+        Self::new(frames)
+    }
+
+    // How many captures have been taken so far
+    pub fn captures(&self) -> usize {
+        self.next
+    }
+}
+
+impl ScreenSource for ReplayScreen {
+    fn capture(&mut self) -> Result<RgbaImage> {
+        let frame = self.frames[self.next.min(self.frames.len() - 1)].clone();
+        self.next += 1;
+        Ok(frame)
+    }
+}
+
+// Remembers every click, and optionally writes them to a log file as x,y lines. Drags are
+// x,y-x,y lines, right clicks x,y right and keys key Name.
+#[derive(Default)]
+pub struct ClickLog {
+    pub clicks: Vec<(u32, u32)>,
+    pub drags: Vec<((u32, u32), (u32, u32))>,
+    pub right_clicks: Vec<(u32, u32)>,
+    pub keys: Vec<Key>,
+    log: Option<BufWriter<File>>,
+}
+
+impl ClickLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn to_file(path: &Path) -> Result<Self> {
+        Ok(Self {
+            log: Some(BufWriter::new(File::create(path)?)),
+            ..Default::default()
+        })
+    }
+
+    fn log(&mut self, line: std::fmt::Arguments) -> Result<()> {
+        if let Some(log) = &mut self.log {
+            writeln!(log, "{line}")?;
+            log.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl InputSink for ClickLog {
+    fn click(&mut self, x: u32, y: u32) -> Result<()> {
+        self.clicks.push((x, y));
+        self.log(format_args!("{x},{y}"))
+    }
+
+    fn drag(&mut self, from: (u32, u32), to: (u32, u32)) -> Result<()> {
+        self.drags.push((from, to));
+        self.log(format_args!("{},{}-{},{}", from.0, from.1, to.0, to.1))
+    }
+
+    fn right_click(&mut self, x: u32, y: u32) -> Result<()> {
+        self.right_clicks.push((x, y));
+        self.log(format_args!("{x},{y} right"))
+    }
+
+    fn key(&mut self, key: Key) -> Result<()> {
+        self.keys.push(key);
+        self.log(format_args!("key {key:?}"))
+    }
+}
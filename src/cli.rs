@@ -0,0 +1,140 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+// Options that take a value. Anything else starting with - is a switch.
+const VALUE_OPTIONS: &[&str] = &[
+    "--game-dir",
+    "--out",
+    "--frame",
+    "-o",
+    "--scale",
+    "--delay",
+    "--boards",
+    "--seed",
+    "--retries",
+    "--samples",
+    "--layout",
+    "--db",
+    "--port",
+];
+
+pub const USAGE: &str = "\
+usage: dungeon-crawler [command] [options]
+
+commands:
+  (none)                  solve boards in the running game
+  decode                  decode every texture in the game's Content folder
+  inspect [--json]        list the textures in the game's Content folder
+  extract <name> [--frame N] [-o file.png]
+                          decode a single texture, or one frame of an array texture
+  offsets                 find where each monster's sprite sits on its tile, using the
+                          screenshots in monster_refs, and save them for parsing
+  sprites [monster] [-o file] [--scale N] [--delay ms]
+                          export decoded monster sprites aligned to their tile, as an
+                          animated .gif or a sprite sheet (.png, .webp). Defaults to
+                          every monster, written to sprites.gif
+  collect [--boards N]    parse random boards into data/puzzles.db (default 4700).
+                          Picks up after the last good record of an earlier run, and
+                          Ctrl-C stops cleanly with everything so far saved
+  db stats [-o dir]       distributions of monsters, treasure and wall counts, seed
+                          coverage and per-cell heatmaps over data/puzzles.db. The
+                          heatmaps are also saved as PNGs (default
+                          script_output/db_stats)
+  db seeds [--boards N]   estimate how many seeds RANDOM can give from the ones
+                          collected so far, how many new ones the next N boards
+                          should turn up (default 4700), and whether they look
+                          uniformly spread
+  play [--seed N]         play a board from data/puzzles.db in the terminal, the one
+                          with seed N or else any of them. Hints and reveal use the
+                          solver
+  edit [file] [-o file] [--db file]
+                          design a board in the terminal by drawing its walls,
+                          monsters and treasure. Shows the counts the walls make, any
+                          tiles breaking the rules and whether the counts have one
+                          solution. Saving writes the puzzle as text (default: back to
+                          file, or designed.txt) and appends it to data/designed.db
+  serve [--port N] [--db file]
+                          answer JSON requests on 127.0.0.1 (default port 7878):
+                          POST /parse a PNG screenshot, POST /solve a puzzle as JSON
+                          or text, GET /puzzles/<seed> from data/puzzles.db and
+                          GET /hint?seed=N[&walls=hex][&open=hex]. GET / lists them
+  calibrate [--samples N] measure how long the game takes to redraw after clicks and
+                          save the delays to data/timing.json, which later runs load
+  simulate [--boards N] [--seed N]
+                          solve boards in a simulated game built from the screenshots
+                          in monster_refs, without touching the real one
+
+options:
+  --game-dir <dir>        Last Call BBS install directory
+  --out <dir>             where decoded textures are written
+  --layout <file>         where things are on screen, if not data/layout.json. See
+                          data/layout.schema.json for the format
+  --retries <n>           times to re-click cells that didn't take after entering a
+                          solution, before giving up on the board (default 3)
+
+environment:
+  DUNGEON_CRAWLER_DEBUG_IMAGES
+                          when set, save the images behind each screen read under
+                          script_output/ and the working directory";
+
+// Minimal command line handling: a command followed by positional arguments, switches and
+// options with values, in any order
+#[derive(Debug, Default)]
+pub struct Args {
+    pub command: Option<String>,
+    pub positional: Vec<String>,
+    options: HashMap<String, String>,
+    switches: HashSet<String>,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if VALUE_OPTIONS.contains(&arg.as_str()) {
+                let value = args.next().ok_or_else(|| anyhow!("{arg} needs a value"))?;
+                parsed.options.insert(arg, value);
+            } else if arg.starts_with('-') {
+                parsed.switches.insert(arg);
+            } else if parsed.command.is_none() {
+                parsed.command = Some(arg);
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+        Ok(parsed)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    pub fn parsed<T: FromStr>(&self, name: &str) -> Result<Option<T>> {
+        self.value(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| anyhow!("Bad value '{value}' for {name}"))
+            })
+            .transpose()
+    }
+
+    // Only the texture commands have switches so far
+    #[cfg_attr(not(feature = "textures"), allow(dead_code))]
+    pub fn switch(&self, name: &str) -> bool {
+        self.switches.contains(name)
+    }
+
+    pub fn positional(&self, index: usize, name: &str) -> Result<&str> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| anyhow!("Missing <{name}>\n\n{USAGE}"))
+    }
+}
@@ -0,0 +1,666 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::backend::{InputSink, ScreenSource};
+use crate::dungeon_crawler::DungeonCrawler;
+use crate::puzzle::Puzzle;
+
+pub const DB_DIR: &str = "data";
+pub const DB_FILE: &str = "puzzles.db";
+
+// Files start with the magic and a version. Older files are bare 26 byte puzzles back to back,
+// and get converted the first time they're opened for writing.
+const MAGIC: &[u8; 4] = b"DCDB";
+const DB_VERSION: u32 = 1;
+const HEADER_LEN: usize = 8;
+
+// Each record is a serialized puzzle followed by the CRC-32 of its bytes, so a record torn by
+// a crash or a flipped bit is caught. Records are all the same length, so one bad record
+// doesn't shift the ones after it.
+pub const PUZZLE_LEN: usize = 26;
+const RECORD_LEN: usize = PUZZLE_LEN + 4;
+
+// Records written between fsyncs. A crash loses at most this many.
+pub const SYNC_INTERVAL: usize = 100;
+
+pub fn path() -> PathBuf {
+    Path::new(DB_DIR).join(DB_FILE)
+}
+
+fn header() -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..4].copy_from_slice(MAGIC);
+    header[4..].copy_from_slice(&DB_VERSION.to_le_bytes());
+    header
+}
+
+fn record(puzzle: &Puzzle) -> Result<[u8; RECORD_LEN]> {
+    let mut bytes = Vec::with_capacity(RECORD_LEN);
+    puzzle.serialize(&mut bytes)?;
+    bytes.extend(crc32fast::hash(&bytes).to_le_bytes());
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("Puzzles should serialize to {PUZZLE_LEN} bytes"))
+}
+
+// Everything readable in a database file, and what couldn't be read
+#[derive(Debug, Default)]
+pub struct Scan {
+    pub puzzles: Vec<Puzzle>,
+    // Bytes of the file holding the header and whole records, damaged ones included
+    pub good_len: u64,
+    // Records that fail their checksum, skipped over
+    pub damaged: usize,
+    // Bytes after the last whole record, from a record that was cut short
+    pub dropped: u64,
+    // Bare puzzles from before records had checksums
+    pub legacy: bool,
+}
+
+impl Display for Scan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} records", self.puzzles.len())?;
+        if self.legacy {
+            write!(f, " in the old format")?;
+        }
+        if self.damaged > 0 {
+            write!(f, ", {} damaged records skipped", self.damaged)?;
+        }
+        if self.dropped > 0 {
+            write!(f, ", {} bytes after the last whole record", self.dropped)?;
+        }
+        Ok(())
+    }
+}
+
+// Read every whole record, skipping and counting ones that fail their checksum. A record cut
+// short can only be the last one, and is reported rather than guessed at.
+pub fn scan(bytes: &[u8]) -> Result<Scan> {
+    let mut scan = Scan::default();
+    let body = match bytes.strip_prefix(MAGIC) {
+        Some(rest) => {
+            let version = rest
+                .first_chunk::<4>()
+                .map(|v| u32::from_le_bytes(*v))
+                .ok_or_else(|| anyhow!("Database header is cut short"))?;
+            if version != DB_VERSION {
+                return Err(anyhow!(
+                    "Database is version {version}, expected {DB_VERSION}"
+                ));
+            }
+            scan.good_len = HEADER_LEN as u64;
+            &bytes[HEADER_LEN..]
+        }
+        None => {
+            scan.legacy = true;
+            bytes
+        }
+    };
+
+    let record_len = if scan.legacy { PUZZLE_LEN } else { RECORD_LEN };
+    let mut rest = body;
+    while rest.len() >= record_len {
+        let (chunk, tail) = rest.split_at(record_len);
+        let puzzle: &[u8; PUZZLE_LEN] = chunk[..PUZZLE_LEN].try_into()?;
+        let intact = scan.legacy
+            || u32::from_le_bytes(chunk[PUZZLE_LEN..].try_into()?) == crc32fast::hash(puzzle);
+        match intact {
+            true => scan.puzzles.push(Puzzle::deserialize(puzzle)?),
+            false => scan.damaged += 1,
+        }
+        scan.good_len += record_len as u64;
+        rest = tail;
+    }
+    scan.dropped = bytes.len() as u64 - scan.good_len;
+    Ok(scan)
+}
+
+pub fn read(path: &Path) -> Result<Scan> {
+    scan(&fs::read(path)?).map_err(|e| anyhow!("{}: {e}", path.display()))
+}
+
+// Appends records to a database, picking up after the last whole one
+pub struct DbWriter {
+    file: File,
+    records: usize,
+    unsynced: usize,
+}
+
+impl DbWriter {
+    // Opens or creates the file. A record cut short at the end is cut off, damaged ones are
+    // left where they are, and files in the old format are rewritten with checksums first.
+    // Returns what was found.
+    pub fn open(path: &Path) -> Result<(Self, Scan)> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let bytes = match path.exists() {
+            true => fs::read(path)?,
+            false => Vec::new(),
+        };
+        // Empty, or a header that was cut short before anything could be added to it
+        let fresh = header().starts_with(&bytes);
+        let scan = match fresh {
+            true => Scan::default(),
+            false => scan(&bytes).map_err(|e| anyhow!("{}: {e}", path.display()))?,
+        };
+        if scan.legacy && scan.puzzles.is_empty() && !fresh {
+            return Err(anyhow!(
+                "{} isn't a puzzle database, leaving it alone",
+                path.display()
+            ));
+        }
+
+        if scan.legacy && !scan.puzzles.is_empty() {
+            // Written alongside and renamed over, so a crash leaves one file or the other
+            let tmp = path.with_extension("db.tmp");
+            let mut bytes = header().to_vec();
+            for puzzle in &scan.puzzles {
+                bytes.extend(record(puzzle)?);
+            }
+            let mut file = File::create(&tmp)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+            fs::rename(&tmp, path)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if fresh {
+            file.set_len(0)?;
+            file.write_all(&header())?;
+        } else if !scan.legacy {
+            file.set_len(scan.good_len)?;
+        }
+        file.seek(SeekFrom::End(0))?;
+        file.sync_all()?;
+
+        let writer = Self {
+            file,
+            records: scan.puzzles.len(),
+            unsynced: 0,
+        };
+        Ok((writer, scan))
+    }
+
+    // Records in the file, including ones from earlier runs
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    pub fn append(&mut self, puzzle: &Puzzle) -> Result<()> {
+        self.file.write_all(&record(puzzle)?)?;
+        self.records += 1;
+        self.unsynced += 1;
+        if self.unsynced >= SYNC_INTERVAL {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+}
+
+impl Drop for DbWriter {
+    // Best effort for early returns, callers that care sync themselves
+    fn drop(&mut self) {
+        let _ = self.file.sync_data();
+    }
+}
+
+// How long to keep capturing after clicking RANDOM before giving up on the board changing
+pub const CHANGE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Timeouts in a row before collecting gives up, e.g. the game was closed or covered
+const MAX_TIMEOUTS: usize = 3;
+
+// What happened over one run of the collector
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CollectStats {
+    // Boards saved to the database
+    pub saved: usize,
+    // Captures that still showed the previous board
+    pub stale: usize,
+    // Captures that didn't parse, usually a board caught mid redraw
+    pub parse_failures: usize,
+    // Boards that never showed up before CHANGE_TIMEOUT
+    pub timeouts: usize,
+    // Saved boards whose seed wasn't in the database yet
+    pub new_seeds: usize,
+}
+
+impl Display for CollectStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} saved, {} new seeds, {} stale captures, {} parse failures, {} timeouts",
+            self.saved, self.new_seeds, self.stale, self.parse_failures, self.timeouts
+        )
+    }
+}
+
+// Saves random boards into a database, making sure each one is a board it hasn't just saved.
+// The game takes a moment to redraw after RANDOM, so a capture can still show the last board.
+pub struct Collector {
+    pub stats: CollectStats,
+    pub timeout: Duration,
+    // Seeds already in the database or saved this run
+    seen: HashSet<u32>,
+}
+
+impl Collector {
+    pub fn new(existing: &[Puzzle]) -> Self {
+        Self {
+            stats: CollectStats::default(),
+            timeout: CHANGE_TIMEOUT,
+            seen: existing.iter().filter_map(|puzzle| puzzle.seed).collect(),
+        }
+    }
+
+    // Parse `boards` random boards into the database, stopping early if `stop` gets set (e.g. by
+    // Ctrl-C). The counts in `stats` cover everything up to an error too, and whatever was saved
+    // before it is synced, e.g. when the game window closes mid run.
+    pub fn run<S: ScreenSource, I: InputSink>(
+        &mut self,
+        dc: &mut DungeonCrawler<S, I>,
+        db: &mut DbWriter,
+        boards: usize,
+        stop: &AtomicBool,
+    ) -> Result<()> {
+        let result = self.collect(dc, db, boards, stop);
+        let synced = db.sync();
+        result.and(synced)
+    }
+
+    fn collect<S: ScreenSource, I: InputSink>(
+        &mut self,
+        dc: &mut DungeonCrawler<S, I>,
+        db: &mut DbWriter,
+        boards: usize,
+        stop: &AtomicBool,
+    ) -> Result<()> {
+        // Whatever is on screen now, so the first board can't be a stale copy of it either
+        let mut previous = dc.parse().ok();
+        let mut timeouts = 0;
+        while self.stats.saved < boards && !stop.load(Ordering::SeqCst) {
+            dc.random_board()
+                .map_err(|e| anyhow!("Couldn't get a new board: {e}"))?;
+            let Some(puzzle) = self.next_board(dc, previous)? else {
+                self.stats.timeouts += 1;
+                timeouts += 1;
+                if timeouts >= MAX_TIMEOUTS {
+                    return Err(anyhow!(
+                        "No new board after {timeouts} tries of {:?}",
+                        self.timeout
+                    ));
+                }
+                continue;
+            };
+            timeouts = 0;
+
+            db.append(&puzzle)?;
+            self.stats.saved += 1;
+            if puzzle.seed.is_some_and(|seed| self.seen.insert(seed)) {
+                self.stats.new_seeds += 1;
+            }
+            previous = Some(puzzle);
+        }
+        Ok(())
+    }
+
+    // Capture until the board differs from `previous`, or None once the timeout passes
+    fn next_board<S: ScreenSource, I: InputSink>(
+        &mut self,
+        dc: &mut DungeonCrawler<S, I>,
+        previous: Option<Puzzle>,
+    ) -> Result<Option<Puzzle>> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            match dc.parse() {
+                Ok(puzzle) if Some(puzzle) != previous => return Ok(Some(puzzle)),
+                Ok(_) => self.stats.stale += 1,
+                Err(_) => self.stats.parse_failures += 1,
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(dc.timing().screenshot_delay());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::simulator::{SimulatedGame, SimulatedInput, SimulatedScreen};
+
+    fn temp_db(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}_{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn puzzles() -> Vec<Puzzle> {
+        let seeds = [Some(22398633), None, Some(7)];
+        seeds
+            .into_iter()
+            .enumerate()
+            .map(|(i, seed)| Puzzle {
+                top_nums: [i as u8; 8],
+                left_nums: [8 - i as u8; 8],
+                seed,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn write(path: &Path, puzzles: &[Puzzle]) {
+        let (mut db, _) = DbWriter::open(path).unwrap();
+        for puzzle in puzzles {
+            db.append(puzzle).unwrap();
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_db("round_trip");
+        let puzzles = puzzles();
+        write(&path, &puzzles[..2]);
+        write(&path, &puzzles[2..]);
+
+        let scan = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(scan.puzzles, puzzles);
+        assert_eq!(scan.dropped, 0);
+        assert_eq!(scan.good_len, (HEADER_LEN + 3 * RECORD_LEN) as u64);
+    }
+
+    #[test]
+    fn resumes_after_torn_write() {
+        let path = temp_db("torn");
+        let puzzles = puzzles();
+        write(&path, &puzzles[..2]);
+
+        // Half a record, like a crash mid write
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.extend(&record(&puzzles[2]).unwrap()[..11]);
+        fs::write(&path, &bytes).unwrap();
+        assert_eq!(read(&path).unwrap().dropped, 11);
+
+        let (mut db, scan) = DbWriter::open(&path).unwrap();
+        assert_eq!((scan.puzzles.len(), scan.dropped), (2, 11));
+        db.append(&puzzles[2]).unwrap();
+        drop(db);
+
+        let scan = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(scan.puzzles, puzzles);
+        assert_eq!(scan.dropped, 0);
+    }
+
+    #[test]
+    fn skips_bad_checksum() {
+        let puzzles = puzzles();
+        let mut bytes = header().to_vec();
+        for puzzle in &puzzles {
+            bytes.extend(record(puzzle).unwrap());
+        }
+        bytes[HEADER_LEN + RECORD_LEN + 3] ^= 0x10;
+
+        let scan = scan(&bytes).unwrap();
+        assert_eq!(scan.puzzles, [puzzles[0], puzzles[2]]);
+        assert_eq!(scan.damaged, 1);
+        assert_eq!(scan.dropped, 0);
+    }
+
+    #[test]
+    fn keeps_records_after_a_damaged_one() {
+        let path = temp_db("damaged");
+        let puzzles = puzzles();
+        write(&path, &puzzles[..2]);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[HEADER_LEN + 3] ^= 0x10;
+        fs::write(&path, &bytes).unwrap();
+
+        let (mut db, scan) = DbWriter::open(&path).unwrap();
+        assert_eq!((scan.puzzles.len(), scan.damaged), (1, 1));
+        db.append(&puzzles[2]).unwrap();
+        drop(db);
+
+        let scan = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(scan.puzzles, puzzles[1..]);
+        assert_eq!(scan.damaged, 1);
+    }
+
+    // Too short to hold a puzzle and not a header either, so it isn't ours to wipe
+    #[test]
+    fn leaves_unknown_files_alone() {
+        let path = temp_db("unknown");
+        fs::write(&path, b"not a database").unwrap();
+        assert!(DbWriter::open(&path).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"not a database");
+
+        // Cut short while the header was being written
+        fs::write(&path, &header()[..6]).unwrap();
+        let (db, scan) = DbWriter::open(&path).unwrap();
+        assert_eq!(db.records(), 0);
+        drop(db);
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(scan.puzzles, []);
+        assert_eq!(bytes, header());
+    }
+
+    #[test]
+    fn converts_legacy_files() {
+        let path = temp_db("legacy");
+        let puzzles = puzzles();
+        let mut bytes = Vec::new();
+        for puzzle in &puzzles {
+            puzzle.serialize(&mut bytes).unwrap();
+        }
+        fs::write(&path, &bytes).unwrap();
+        assert!(read(&path).unwrap().legacy);
+
+        let (db, scan) = DbWriter::open(&path).unwrap();
+        assert!(scan.legacy);
+        assert_eq!(db.records(), 3);
+        drop(db);
+
+        let scan = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!scan.legacy);
+        assert_eq!(scan.puzzles, puzzles);
+    }
+
+    // Whole records are read and the rest is reported, whatever the format. Legacy files read
+    // the same as taking them 26 bytes at a time.
+    #[test_case(false, 0, 0)]
+    #[test_case(false, 3, 0)]
+    #[test_case(false, 3, 11)]
+    #[test_case(false, 1, PUZZLE_LEN - 1)]
+    #[test_case(true, 0, 0)]
+    #[test_case(true, 3, 0)]
+    #[test_case(true, 2, 11)]
+    #[test_case(true, 1, RECORD_LEN - 1)]
+    fn reads_whole_records(versioned: bool, records: usize, extra: usize) {
+        let puzzles = puzzles();
+        let encode = |puzzle: &Puzzle| match versioned {
+            true => record(puzzle).unwrap().to_vec(),
+            false => {
+                let mut bytes = Vec::new();
+                puzzle.serialize(&mut bytes).unwrap();
+                bytes
+            }
+        };
+        let mut bytes = match versioned {
+            true => header().to_vec(),
+            false => Vec::new(),
+        };
+        for puzzle in &puzzles[..records] {
+            bytes.extend(encode(puzzle));
+        }
+        // The start of one more, cut short
+        bytes.extend(&encode(&puzzles[records % puzzles.len()])[..extra]);
+
+        let scan = scan(&bytes).unwrap();
+        assert_eq!(scan.legacy, !versioned);
+        assert_eq!(scan.puzzles, puzzles[..records]);
+        assert_eq!(scan.dropped, extra as u64);
+        if !versioned {
+            let chunked: Vec<Puzzle> = bytes
+                .chunks_exact(PUZZLE_LEN)
+                .map(|chunk| Puzzle::deserialize(chunk.try_into().unwrap()).unwrap())
+                .collect();
+            assert_eq!(scan.puzzles, chunked);
+        }
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut bytes = header().to_vec();
+        bytes[4] += 1;
+        assert!(scan(&bytes).is_err());
+    }
+
+    fn sim_collector() -> (
+        SimulatedGame,
+        DungeonCrawler<SimulatedScreen, SimulatedInput>,
+    ) {
+        let sim = SimulatedGame::from_screenshots(Path::new("monster_refs"), 3).unwrap();
+        let dc = DungeonCrawler::with_backend(sim.screen(), sim.input()).unwrap();
+        (sim, dc)
+    }
+
+    #[test]
+    fn collect_stops_when_asked() {
+        let path = temp_db("collect");
+        let (_sim, mut dc) = sim_collector();
+        let (mut db, _) = DbWriter::open(&path).unwrap();
+
+        let mut collector = Collector::new(&[]);
+        collector
+            .run(&mut dc, &mut db, 4, &AtomicBool::new(false))
+            .unwrap();
+        assert_eq!(collector.stats.saved, 4);
+
+        let mut collector = Collector::new(&[]);
+        collector
+            .run(&mut dc, &mut db, 4, &AtomicBool::new(true))
+            .unwrap();
+        assert_eq!(collector.stats, CollectStats::default());
+        drop(db);
+
+        let scan = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(scan.puzzles.len(), 4);
+    }
+
+    #[test]
+    fn waits_out_stale_captures() {
+        let path = temp_db("stale");
+        let (sim, mut dc) = sim_collector();
+        // The screen lags well behind the screenshot delay, so the first few captures after
+        // each click still show the last board
+        sim.set_latency(dc.timing().screenshot_delay() * 4);
+        let (mut db, _) = DbWriter::open(&path).unwrap();
+
+        let mut collector = Collector::new(&[]);
+        collector
+            .run(&mut dc, &mut db, 3, &AtomicBool::new(false))
+            .unwrap();
+        drop(db);
+        let scan = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let stats = collector.stats;
+        assert_eq!(stats.saved, 3);
+        assert!(stats.stale >= 3, "{stats}");
+        assert_eq!(stats.timeouts, 0, "{stats}");
+        for pair in scan.puzzles.windows(2) {
+            assert_ne!(pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn collect_stops_when_the_window_closes() {
+        let path = temp_db("closed");
+        let (sim, mut dc) = sim_collector();
+        let (mut db, _) = DbWriter::open(&path).unwrap();
+        let mut collector = Collector::new(&[]);
+        collector
+            .run(&mut dc, &mut db, 2, &AtomicBool::new(false))
+            .unwrap();
+
+        sim.close();
+        let error = collector
+            .run(&mut dc, &mut db, 4, &AtomicBool::new(false))
+            .unwrap_err();
+        assert!(error.to_string().contains("Game window closed"), "{error}");
+        assert_eq!(collector.stats.saved, 2);
+        drop(db);
+
+        let scan = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((scan.puzzles.len(), scan.dropped), (2, 0));
+    }
+
+    #[test]
+    fn counts_new_seeds() {
+        let (_sim, mut dc) = sim_collector();
+        let known = dc.parse().unwrap();
+        let path = temp_db("new_seeds");
+        let (mut db, _) = DbWriter::open(&path).unwrap();
+
+        let mut collector = Collector::new(&[known]);
+        collector
+            .run(&mut dc, &mut db, 6, &AtomicBool::new(false))
+            .unwrap();
+        drop(db);
+        let scan = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut seeds: HashSet<u32> = scan.puzzles.iter().filter_map(|p| p.seed).collect();
+        seeds.remove(&known.seed.unwrap());
+        assert_eq!(collector.stats.new_seeds, seeds.len());
+    }
+
+    #[test]
+    fn gives_up_when_the_board_never_changes() {
+        let (sim, mut dc) = sim_collector();
+        let path = temp_db("frozen");
+        let (mut db, _) = DbWriter::open(&path).unwrap();
+        sim.set_latency(Duration::from_secs(60));
+
+        let mut collector = Collector::new(&[]);
+        collector.timeout = Duration::from_millis(30);
+        let result = collector.run(&mut dc, &mut db, 2, &AtomicBool::new(false));
+        drop(db);
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(collector.stats.timeouts, MAX_TIMEOUTS);
+        assert_eq!(collector.stats.saved, 0);
+    }
+}
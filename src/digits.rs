@@ -0,0 +1,178 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use image::{self, GenericImageView, Rgba, RgbaImage};
+use std::sync::OnceLock;
+
+use crate::assets::AssetLocator;
+
+// Relative to the sprites directory
+pub const LARGE_FONT_PATH: &str = "fonts/numbers.png";
+
+// Location of each digit within the font sheet. Digits are laid out left to right, with one
+// row per colouring.
+pub const DIGIT_OFFSETS: [u32; 9] = [4, 32, 60, 88, 116, 144, 172, 200, 228];
+pub const DIGIT_SIZE: (u32, u32) = (24, 28);
+const COLOUR_ROWS: [(DigitColour, u32); 2] = [(DigitColour::Red, 0), (DigitColour::Brown, 32)];
+
+// How far (in pixels) to slide each template around the expected digit position
+const SEARCH_RADIUS: i32 = 2;
+
+// Matches scoring below this are rejected rather than guessed at
+const MIN_SCORE: f32 = 0.6;
+
+// One bit per pixel, one u32 per row. Digits are at most 24 pixels wide.
+type GlyphMask = [u32; DIGIT_SIZE.1 as usize];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DigitColour {
+    Red,
+    Brown,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DigitMatch {
+    pub digit: u8,
+    pub colour: DigitColour,
+    // Intersection over union of the template and the on-screen glyph, from 0 to 1
+    pub score: f32,
+}
+
+struct Template {
+    digit: u8,
+    colour: DigitColour,
+    mask: GlyphMask,
+    ink: u32,
+}
+
+pub struct DigitTemplates {
+    templates: Vec<Template>,
+}
+
+impl DigitTemplates {
+    pub fn load(assets: &AssetLocator) -> Result<Self> {
+        let sheet = image::open(assets.sprites(LARGE_FONT_PATH))?.to_rgba8();
+        Self::from_font_sheet(&sheet)
+    }
+
+    // Cut each digit out of the font sheet. Cells without any ink (e.g. a colouring the
+    // sheet doesn't provide for a given digit) are skipped.
+    pub fn from_font_sheet(sheet: &RgbaImage) -> Result<Self> {
+        let mut templates = Vec::new();
+        for (colour, row_y) in COLOUR_ROWS {
+            for (digit, &x) in DIGIT_OFFSETS.iter().enumerate() {
+                let mask = glyph_mask(sheet, x as i32, row_y as i32, colour);
+                let ink = mask_ink(&mask);
+                if ink > 0 {
+                    templates.push(Template {
+                        digit: digit as u8,
+                        colour,
+                        mask,
+                        ink,
+                    });
+                }
+            }
+        }
+
+        if templates.is_empty() {
+            return Err(anyhow!("Font sheet contains no digits"));
+        }
+
+        Ok(Self { templates })
+    }
+
+    // Recognise the digit whose cell has its top left corner at roughly (x, y) in img
+    pub fn recognise<I>(&self, img: &I, x: u32, y: u32) -> Result<DigitMatch>
+    where
+        I: GenericImageView<Pixel = Rgba<u8>>,
+    {
+        let mut best: Option<DigitMatch> = None;
+        for dy in -SEARCH_RADIUS..=SEARCH_RADIUS {
+            for dx in -SEARCH_RADIUS..=SEARCH_RADIUS {
+                let (cx, cy) = (x as i32 + dx, y as i32 + dy);
+                for colour in [DigitColour::Red, DigitColour::Brown] {
+                    let mask = glyph_mask(img, cx, cy, colour);
+                    if mask_ink(&mask) == 0 {
+                        continue;
+                    }
+                    for template in self.templates.iter().filter(|t| t.colour == colour) {
+                        let score = iou(&template.mask, template.ink, &mask);
+                        if best.is_none_or(|b| score > b.score) {
+                            best = Some(DigitMatch {
+                                digit: template.digit,
+                                colour,
+                                score,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        match best {
+            None => Err(anyhow!("No digit found at {x},{y}")),
+            Some(m) if m.score < MIN_SCORE => Err(anyhow!(
+                "Digit at {x},{y} is unrecognised (best guess {} scored {:.2})",
+                m.digit,
+                m.score
+            )),
+            Some(m) => Ok(m),
+        }
+    }
+}
+
+// The font sheet is only decoded once per run. None if it couldn't be loaded, e.g. when the
+// game textures haven't been extracted.
+pub fn templates() -> Option<&'static DigitTemplates> {
+    static TEMPLATES: OnceLock<Option<DigitTemplates>> = OnceLock::new();
+    TEMPLATES
+        .get_or_init(|| AssetLocator::global().and_then(DigitTemplates::load).ok())
+        .as_ref()
+}
+
+// Both colourings share the green and blue channels closely enough to threshold together,
+// the red channel tells them apart.
+pub fn ink_colour(p: [u8; 4]) -> Option<DigitColour> {
+    let [r, g, b, _a] = p;
+    if g != 91 || !(69..=78).contains(&b) {
+        None
+    } else if r > 200 {
+        Some(DigitColour::Red)
+    } else {
+        Some(DigitColour::Brown)
+    }
+}
+
+// Threshold a digit sized cell into a bitmask of the given colour's ink. Pixels outside the
+// image count as background.
+fn glyph_mask<I>(img: &I, x: i32, y: i32, colour: DigitColour) -> GlyphMask
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    let mut mask = GlyphMask::default();
+    for (j, row) in mask.iter_mut().enumerate() {
+        for i in 0..DIGIT_SIZE.0 {
+            let (px, py) = (x + i as i32, y + j as i32);
+            if px < 0 || py < 0 || px as u32 >= img.width() || py as u32 >= img.height() {
+                continue;
+            }
+            if ink_colour(img.get_pixel(px as u32, py as u32).0) == Some(colour) {
+                *row |= 1 << i;
+            }
+        }
+    }
+    mask
+}
+
+fn mask_ink(mask: &GlyphMask) -> u32 {
+    mask.iter().map(|row| row.count_ones()).sum()
+}
+
+fn iou(template: &GlyphMask, template_ink: u32, mask: &GlyphMask) -> f32 {
+    let intersection: u32 = template
+        .iter()
+        .zip(mask)
+        .map(|(a, b)| (a & b).count_ones())
+        .sum();
+    let union = template_ink + mask_ink(mask) - intersection;
+    intersection as f32 / union as f32
+}
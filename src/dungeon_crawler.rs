@@ -1,57 +1,86 @@
-use std::{path::Path, thread, time::Duration};
-
-use enigo::{Button, Coordinate, Direction, Enigo, Mouse, Settings};
-use xcap::{
-    image::{GenericImageView, RgbaImage},
-    Window,
+use std::{
+    path::Path,
+    thread,
+    time::{Duration, Instant},
 };
 
-use anyhow::anyhow;
-use anyhow::{Context, Result};
-
-use crate::puzzle::Puzzle;
+use image::{GenericImageView, RgbaImage};
 
+use anyhow::anyhow;
+use anyhow::Result;
+
+#[cfg(feature = "automation")]
+use crate::backend::{EnigoInput, XcapScreen};
+use crate::backend::{InputSink, Key, ScreenSource};
+use crate::input_plan::{self, AdaptiveDelay, EntryReport, RetryPolicy, WallMismatch};
+use crate::layout::Layout;
+use crate::puzzle::{Puzzle, Tile};
+use crate::solve::{BackTracker, Solver};
+use crate::timing::{self, TimingProfile};
+#[cfg(feature = "automation")]
+use crate::util::Versioned;
+use crate::util::{debug_images, save_debug_image};
+
+#[cfg(feature = "automation")]
 const GAME_TITLE: &str = "Last Call BBS";
-const GAME_CROP: (u32, u32) = (335, 459);
 
-const CLICK_DELAY: u64 = 20;
-const SCREENSHOT_DELAY: u64 = 10;
+// Calibration watches a small area around the reset button for the menu opening and closing
+const MENU_WATCH_SIZE: (u32, u32) = (40, 12);
+// Give up on a redraw that takes longer than this
+const CALIBRATION_TIMEOUT: Duration = Duration::from_secs(2);
 
-const RANDOM_OFFSET: (u32, u32) = (285, 111);
-
-pub struct DungeonCrawler {
-    enigo: Enigo,
+// Drives the game through a screen and a mouse, the real ones with the automation feature or
+// any of the others in backend.rs
+pub struct DungeonCrawler<S: ScreenSource, I: InputSink> {
+    screen: S,
+    input: I,
     dnd_img: RgbaImage,
-    window: Window,
     dnd_offset: (u32, u32),
-    win_offset: (u32, u32),
+    // Where the mouse was last clicked, relative to the DnD subwindow
+    cursor: (u32, u32),
+    // The board as last parsed, for checking walls against
+    board: Option<Puzzle>,
+    // Whether walls can be painted by dragging, see detect_drag
+    drag: bool,
+    delay: AdaptiveDelay,
+    timing: TimingProfile,
+    pub retry: RetryPolicy,
+    // Where everything is in the DnD subwindow
+    pub layout: Layout,
 }
 
-impl DungeonCrawler {
+#[cfg(feature = "automation")]
+impl DungeonCrawler<XcapScreen, EnigoInput> {
+    // Uses the timing profile from the calibrate command if there is one
     pub fn new() -> Result<Self> {
-        let plate = xcap::image::open("plate.png")?.to_rgba8();
+        let timing = TimingProfile::load_or_default(&TimingProfile::path())?;
 
         // Find 'Last Call BBS' window
-        let windows = Window::all().unwrap();
-        let window = windows
-            .iter()
-            .find(|win| win.title() == GAME_TITLE)
-            .context("Failed to find Last Call BBS Window")?
-            .clone();
+        let screen = XcapScreen::find(GAME_TITLE)?;
+        let input = EnigoInput::new(screen.position())?;
+        let mut dc = Self::with_backend(screen, input)?;
+        dc.set_timing(timing);
+        Ok(dc)
+    }
+}
+
+impl<S: ScreenSource, I: InputSink> DungeonCrawler<S, I> {
+    pub fn with_backend(mut screen: S, input: I) -> Result<Self> {
+        let plate = image::open("plate.png")?.to_rgba8();
 
         // Capture the screen
-        let img = window.capture_image()?;
-        #[cfg(debug_assertions)]
-        img.save("game.png")?;
+        let img = screen.capture()?;
+        save_debug_image(&img, "game.png");
 
-        let dnd_offset = find_dnd_offset(&img)?;
+        let layout = Layout::global()?.clone();
+        let dnd_offset = find_dnd_offset(&img, &layout)?;
 
+        let (width, height) = layout.window.size;
         let dnd_img = img
-            .view(dnd_offset.0, dnd_offset.1, GAME_CROP.0, GAME_CROP.1)
+            .view(dnd_offset.0, dnd_offset.1, width, height)
             .to_image();
 
-        #[cfg(debug_assertions)]
-        dnd_img.save("dnd.png")?;
+        save_debug_image(&dnd_img, "dnd.png");
 
         let mut mask = plate.clone();
         mask.pixels_mut()
@@ -61,8 +90,7 @@ impl DungeonCrawler {
                 false => a.0 = [255; 4],
             });
 
-        #[cfg(debug_assertions)]
-        mask.save("mask.png").unwrap();
+        save_debug_image(&mask, "mask.png");
 
         let mut dnd_img = dnd_img.clone();
         for (dst, src) in dnd_img.pixels_mut().zip(plate.pixels()) {
@@ -72,97 +100,326 @@ impl DungeonCrawler {
             }
         }
 
-        #[cfg(debug_assertions)]
-        dnd_img.save("dnd_img_alpha.png").unwrap();
+        save_debug_image(&dnd_img, "dnd_img_alpha.png");
 
-        let mut settings = Settings::default();
-        settings.linux_delay = 0;
         // Locate DnD subwindow
         let mut dc = Self {
-            enigo: Enigo::new(&settings)?,
+            screen,
+            input,
             dnd_img,
             dnd_offset,
-            win_offset: (window.x() as u32, window.y() as u32),
-            window,
+            cursor: (0, 0),
+            board: None,
+            drag: false,
+            delay: AdaptiveDelay::new(TimingProfile::default().click_delay()),
+            timing: TimingProfile::default(),
+            retry: RetryPolicy::default(),
+            layout,
         };
+        dc.input.set_delay(dc.delay.current);
 
         // Force a click to capture the mouse in the application
-        dc.click(0, 0)?;
+        dc.click(dc.layout.controls.neutral)?;
 
         Ok(dc)
     }
 
-    fn click(&mut self, x: u32, y: u32) -> Result<()> {
-        let cx = (x + self.win_offset.0 + self.dnd_offset.0) as i32;
-        let cy = (y + self.win_offset.1 + self.dnd_offset.1) as i32;
-        self.enigo.move_mouse(cx, cy, Coordinate::Abs)?;
-        thread::sleep(Duration::from_millis(CLICK_DELAY / 2));
-        self.enigo.button(Button::Left, Direction::Click)?;
-        thread::sleep(Duration::from_millis(CLICK_DELAY / 2));
+    pub fn timing(&self) -> TimingProfile {
+        self.timing
+    }
+
+    pub fn set_timing(&mut self, timing: TimingProfile) {
+        self.timing = timing;
+        self.delay = AdaptiveDelay::new(timing.click_delay());
+        self.input.set_delay(self.delay.current);
+    }
+
+    // Coordinates are relative to the DnD subwindow
+    fn click(&mut self, (x, y): (u32, u32)) -> Result<()> {
+        self.cursor = (x, y);
+        self.input
+            .click(x + self.dnd_offset.0, y + self.dnd_offset.1)
+    }
+
+    fn drag(&mut self, from: (u32, u32), to: (u32, u32)) -> Result<()> {
+        let (ox, oy) = self.dnd_offset;
+        self.input
+            .drag((from.0 + ox, from.1 + oy), (to.0 + ox, to.1 + oy))?;
+        self.cursor = to;
         Ok(())
     }
 
-    pub fn random_board(&mut self) {
-        self.click(RANDOM_OFFSET.0, RANDOM_OFFSET.1).unwrap();
-        thread::sleep(Duration::from_millis(SCREENSHOT_DELAY));
-        self.dnd_img = self
-            .window
-            .capture_image()
-            .expect("failed to capture image")
-            .view(
-                self.dnd_offset.0,
-                self.dnd_offset.1,
-                GAME_CROP.0,
-                GAME_CROP.1,
-            )
-            .to_image();
+    // Cursor position in board cells, for planning from
+    fn cursor_cell(&self) -> (f32, f32) {
+        self.layout.board.cell_at(self.cursor)
+    }
+
+    // The DnD subwindow, as it is now
+    fn capture_window(&mut self) -> Result<RgbaImage> {
+        let img = self.screen.capture()?;
+        let (width, height) = self.layout.window.size;
+        Ok(img
+            .view(self.dnd_offset.0, self.dnd_offset.1, width, height)
+            .to_image())
+    }
+
+    // Walls currently on the board, if it has been parsed
+    fn placed_walls(&mut self) -> Result<Option<u64>> {
+        let Some(board) = self.board else {
+            return Ok(None);
+        };
+        thread::sleep(self.timing.screenshot_delay());
+        let img = self.capture_window()?;
+        let img = img.view(0, 0, img.width(), img.height());
+        Ok(Some(board.placed_walls(img, &self.layout.board)))
+    }
+
+    // Walls on the board once it shows `expected`, or whatever it shows after the retry policy's
+    // settle time. A slow redraw looks just like a dropped click on the first capture.
+    fn settled_walls(&mut self, expected: u64) -> Result<Option<u64>> {
+        let t0 = Instant::now();
+        loop {
+            let placed = self.placed_walls()?;
+            if placed.is_none_or(|placed| placed == expected) || t0.elapsed() > self.retry.settle {
+                return Ok(placed);
+            }
+        }
+    }
+
+    pub fn random_board(&mut self) -> Result<()> {
+        self.click(self.layout.controls.random)?;
+        self.board = None;
+        thread::sleep(self.timing.screenshot_delay());
+        self.dnd_img = self.capture_window()?;
+        Ok(())
     }
 
     pub fn parse(&mut self) -> Result<Puzzle> {
-        let img = self.window.capture_image()?;
-        let img = img.view(
-            self.dnd_offset.0,
-            self.dnd_offset.1,
-            GAME_CROP.0,
-            GAME_CROP.1,
-        );
-        let puzzle = Puzzle::from_image(img)?;
+        let img = self.capture_window()?;
+        let img = img.view(0, 0, img.width(), img.height());
+        let puzzle = Puzzle::from_image_with(img, &self.layout)?;
 
-        #[cfg(debug_assertions)]
-        puzzle.draw_parsing_overlay(img);
+        if debug_images() {
+            puzzle.draw_parsing_overlay(img, &self.layout);
+        }
 
+        self.board = Some(puzzle);
         Ok(puzzle)
     }
 
     pub fn reset_solution(&mut self) -> Result<()> {
-        self.click(74, 33)?;
-        thread::sleep(Duration::from_millis(250));
-        self.click(74, 93)
+        self.click(self.layout.controls.menu)?;
+        thread::sleep(self.timing.menu_delay());
+        self.click(self.layout.controls.reset)
     }
 
     pub fn place_wall(&mut self, x: u8, y: u8) -> Result<()> {
-        self.click(x as u32 * 33 + 66, y as u32 * 33 + 191)
+        self.click(self.layout.board.click_point(x as usize, y as usize))
     }
 
-    pub fn enter_solution(&mut self, solution: u64) -> Result<()> {
+    fn paint_walls(&mut self, from: (u8, u8), to: (u8, u8)) -> Result<()> {
+        let board = &self.layout.board;
+        let (from, to) = (
+            board.click_point(from.0 as usize, from.1 as usize),
+            board.click_point(to.0 as usize, to.1 as usize),
+        );
+        self.drag(from, to)
+    }
+
+    // Mark a cell as definitely empty, like the game's right click
+    pub fn mark_empty(&mut self, x: u8, y: u8) -> Result<()> {
+        let (cx, cy) = self.layout.board.click_point(x as usize, y as usize);
+        self.cursor = (cx, cy);
+        self.input
+            .right_click(cx + self.dnd_offset.0, cy + self.dnd_offset.1)
+    }
+
+    // Type in a seed to play that board, for games that allow it
+    pub fn enter_seed(&mut self, seed: u32) -> Result<()> {
+        let entry = self
+            .layout
+            .controls
+            .seed_entry
+            .ok_or_else(|| anyhow!("This game has no way to type in a seed"))?;
+        self.click(entry)?;
+        for c in seed.to_string().chars() {
+            self.input.key(Key::Char(c))?;
+        }
+        self.input.key(Key::Enter)?;
+        self.board = None;
+        thread::sleep(self.timing.screenshot_delay());
+        Ok(())
+    }
+
+    // Try painting two walls with one drag on the parsed board, then take them off again.
+    // Entering solutions sticks to clicks unless this finds dragging works.
+    pub fn detect_drag(&mut self) -> Result<bool> {
+        let board = self.board.ok_or_else(|| anyhow!("Parse a board first"))?;
+        let empty = |x: usize, y: usize| board.tiles[y][x] == Tile::Empty;
+        let pair = (0..8)
+            .flat_map(|y| (0..7).map(move |x| (x, y)))
+            .find(|&(x, y)| empty(x, y) && empty(x + 1, y))
+            .ok_or_else(|| anyhow!("No room on the board to try dragging"))?;
+        let (from, to) = (
+            (pair.0 as u8, pair.1 as u8),
+            (pair.0 as u8 + 1, pair.1 as u8),
+        );
+
+        self.drag = match self.paint_walls(from, to) {
+            Ok(()) => {
+                let painted = self.placed_walls()?.unwrap_or(0);
+                for (x, y) in input_plan::cells(painted) {
+                    self.place_wall(x, y)?;
+                }
+                painted == input_plan::cell_bit(from.0, from.1) | input_plan::cell_bit(to.0, to.1)
+            }
+            Err(_) => false,
+        };
+        Ok(self.drag)
+    }
+
+    pub fn enter_solution(&mut self, solution: u64) -> Result<EntryReport> {
         println!("{solution}");
-        for i in 0..64 {
-            let y = i / 8;
-            let x = i % 8;
-            if solution & (1 << (63 - i)) != 0 {
+        let t0 = Instant::now();
+
+        let start = self.cursor_cell();
+        let strokes = input_plan::plan(solution, start, self.drag);
+        self.input.set_delay(self.delay.current);
+        let mut report = EntryReport {
+            walls: solution.count_ones(),
+            strokes: strokes.len(),
+            travel: input_plan::travel(start, &strokes),
+            delay: self.delay.current,
+            ..Default::default()
+        };
+
+        for stroke in strokes {
+            match stroke.is_click() {
+                true => self.place_wall(stroke.from.0, stroke.from.1)?,
+                false => self.paint_walls(stroke.from, stroke.to)?,
+            }
+        }
+
+        // Check what actually landed, if the board has been parsed. Dropped inputs mean the
+        // game needs more time between them, so slow down and click whatever is wrong again.
+        // Otherwise try going a bit faster next time.
+        while let Some(placed) = self.settled_walls(solution)? {
+            let wrong = placed ^ solution;
+            if wrong == 0 {
+                if report.retries == 0 {
+                    self.delay.succeeded();
+                }
+                break;
+            }
+            if report.retries == self.retry.retries {
+                return Err(WallMismatch {
+                    expected: solution,
+                    placed,
+                    attempts: report.retries + 1,
+                }
+                .into());
+            }
+
+            report.retries += 1;
+            report.missed += wrong.count_ones();
+            self.delay.failed();
+            self.input.set_delay(self.delay.current);
+            for (x, y) in input_plan::cells(wrong) {
                 self.place_wall(x, y)?;
             }
         }
-        self.click(0, 0)?;
-        thread::sleep(Duration::from_millis(10));
-        Ok(())
+
+        self.click(self.layout.controls.neutral)?;
+        thread::sleep(self.timing.screenshot_delay());
+        report.elapsed = t0.elapsed();
+        Ok(report)
+    }
+
+    // Part of the DnD subwindow, as it is now
+    fn capture_region(&mut self, (x, y, w, h): (u32, u32, u32, u32)) -> Result<RgbaImage> {
+        let img = self.screen.capture()?;
+        let (ox, oy) = self.dnd_offset;
+        Ok(img.view(ox + x, oy + y, w, h).to_image())
     }
 
-    pub fn save_board_image(&self, crop: Option<(u32, u32, u32, u32)>, path: &Path) -> Result<()> {
-        let img = self.window.capture_image()?;
+    // Time from an input to the region of the window it affects changing
+    fn redraw_time(
+        &mut self,
+        region: (u32, u32, u32, u32),
+        input: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<Duration> {
+        let before = self.capture_region(region)?;
+        let t0 = Instant::now();
+        input(self)?;
+        loop {
+            if self.capture_region(region)? != before {
+                return Ok(t0.elapsed());
+            }
+            if t0.elapsed() > CALIBRATION_TIMEOUT {
+                return Err(anyhow!(
+                    "Nothing changed at {region:?} within {CALIBRATION_TIMEOUT:?}"
+                ));
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // Measure how long the game takes to redraw after toggling a wall, opening and closing the
+    // menu and asking for a new board, `samples` times each. The slowest of each, with some
+    // headroom, becomes the new timing profile. Needs a parsed board with an empty tile, and
+    // leaves a different board showing.
+    pub fn calibrate(&mut self, samples: usize) -> Result<TimingProfile> {
+        let board = self.board.ok_or_else(|| anyhow!("Parse a board first"))?;
+        let (x, y) = (0..64)
+            .map(|i| (i % 8, i / 8))
+            .find(|&(x, y)| board.tiles[y as usize][x as usize] == Tile::Empty)
+            .ok_or_else(|| anyhow!("No empty tile to calibrate with"))?;
+        let tile = self.layout.board.tile_rect(x as usize, y as usize);
+        let seed = self.layout.seed.region.rect();
+        let reset = self.layout.controls.reset;
+        let (w, h) = MENU_WATCH_SIZE;
+        let menu_watch = (reset.0 - w / 2, reset.1 - h / 2, w, h);
+
+        // Inputs go as fast as possible, so only the game's redraw gets measured
+        self.input.set_delay(Duration::ZERO);
+        let mut clicks = Vec::new();
+        let mut menus = Vec::new();
+        let mut boards = Vec::new();
+        for _ in 0..samples {
+            // Wall on, wall off
+            for _ in 0..2 {
+                clicks.push(self.redraw_time(tile, |dc| dc.place_wall(x, y))?);
+            }
+            menus.push(self.redraw_time(menu_watch, |dc| dc.click(dc.layout.controls.menu))?);
+            menus.push(self.redraw_time(menu_watch, |dc| dc.click(dc.layout.controls.reset))?);
+        }
+        for _ in 0..samples {
+            boards.push(self.redraw_time(seed, |dc| dc.click(dc.layout.controls.random))?);
+        }
+        self.board = None;
+
+        let profile = TimingProfile {
+            click_delay_ms: timing::padded_ms(&clicks),
+            screenshot_delay_ms: timing::padded_ms(&boards),
+            menu_delay_ms: timing::padded_ms(&menus),
+            ..self.timing
+        };
+        self.set_timing(profile);
+        Ok(profile)
+    }
+
+    pub fn save_board_image(
+        &mut self,
+        crop: Option<(u32, u32, u32, u32)>,
+        path: &Path,
+    ) -> Result<()> {
+        let img = self.screen.capture()?;
         let (x, y, w, h) = match crop {
             Some((x, y, w, h)) => (x, y, w, h),
-            None => (self.dnd_offset.0 + 10, self.dnd_offset.1 + 135, 310, 310),
+            None => {
+                let (x, y, w, h) = self.layout.board.picture.rect();
+                (self.dnd_offset.0 + x, self.dnd_offset.1 + y, w, h)
+            }
         };
         img.view(x, y, w, h).to_image().save(path)?;
 
@@ -170,25 +427,91 @@ impl DungeonCrawler {
     }
 }
 
-fn find_dnd_offset(image: &RgbaImage) -> Result<(u32, u32)> {
+// Solve whatever board is showing, then move on to a random one. Stops after `boards` boards,
+// or never if that's None.
+pub fn solve_boards<S: ScreenSource, I: InputSink>(
+    dc: &mut DungeonCrawler<S, I>,
+    boards: Option<usize>,
+    pause: Duration,
+) -> Result<()> {
+    let bt = BackTracker {};
+
+    for board in 0..boards.unwrap_or(usize::MAX) {
+        let puzzle = dc.parse()?;
+        if board == 0 {
+            let drag = dc.detect_drag()?;
+            println!("drag painting: {}", if drag { "yes" } else { "no" });
+        }
+        println!("{puzzle}");
+        println!("seed: {:?}", puzzle.seed);
+        let now = Instant::now();
+        let solutions = bt.solve(&puzzle);
+        println!(
+            "{} solutions found in {:.2}s",
+            solutions.len(),
+            now.elapsed().as_secs_f32()
+        );
+
+        // output dir
+        // let path = Path::new("script_output").join("solve_bt");
+        // let mut count = 0;
+        // for solution in solutions.into_iter().step_by(5000) {
+        // for solution in solutions.into_iter() {
+        // dc.enter_solution(solution)?;
+        // dc.save_board_image(None, &path.join(format!("{count:>04}.png")))?;
+        // dc.reset_solution()?;
+        // count += 1;
+        // }
+
+        match solutions.len() {
+            0 => println!("  no solution"),
+            1 => {
+                // A board that won't go in right is left for the next one, anything else
+                // going wrong stops the loop
+                match dc.enter_solution(*solutions.last().unwrap()) {
+                    Ok(report) => println!("  {report}"),
+                    Err(e) if e.is::<WallMismatch>() => {
+                        println!("  {e}");
+                        dc.reset_solution()?;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            _ => {
+                println!("  multiple solutions");
+                for sol in solutions {
+                    println!("    {:064b}", sol);
+                }
+            }
+        }
+
+        thread::sleep(pause);
+        dc.random_board()?;
+    }
+
+    Ok(())
+}
+
+// For the controls nobody has measured from the game yet, rather than clicking a guess
+fn find_dnd_offset(image: &RgbaImage, layout: &Layout) -> Result<(u32, u32)> {
     // Pattern of image bytes to uniquely locate the DnD subwindow. The chosen pattern
-    // exists at 299,0 relative to the top left corner of the subwindow.
+    // exists at the layout's marker relative to the top left corner of the subwindow.
     const PATTERN_LEN: usize = 12;
     const PATTERN: [u8; PATTERN_LEN] = [69, 52, 56, 255, 237, 169, 135, 255, 181, 147, 131, 255];
-    const PATTERN_OFFSET: (u32, u32) = (299, 0);
+    let marker = layout.window.marker;
 
     // Iterate over sliding window of 12 bytes, considering only every 4th window (pixel alignment)
     let matches = image
-        .array_windows::<PATTERN_LEN>()
+        .windows(PATTERN_LEN)
         .step_by(4)
         .enumerate()
-        .filter_map(|(i, &chunk)| {
+        .filter_map(|(i, chunk)| {
             if chunk == PATTERN {
                 // Given the window index, calculate x and y offsets. Wrapping
                 // subtraction here simplifies the bounds check later
                 Some((
-                    (i as u32 % image.width()).wrapping_sub(PATTERN_OFFSET.0),
-                    (i as u32 / image.width()).wrapping_sub(PATTERN_OFFSET.1),
+                    (i as u32 % image.width()).wrapping_sub(marker.0),
+                    (i as u32 / image.width()).wrapping_sub(marker.1),
                 ))
             } else {
                 None
@@ -200,7 +523,7 @@ fn find_dnd_offset(image: &RgbaImage) -> Result<(u32, u32)> {
         0 => Err(anyhow!("Not found")),
         1 => {
             let (x, y) = matches[0];
-            if x > 625 || y > 80 {
+            if x > layout.window.max_offset.0 || y > layout.window.max_offset.1 {
                 Err(anyhow!("Out of bounds"))
             } else {
                 Ok(matches[0])
@@ -209,3 +532,183 @@ fn find_dnd_offset(image: &RgbaImage) -> Result<(u32, u32)> {
         n => Err(anyhow!("Multiple matches ({n})")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use image::{imageops::replace, open, Rgba};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::backend::{ClickLog, ReplayScreen};
+
+    // Where the DnD subwindow sits in the fake desktop window
+    const DND_OFFSET: (u32, u32) = (40, 30);
+
+    // Put a reference screenshot of the DnD subwindow inside a bigger window
+    fn window(monster: &str) -> RgbaImage {
+        let dnd = open(format!("monster_refs/{monster}.png"))
+            .unwrap()
+            .to_rgba8();
+        let mut window = RgbaImage::from_pixel(800, 600, Rgba([0, 0, 0, 255]));
+        replace(&mut window, &dnd, DND_OFFSET.0 as i64, DND_OFFSET.1 as i64);
+        window
+    }
+
+    fn in_window((x, y): (u32, u32)) -> (u32, u32) {
+        (x + DND_OFFSET.0, y + DND_OFFSET.1)
+    }
+
+    fn replay(monsters: &[&str]) -> DungeonCrawler<ReplayScreen, ClickLog> {
+        let frames = monsters.iter().map(|monster| window(monster)).collect();
+        DungeonCrawler::with_backend(ReplayScreen::new(frames).unwrap(), ClickLog::new()).unwrap()
+    }
+
+    #[test]
+    fn locate_and_parse() {
+        let mut dc = replay(&["goblin"]);
+        assert_eq!(dc.dnd_offset, DND_OFFSET);
+        // The first click grabs the mouse
+        assert_eq!(dc.input.clicks, [in_window((0, 0))]);
+
+        let puzzle = dc.parse().unwrap();
+        assert_eq!(puzzle.seed, Some(22398633));
+        assert_eq!(dc.screen.captures(), 2);
+    }
+
+    // The subwindow pasted at each of `places` in a blank game window
+    #[test_case(&[(0, 0)], Ok((0, 0)))]
+    #[test_case(&[DND_OFFSET], Ok(DND_OFFSET))]
+    #[test_case(&[(625, 80)], Ok((625, 80)))]
+    #[test_case(&[(626, 30)], Err("Out of bounds"))]
+    #[test_case(&[(40, 81)], Err("Out of bounds"))]
+    #[test_case(&[], Err("Not found"))]
+    #[test_case(&[(0, 0), (0, 470)], Err("Multiple matches (2)"))]
+    fn locate_window(places: &[(u32, u32)], expected: Result<(u32, u32), &str>) {
+        let dnd = open("monster_refs/goblin.png").unwrap().to_rgba8();
+        let mut window = RgbaImage::from_pixel(1000, 1000, Rgba([0, 0, 0, 255]));
+        for &(x, y) in places {
+            replace(&mut window, &dnd, x as i64, y as i64);
+        }
+        let found = find_dnd_offset(&window, &Layout::default()).map_err(|e| e.to_string());
+        assert_eq!(found, expected.map_err(String::from));
+    }
+
+    #[test]
+    fn random_board() {
+        // One capture to find the window, then one per call below
+        let mut dc = replay(&["goblin", "goblin", "minotaur", "minotaur"]);
+        let controls = dc.layout.controls;
+        assert_eq!(dc.parse().unwrap().seed, Some(22398633));
+        dc.random_board().unwrap();
+        assert_eq!(dc.input.clicks.last(), Some(&in_window(controls.random)));
+        assert_eq!(dc.parse().unwrap().seed, Some(24737362));
+    }
+
+    #[test]
+    fn enter_solution() {
+        let mut dc = replay(&["goblin"]);
+        // Walls in the top left and bottom right corners, and one in the middle
+        let solution = 1 << 63 | 1 << (63 - 27) | 1;
+        dc.enter_solution(solution).unwrap();
+        assert_eq!(
+            dc.input.clicks[1..],
+            [
+                in_window((66, 191)),
+                in_window((3 * 33 + 66, 3 * 33 + 191)),
+                in_window((7 * 33 + 66, 7 * 33 + 191)),
+                in_window((0, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn enter_solution_with_drags() {
+        let mut dc = replay(&["goblin"]);
+        dc.drag = true;
+        // A row of three walls and a lone one
+        let solution = 0b1110_0000 << 8 | 1;
+        let report = dc.enter_solution(solution).unwrap();
+        assert_eq!(report.walls, 4);
+        assert_eq!(report.strokes, 2);
+        assert_eq!(
+            dc.input.drags,
+            [(
+                in_window((66, 6 * 33 + 191)),
+                in_window((2 * 33 + 66, 6 * 33 + 191))
+            )]
+        );
+        assert_eq!(
+            dc.input.clicks[1..],
+            [in_window((7 * 33 + 66, 7 * 33 + 191)), in_window((0, 0))]
+        );
+    }
+
+    #[test]
+    fn reset_solution() {
+        let mut dc = replay(&["goblin"]);
+        let controls = dc.layout.controls;
+        dc.reset_solution().unwrap();
+        assert_eq!(
+            dc.input.clicks[1..],
+            [in_window(controls.menu), in_window(controls.reset)]
+        );
+    }
+
+    #[test]
+    fn marks() {
+        let mut dc = replay(&["goblin"]);
+        dc.mark_empty(2, 1).unwrap();
+        assert_eq!(dc.input.right_clicks, [in_window((2 * 33 + 66, 33 + 191))]);
+    }
+
+    #[test]
+    fn enter_seed() {
+        let mut dc = replay(&["goblin"]);
+        assert!(dc.enter_seed(1234).is_err());
+        assert!(dc.input.keys.is_empty());
+
+        dc.layout.controls.seed_entry = Some((150, 20));
+        dc.enter_seed(1234).unwrap();
+        assert_eq!(dc.input.clicks.last(), Some(&in_window((150, 20))));
+        assert_eq!(
+            dc.input.keys,
+            [
+                Key::Char('1'),
+                Key::Char('2'),
+                Key::Char('3'),
+                Key::Char('4'),
+                Key::Enter
+            ]
+        );
+    }
+
+    #[test]
+    fn board_image() {
+        let path = std::env::temp_dir().join(format!("board_{}.png", std::process::id()));
+        let mut dc = replay(&["goblin", "goblin"]);
+        dc.save_board_image(None, &path).unwrap();
+        let saved = open(&path).unwrap().to_rgba8();
+        fs::remove_file(&path).unwrap();
+
+        let (x, y, w, h) = dc.layout.board.picture.rect();
+        let (x, y) = in_window((x, y));
+        assert_eq!(saved, window("goblin").view(x, y, w, h).to_image());
+    }
+
+    #[test]
+    fn click_log_file() {
+        let path = std::env::temp_dir().join(format!("clicks_{}.log", std::process::id()));
+        let frames = vec![window("goblin")];
+        let screen = ReplayScreen::new(frames).unwrap();
+        let mut dc =
+            DungeonCrawler::with_backend(screen, ClickLog::to_file(&path).unwrap()).unwrap();
+        dc.place_wall(1, 2).unwrap();
+        drop(dc);
+
+        let log = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(log, "40,30\n139,287\n");
+    }
+}
@@ -0,0 +1,473 @@
+use std::{
+    fs,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::db::{self, DbWriter};
+use crate::play::{draw_board, highlight, BROKEN, CLEAR_SCREEN};
+use crate::puzzle::{bit, Puzzle, Tile};
+use crate::solve::{self, BackTracker, Bounded, Budget};
+
+pub const HELP: &str = "\
+w a s d: move   x: wall   m: monster   t: treasure   e: erase   c: clear all
+p: save   q: quit
+Type any number of keys, then Enter";
+
+// Designed boards are kept apart from the collected ones so they don't skew `db stats`
+pub const DESIGNED_DB_FILE: &str = "designed.db";
+
+// Tiles that differ between solutions
+const AMBIGUOUS: &str = "\x1b[43m";
+
+// The search reruns on every key, and all it has to tell apart is none, one or more. Sparse
+// drawings can have millions of solutions, so it stops at two or after about half a second.
+const BUDGET: Budget = Budget {
+    solutions: 2,
+    steps: 1_000_000,
+};
+
+pub fn designed_db_path() -> PathBuf {
+    Path::new(db::DB_DIR).join(DESIGNED_DB_FILE)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Draw(Tile),
+    ClearAll,
+    Save,
+    Quit,
+}
+
+impl Key {
+    pub fn from_char(c: char) -> Option<Key> {
+        Some(match c.to_ascii_lowercase() {
+            'w' => Key::Up,
+            's' => Key::Down,
+            'a' => Key::Left,
+            'd' => Key::Right,
+            'x' => Key::Draw(Tile::Wall),
+            'm' => Key::Draw(Tile::Monster),
+            't' => Key::Draw(Tile::Treasure),
+            'e' | '.' => Key::Draw(Tile::Empty),
+            'c' => Key::ClearAll,
+            'p' => Key::Save,
+            'q' => Key::Quit,
+            _ => return None,
+        })
+    }
+}
+
+// Tiles that differ between solutions, none unless there are at least two
+pub fn ambiguous_cells(solutions: &[u64]) -> u64 {
+    let any = solutions.iter().fold(0, |acc, s| acc | s);
+    let all = solutions.iter().fold(u64::MAX, |acc, s| acc & s);
+    match solutions.len() {
+        0 | 1 => 0,
+        _ => any & !all,
+    }
+}
+
+// A board being designed. The author draws the answer, walls and all, and the clues are
+// whatever those walls add up to.
+pub struct Editor {
+    pub board: Puzzle,
+    pub cursor: (usize, usize),
+    pub message: String,
+    // Where `p` saves to
+    pub text_path: PathBuf,
+    pub db_path: PathBuf,
+    // Solutions to the clues, and the puzzle they were worked out for
+    solved: Option<(Puzzle, Bounded)>,
+}
+
+impl Editor {
+    pub fn new(board: Puzzle, text_path: PathBuf, db_path: PathBuf) -> Self {
+        let mut editor = Self {
+            board,
+            cursor: (0, 0),
+            message: String::new(),
+            text_path,
+            db_path,
+            solved: None,
+        };
+        editor.board.seed = None;
+        editor.board.top_nums = editor.puzzle().top_nums;
+        editor.board.left_nums = editor.puzzle().left_nums;
+        editor
+    }
+
+    // Open a saved puzzle. The text has no walls, so they're filled in from its solution when
+    // there's only one.
+    pub fn load(puzzle: Puzzle, text_path: PathBuf, db_path: PathBuf) -> Self {
+        let mut board = puzzle;
+        let mut message = String::new();
+        match BackTracker.solve_bounded(&puzzle, BUDGET) {
+            Bounded::All(solutions) if solutions.len() == 1 => {
+                for (i, tile) in board.tiles.as_flattened_mut().iter_mut().enumerate() {
+                    if solutions[0] & bit(i % 8, i / 8) != 0 {
+                        *tile = Tile::Wall;
+                    }
+                }
+            }
+            solved => {
+                let solutions = match solved {
+                    Bounded::All(solutions) => format!("{} solutions", solutions.len()),
+                    Bounded::AtLeast(_) => "More than one solution".to_string(),
+                    Bounded::GaveUp(_) => "The search gave up".to_string(),
+                };
+                message =
+                    format!("{solutions}, so no walls drawn and the counts start from scratch")
+            }
+        }
+        let mut editor = Self::new(board, text_path, db_path);
+        editor.message = message;
+        editor
+    }
+
+    pub fn walls(&self) -> u64 {
+        let mut walls = 0;
+        for (i, &tile) in self.board.tiles.as_flattened().iter().enumerate() {
+            if tile == Tile::Wall {
+                walls |= bit(i % 8, i / 8);
+            }
+        }
+        walls
+    }
+
+    // The clues for what's drawn: the board without its walls, and the walls counted up
+    pub fn puzzle(&self) -> Puzzle {
+        let mut puzzle = Puzzle {
+            tiles: self.board.tiles,
+            seed: None,
+            ..Default::default()
+        };
+        for (y, row) in puzzle.tiles.iter_mut().enumerate() {
+            for (x, tile) in row.iter_mut().enumerate() {
+                if *tile == Tile::Wall {
+                    *tile = Tile::Empty;
+                    puzzle.top_nums[x] += 1;
+                    puzzle.left_nums[y] += 1;
+                }
+            }
+        }
+        puzzle
+    }
+
+    // Tiles of the drawn answer that break the rules
+    pub fn violations(&self) -> u64 {
+        solve::violations(&self.board)
+    }
+
+    pub fn solutions(&mut self) -> &Bounded {
+        let puzzle = self.puzzle();
+        if self.solved.as_ref().is_none_or(|(p, _)| *p != puzzle) {
+            self.solved = Some((puzzle, BackTracker.solve_bounded(&puzzle, BUDGET)));
+        }
+        &self.solved.as_ref().unwrap().1
+    }
+
+    // How the clues stand, for under the board
+    pub fn report(&mut self) -> String {
+        let walls = self.walls();
+        let rules = match self.violations().count_ones() {
+            0 => "Rules: ok".to_string(),
+            n => format!("Rules: {n} tiles break them"),
+        };
+        let solutions = match self.solutions() {
+            Bounded::All(solutions) => match solutions[..] {
+                [] => "no solutions".to_string(),
+                [only] if only == walls => "1 solution, this one".to_string(),
+                [_] => "1 solution, but not this one".to_string(),
+                ref many => format!(
+                    "{} solutions, tiles they disagree on highlighted",
+                    many.len()
+                ),
+            },
+            Bounded::AtLeast(_) => {
+                "more than one solution, tiles two of them disagree on highlighted".to_string()
+            }
+            Bounded::GaveUp(_) => "search gave up, too much is left open".to_string(),
+        };
+        format!("{rules}   Clues: {solutions}")
+    }
+
+    // Finished means the drawing follows the rules and the clues lead only to it
+    pub fn finished(&mut self) -> bool {
+        let walls = self.walls();
+        self.violations() == 0
+            && matches!(self.solutions(), Bounded::All(solutions) if *solutions == [walls])
+    }
+
+    // Write the clues as text and append them to the designed boards database. Returns false
+    // if the board, or a rotation or reflection of it, was in the database already, and so
+    // wasn't added again.
+    pub fn save(&mut self) -> Result<bool> {
+        if !self.finished() {
+            return Err(anyhow!(
+                "Not saved, the board has to follow the rules and have one solution"
+            ));
+        }
+        let puzzle = self.puzzle();
+        if let Some(parent) = self.text_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.text_path, puzzle.to_string())?;
+        let (mut db, scan) = DbWriter::open(&self.db_path)?;
+        let hash = puzzle.canonical_hash();
+        if scan
+            .puzzles
+            .iter()
+            .any(|saved| saved.canonical_hash() == hash)
+        {
+            return Ok(false);
+        }
+        db.append(&puzzle)?;
+        db.sync()?;
+        Ok(true)
+    }
+
+    // Returns false once the author quits
+    pub fn press(&mut self, key: Key) -> bool {
+        self.message.clear();
+        let (x, y) = self.cursor;
+        match key {
+            Key::Up => self.cursor.1 = y.saturating_sub(1),
+            Key::Down => self.cursor.1 = (y + 1).min(7),
+            Key::Left => self.cursor.0 = x.saturating_sub(1),
+            Key::Right => self.cursor.0 = (x + 1).min(7),
+            Key::Draw(tile) => {
+                // Drawing what's already there rubs it out
+                let current = &mut self.board.tiles[y][x];
+                *current = if *current == tile { Tile::Empty } else { tile };
+            }
+            Key::ClearAll => self.board.tiles = Default::default(),
+            Key::Save => {
+                self.message = match self.save() {
+                    Ok(true) => format!(
+                        "Saved to {} and {}",
+                        self.text_path.display(),
+                        self.db_path.display()
+                    ),
+                    Ok(false) => format!(
+                        "Saved to {}, already in {} so not added again",
+                        self.text_path.display(),
+                        self.db_path.display()
+                    ),
+                    Err(e) => e.to_string(),
+                }
+            }
+            Key::Quit => return false,
+        }
+        let puzzle = self.puzzle();
+        self.board.top_nums = puzzle.top_nums;
+        self.board.left_nums = puzzle.left_nums;
+        true
+    }
+
+    // The drawing with the clues it makes, rule breaking tiles in red and tiles the solutions
+    // disagree on in yellow
+    pub fn render(&mut self) -> String {
+        let bad = self.violations();
+        let ambiguous = ambiguous_cells(self.solutions().solutions());
+        let top = self.board.top_nums.map(|n| n.to_string());
+        let left = self.board.left_nums.map(|n| n.to_string());
+        draw_board(&top, &left, self.cursor, |x, y| {
+            let glyph = self.board.tiles[y][x].to_str();
+            match (bad & bit(x, y) != 0, ambiguous & bit(x, y) != 0) {
+                (true, _) => highlight(glyph, true, BROKEN),
+                (_, ambiguous) => highlight(glyph, ambiguous, AMBIGUOUS),
+            }
+        })
+    }
+}
+
+// Edit in the terminal until the author quits or input runs out. Each line of input is read as
+// a run of keys.
+pub fn run(mut editor: Editor, input: impl BufRead, mut output: impl Write) -> Result<Editor> {
+    let mut lines = input.lines();
+    loop {
+        write!(output, "{CLEAR_SCREEN}")?;
+        let board = editor.render();
+        let report = editor.report();
+        writeln!(output, "{board}\n{report}\n{}\n\n{HELP}", editor.message)?;
+        output.flush()?;
+
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(editor);
+        };
+        for c in line.chars().filter(|c| !c.is_whitespace()) {
+            match Key::from_char(c) {
+                Some(key) => {
+                    if !editor.press(key) {
+                        return Ok(editor);
+                    }
+                }
+                None => editor.message = format!("Unknown key '{c}'"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::puzzle;
+
+    // The corridor, drawn with its walls
+    fn corridor() -> Puzzle {
+        let (puzzle, walls) = puzzle::corridor();
+        puzzle::with_walls(&puzzle, walls)
+    }
+
+    fn temp(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    // Each test gets its own files, tests run at the same time
+    fn editor(name: &str, board: Puzzle) -> Editor {
+        Editor::new(
+            board,
+            temp(&format!("{name}.txt")),
+            temp(&format!("{name}.db")),
+        )
+    }
+
+    #[test]
+    fn counts_from_walls() {
+        let editor = editor("editor_counts_from_walls", corridor());
+        let puzzle = editor.puzzle();
+        assert_eq!(puzzle.top_nums, [0, 1, 7, 7, 7, 7, 7, 7]);
+        assert_eq!(puzzle.left_nums, [6, 6, 6, 6, 6, 6, 7, 0]);
+        assert!(puzzle.tiles.iter().flatten().all(|&t| t != Tile::Wall));
+        assert_eq!(editor.board.top_nums, puzzle.top_nums);
+    }
+
+    #[test]
+    fn drawing() {
+        let mut editor = editor("editor_drawing", Puzzle::default());
+        for c in "xdmdtdx".chars() {
+            editor.press(Key::from_char(c).unwrap());
+        }
+        assert_eq!(
+            editor.board.tiles[0][..4],
+            [Tile::Wall, Tile::Monster, Tile::Treasure, Tile::Wall]
+        );
+        assert_eq!(editor.board.top_nums[..4], [1, 0, 0, 1]);
+        assert_eq!(editor.board.left_nums[0], 2);
+
+        // Drawing the same thing again rubs it out
+        editor.press(Key::Draw(Tile::Wall));
+        assert_eq!(editor.board.tiles[0][3], Tile::Empty);
+        assert_eq!(editor.board.left_nums[0], 1);
+
+        editor.press(Key::ClearAll);
+        assert_eq!(editor.board, Puzzle::default());
+    }
+
+    #[test]
+    fn unique_design() {
+        let mut editor = editor("editor_unique_design", corridor());
+        assert_eq!(editor.violations(), 0);
+        assert_eq!(editor.report(), "Rules: ok   Clues: 1 solution, this one");
+        assert!(editor.finished());
+    }
+
+    #[test]
+    fn broken_design() {
+        // A wall across the corridor traps a monster
+        let mut board = corridor();
+        board.tiles[3][0] = Tile::Wall;
+        let mut editor = editor("editor_broken_design", board);
+        assert_ne!(editor.violations() & bit(1, 3), 0);
+        assert!(editor.report().starts_with("Rules: 4 tiles break them"));
+        assert!(!editor.finished());
+        assert!(editor.save().is_err());
+        assert!(!editor.text_path.exists());
+    }
+
+    // One monster and two walls to each row and column, which the full search takes minutes over.
+    // Both end well inside the budget's half second in a release build.
+    #[test_case(2, (3, 3), "more than one solution, tiles two of them disagree on highlighted")]
+    #[test_case(0, (4, 0), "search gave up, too much is left open")]
+    fn sparse_design(shift: usize, (x, y): (usize, usize), clues: &str) {
+        let mut board = Puzzle::default();
+        for (y, row) in board.tiles.iter_mut().enumerate() {
+            row[(y + shift) % 8] = Tile::Wall;
+            row[(y + shift + 1) % 8] = Tile::Wall;
+        }
+        board.tiles[y][x] = Tile::Monster;
+        let mut editor = editor(&format!("editor_sparse_{shift}"), board);
+        let report = editor.report();
+        assert!(report.ends_with(clues), "{report}");
+        assert!(!editor.finished());
+    }
+
+    #[test_case(&[], 0)]
+    #[test_case(&[0b1010], 0)]
+    #[test_case(&[0b1010, 0b1010], 0)]
+    #[test_case(&[0b1010, 0b0110], 0b1100)]
+    #[test_case(&[0b1000, 0b0100, 0b0010], 0b1110)]
+    fn ambiguity(solutions: &[u64], cells: u64) {
+        assert_eq!(ambiguous_cells(solutions), cells);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut editor = editor("editor_save_and_load", corridor());
+        assert!(editor.save().unwrap());
+
+        let text = fs::read_to_string(&editor.text_path).unwrap();
+        let saved: Puzzle = text.parse().unwrap();
+        assert_eq!(saved, editor.puzzle());
+        let scan = db::read(&editor.db_path).unwrap();
+        assert_eq!(scan.puzzles, [editor.puzzle()]);
+
+        // Saving again, or saving the same board turned around, doesn't add it twice
+        assert!(!editor.save().unwrap());
+        let mut flipped = editor.board;
+        flipped.tiles.reverse();
+        let mut mirror = Editor::new(flipped, editor.text_path.clone(), editor.db_path.clone());
+        assert_ne!(mirror.puzzle(), editor.puzzle());
+        assert!(!mirror.save().unwrap());
+        assert_eq!(db::read(&editor.db_path).unwrap().puzzles.len(), 1);
+
+        // Loading draws the walls back in from the only solution
+        let loaded = Editor::load(saved, temp("editor_loaded.txt"), temp("editor_loaded.db"));
+        assert_eq!(loaded.board, editor.board);
+        assert!(loaded.message.is_empty());
+
+        fs::remove_file(&editor.text_path).unwrap();
+        fs::remove_file(&editor.db_path).unwrap();
+    }
+
+    #[test]
+    fn runs_on_plain_io() {
+        let mut output = Vec::new();
+        let editor = run(
+            editor("editor_runs_on_plain_io", corridor()),
+            Cursor::new("ssx\nq\nx\n"),
+            &mut output,
+        )
+        .unwrap();
+        // The corridor tile at (0, 2) got a wall, the last line wasn't read
+        assert_eq!(editor.board.tiles[2][0], Tile::Wall);
+        assert_eq!(editor.board.left_nums[2], 7);
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("1 solution, this one"));
+        assert!(output.contains(HELP));
+    }
+}
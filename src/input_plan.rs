@@ -0,0 +1,337 @@
+use std::{fmt::Display, time::Duration};
+
+use thiserror::Error;
+
+use crate::puzzle::bit;
+
+// A single press and release on the board, in cell coordinates. A click if both ends are the
+// same cell, otherwise a drag along a row or column painting every cell in between.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Stroke {
+    pub from: (u8, u8),
+    pub to: (u8, u8),
+}
+
+impl Stroke {
+    fn click(x: u8, y: u8) -> Self {
+        Self {
+            from: (x, y),
+            to: (x, y),
+        }
+    }
+
+    pub fn is_click(&self) -> bool {
+        self.from == self.to
+    }
+
+    fn reversed(self) -> Self {
+        Self {
+            from: self.to,
+            to: self.from,
+        }
+    }
+
+    // Every cell the stroke paints, as bits of a solution mask
+    pub fn mask(&self) -> u64 {
+        let (x0, x1) = (self.from.0.min(self.to.0), self.from.0.max(self.to.0));
+        let (y0, y1) = (self.from.1.min(self.to.1), self.from.1.max(self.to.1));
+        let mut mask = 0;
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                mask |= cell_bit(x, y);
+            }
+        }
+        mask
+    }
+}
+
+pub fn cell_bit(x: u8, y: u8) -> u64 {
+    bit(x as usize, y as usize)
+}
+
+// Cells set in a solution mask, in row order
+pub fn cells(mask: u64) -> impl Iterator<Item = (u8, u8)> {
+    (0..64u8)
+        .filter(move |i| mask & (1 << (63 - i)) != 0)
+        .map(|i| (i % 8, i / 8))
+}
+
+fn distance(a: (f32, f32), b: (u8, u8)) -> f32 {
+    (a.0 - b.0 as f32).hypot(a.1 - b.1 as f32)
+}
+
+fn point(cell: (u8, u8)) -> (f32, f32) {
+    (cell.0 as f32, cell.1 as f32)
+}
+
+// How far the cursor moves to make the strokes in order, in cells, starting from `start`
+pub fn travel(start: (f32, f32), strokes: &[Stroke]) -> f32 {
+    let mut cursor = start;
+    let mut total = 0.0;
+    for stroke in strokes {
+        total += distance(cursor, stroke.from) + distance(point(stroke.from), stroke.to);
+        cursor = point(stroke.to);
+    }
+    total
+}
+
+// Work out the strokes needed to place the walls of a solution, ordered to keep cursor travel
+// short. With `drag` set, runs of two or more walls along a row become one drag, then the same
+// for columns, and whatever is left is clicked.
+pub fn plan(solution: u64, start: (f32, f32), drag: bool) -> Vec<Stroke> {
+    let mut remaining = solution;
+    let mut strokes = Vec::new();
+
+    if drag {
+        for vertical in [false, true] {
+            for line in 0..8 {
+                let mut run: Vec<(u8, u8)> = Vec::new();
+                for along in 0..=8 {
+                    let cell = match vertical {
+                        false => (along, line),
+                        true => (line, along),
+                    };
+                    if along < 8 && remaining & cell_bit(cell.0, cell.1) != 0 {
+                        run.push(cell);
+                        continue;
+                    }
+                    if run.len() >= 2 {
+                        let stroke = Stroke {
+                            from: run[0],
+                            to: run[run.len() - 1],
+                        };
+                        remaining &= !stroke.mask();
+                        strokes.push(stroke);
+                    }
+                    run.clear();
+                }
+            }
+        }
+    }
+    strokes.extend(cells(remaining).map(|(x, y)| Stroke::click(x, y)));
+
+    order(strokes, start)
+}
+
+// Nearest neighbour to get a reasonable tour, then 2-opt until no reversal of a stretch of the
+// tour shortens it. Boards have at most a few dozen strokes so this is quick.
+fn order(mut unvisited: Vec<Stroke>, start: (f32, f32)) -> Vec<Stroke> {
+    let mut tour = Vec::with_capacity(unvisited.len());
+    let mut cursor = start;
+    while !unvisited.is_empty() {
+        let (i, reverse) = unvisited
+            .iter()
+            .enumerate()
+            .flat_map(|(i, s)| [(i, false, s.from), (i, true, s.to)])
+            .min_by(|a, b| distance(cursor, a.2).total_cmp(&distance(cursor, b.2)))
+            .map(|(i, reverse, _)| (i, reverse))
+            .unwrap();
+        let stroke = unvisited.swap_remove(i);
+        let stroke = if reverse { stroke.reversed() } else { stroke };
+        cursor = point(stroke.to);
+        tour.push(stroke);
+    }
+
+    let mut best = travel(start, &tour);
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 0..tour.len() {
+            for j in i + 1..tour.len() {
+                let mut candidate = tour.clone();
+                candidate[i..=j].reverse();
+                candidate[i..=j].iter_mut().for_each(|s| *s = s.reversed());
+                let length = travel(start, &candidate);
+                if length + 1e-4 < best {
+                    (tour, best, improved) = (candidate, length, true);
+                }
+            }
+        }
+    }
+    tour
+}
+
+// Time to leave between inputs. Backs off when clicks get dropped, and creeps back down while
+// everything registers.
+#[derive(Debug, Copy, Clone)]
+pub struct AdaptiveDelay {
+    pub current: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl AdaptiveDelay {
+    pub fn new(initial: Duration) -> Self {
+        Self {
+            current: initial,
+            min: Duration::from_millis(2),
+            max: Duration::from_millis(200),
+        }
+    }
+
+    pub fn succeeded(&mut self) {
+        self.current = (self.current * 3 / 4).max(self.min);
+    }
+
+    pub fn failed(&mut self) {
+        self.current = (self.current * 2).min(self.max);
+    }
+}
+
+// How hard to try getting a board right after entering it. Each retry re-captures the board
+// and clicks every cell that's still wrong. Before that the board gets up to `settle` to catch
+// up, since clicking a wall again that just hasn't been drawn yet would take it back off.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub settle: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            settle: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("board still wrong after {attempts} attempts (+ missing, x extra):\n{}", diff(*.expected, *.placed))]
+pub struct WallMismatch {
+    pub expected: u64,
+    pub placed: u64,
+    pub attempts: u32,
+}
+
+// The board as rows of cells: # wall, . no wall, + wall missing, x wall that shouldn't be there
+pub fn diff(expected: u64, placed: u64) -> String {
+    let mut out = String::new();
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = cell_bit(x, y);
+            out.push(match (expected & bit != 0, placed & bit != 0) {
+                (true, true) => '#',
+                (false, false) => '.',
+                (true, false) => '+',
+                (false, true) => 'x',
+            });
+        }
+        if y < 7 {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+// What happened while entering a board
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EntryReport {
+    pub walls: u32,
+    pub strokes: usize,
+    pub travel: f32,
+    // Cells that were wrong when the board was checked, and had to be clicked again
+    pub missed: u32,
+    pub retries: u32,
+    pub delay: Duration,
+    pub elapsed: Duration,
+}
+
+impl Display for EntryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} walls in {} strokes ({:.1} cells of travel), {} missed over {} retries, {}ms delay, entered in {}ms",
+            self.walls,
+            self.strokes,
+            self.travel,
+            self.missed,
+            self.retries,
+            self.delay.as_millis(),
+            self.elapsed.as_millis()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Walls down the left edge, along the bottom, and a few scattered about
+    const SOLUTION: u64 = 0x80_80_86_80_90_80_80_ff;
+
+    fn covered(strokes: &[Stroke]) -> u64 {
+        strokes.iter().fold(0, |acc, s| {
+            assert_eq!(acc & s.mask(), 0, "{s:?} paints a cell twice");
+            acc | s.mask()
+        })
+    }
+
+    #[test]
+    fn clicks_cover_solution() {
+        let strokes = plan(SOLUTION, (0.0, 0.0), false);
+        assert!(strokes.iter().all(Stroke::is_click));
+        assert_eq!(strokes.len(), SOLUTION.count_ones() as usize);
+        assert_eq!(covered(&strokes), SOLUTION);
+    }
+
+    #[test]
+    fn drags_cover_solution() {
+        let strokes = plan(SOLUTION, (0.0, 0.0), true);
+        assert_eq!(covered(&strokes), SOLUTION);
+        // The bottom row, the rest of the left column, the pair in row 2, and the lone wall
+        assert_eq!(strokes.len(), 4);
+        assert!(strokes
+            .iter()
+            .all(|s| s.from.0 == s.to.0 || s.from.1 == s.to.1));
+    }
+
+    #[test]
+    fn shorter_than_row_order() {
+        let row_order: Vec<Stroke> = cells(SOLUTION).map(|(x, y)| Stroke::click(x, y)).collect();
+        let start = (7.0, 0.0);
+        let planned = plan(SOLUTION, start, false);
+        assert!(travel(start, &planned) < travel(start, &row_order));
+    }
+
+    #[test]
+    fn starts_near_cursor() {
+        let strokes = plan(cell_bit(0, 0) | cell_bit(7, 7), (6.0, 7.0), false);
+        assert_eq!(strokes[0], Stroke::click(7, 7));
+    }
+
+    #[test]
+    fn delay_adapts() {
+        let mut delay = AdaptiveDelay::new(Duration::from_millis(20));
+        delay.failed();
+        assert_eq!(delay.current, Duration::from_millis(40));
+        delay.succeeded();
+        assert_eq!(delay.current, Duration::from_millis(30));
+        for _ in 0..20 {
+            delay.succeeded();
+        }
+        assert_eq!(delay.current, delay.min);
+        for _ in 0..20 {
+            delay.failed();
+        }
+        assert_eq!(delay.current, delay.max);
+    }
+
+    #[test]
+    fn mismatch_diff() {
+        let expected = cell_bit(0, 0) | cell_bit(1, 0);
+        let placed = cell_bit(0, 0) | cell_bit(7, 7);
+        let error = WallMismatch {
+            expected,
+            placed,
+            attempts: 2,
+        };
+        let message = error.to_string();
+        assert!(message.starts_with("board still wrong after 2 attempts"));
+        let rows: Vec<&str> = message.lines().skip(1).collect();
+        assert_eq!(rows.len(), 8);
+        assert_eq!(rows[0], "#+......");
+        assert_eq!(rows[3], "........");
+        assert_eq!(rows[7], ".......x");
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use image::{RgbaImage, SubImage};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::digits::DIGIT_SIZE;
+use crate::util::Versioned;
+
+// Where everything is on screen, so moving things after a game update is an edit to the json.
+// Loading rejects unknown or missing fields and anything that doesn't fit in the window (see
+// check). data/layout.schema.json isn't read at runtime, it's for editors, and the tests keep
+// it in step with these structs.
+pub const LAYOUT_DIR: &str = "data";
+pub const LAYOUT_FILE: &str = "layout.json";
+
+// Bump this when the meaning of a field changes, along with the schema's version const
+pub const LAYOUT_VERSION: u32 = 3;
+
+// Used when there's no layout file next to the binary
+const BUILT_IN: &str = include_str!("../data/layout.json");
+
+static GLOBAL: OnceLock<Result<Layout, String>> = OnceLock::new();
+
+// A rectangle of the DnD subwindow
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    pub origin: (u32, u32),
+    pub size: (u32, u32),
+}
+
+impl Region {
+    pub fn rect(&self) -> (u32, u32, u32, u32) {
+        (self.origin.0, self.origin.1, self.size.0, self.size.1)
+    }
+
+    pub fn view<'a>(&self, img: &'a SubImage<&RgbaImage>) -> SubImage<&'a RgbaImage> {
+        img.view(self.origin.0, self.origin.1, self.size.0, self.size.1)
+    }
+
+    fn end(&self) -> (u32, u32) {
+        (self.origin.0 + self.size.0, self.origin.1 + self.size.1)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Window {
+    // Size of the DnD subwindow
+    pub size: (u32, u32),
+    // Where the pixels used to find the subwindow sit inside it
+    pub marker: (u32, u32),
+    // Furthest the subwindow can be from the game window's corner
+    pub max_offset: (u32, u32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Board {
+    pub region: Region,
+    pub tile_stride: u32,
+    pub tile_size: u32,
+    // Both from a tile's top left corner
+    pub sample_point: (u32, u32),
+    pub click_point: (u32, u32),
+    // The board along with its wall counts, for saving pictures of it
+    pub picture: Region,
+}
+
+impl Board {
+    pub fn tile_corner(&self, x: usize, y: usize) -> (u32, u32) {
+        (
+            self.region.origin.0 + x as u32 * self.tile_stride,
+            self.region.origin.1 + y as u32 * self.tile_stride,
+        )
+    }
+
+    pub fn tile_rect(&self, x: usize, y: usize) -> (u32, u32, u32, u32) {
+        let (tx, ty) = self.tile_corner(x, y);
+        (tx, ty, self.tile_size, self.tile_size)
+    }
+
+    pub fn sample_point(&self, x: usize, y: usize) -> (u32, u32) {
+        let (tx, ty) = self.tile_corner(x, y);
+        (tx + self.sample_point.0, ty + self.sample_point.1)
+    }
+
+    pub fn click_point(&self, x: usize, y: usize) -> (u32, u32) {
+        let (tx, ty) = self.tile_corner(x, y);
+        (tx + self.click_point.0, ty + self.click_point.1)
+    }
+
+    // A point in tile units, where the click point of the top left tile is 0,0
+    pub fn cell_at(&self, (x, y): (u32, u32)) -> (f32, f32) {
+        let (x0, y0) = self.click_point(0, 0);
+        (
+            (x as f32 - x0 as f32) / self.tile_stride as f32,
+            (y as f32 - y0 as f32) / self.tile_stride as f32,
+        )
+    }
+
+    // The tile a point is on, if it's on one rather than between them
+    pub fn tile_at(&self, (x, y): (u32, u32)) -> Option<(usize, usize)> {
+        let bx = x.checked_sub(self.region.origin.0)?;
+        let by = y.checked_sub(self.region.origin.1)?;
+        let (tile_x, tile_y) = (bx / self.tile_stride, by / self.tile_stride);
+        let inside =
+            bx % self.tile_stride < self.tile_size && by % self.tile_stride < self.tile_size;
+        (tile_x < 8 && tile_y < 8 && inside).then_some((tile_x as usize, tile_y as usize))
+    }
+}
+
+// A strip of wall counts. Digits are a tile stride apart, plus a nudge of a pixel or two for
+// some of them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CountStrip {
+    pub region: Region,
+    pub nudges: [u32; 8],
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedStrip {
+    pub region: Region,
+    // How far each digit advances the next one
+    pub digit_widths: [u32; 10],
+}
+
+// Everything that gets clicked, other than the board
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Controls {
+    pub random: (u32, u32),
+    // Opens the menu that reset is in
+    pub menu: (u32, u32),
+    pub reset: (u32, u32),
+    // Box to type a seed into, if the game has one
+    pub seed_entry: Option<(u32, u32)>,
+    // Somewhere clicking does nothing, for grabbing the mouse
+    pub neutral: (u32, u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layout {
+    pub version: u32,
+    pub window: Window,
+    pub board: Board,
+    pub top_counts: CountStrip,
+    pub left_counts: CountStrip,
+    pub seed: SeedStrip,
+    pub controls: Controls,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::from_json(BUILT_IN).expect("the built in layout should be valid")
+    }
+}
+
+impl Layout {
+    // Make this the layout returned by global(). Has no effect if one was already set.
+    pub fn install(self) -> Result<&'static Layout> {
+        GLOBAL
+            .get_or_init(|| Ok(self))
+            .as_ref()
+            .map_err(|e| anyhow!("{e}"))
+    }
+
+    // Falls back to data/layout.json, or the built in one, if main didn't install one
+    pub fn global() -> Result<&'static Layout> {
+        GLOBAL
+            .get_or_init(|| Self::load_or_default(&Self::path()).map_err(|e| e.to_string()))
+            .as_ref()
+            .map_err(|e| anyhow!("{e}"))
+    }
+
+    // Corners of each wall count digit
+    pub fn top_count_corner(&self, i: usize) -> (u32, u32) {
+        let (x, y) = self.top_counts.region.origin;
+        (
+            x + i as u32 * self.board.tile_stride + self.top_counts.nudges[i],
+            y,
+        )
+    }
+
+    pub fn left_count_corner(&self, i: usize) -> (u32, u32) {
+        let (x, y) = self.left_counts.region.origin;
+        (
+            x,
+            y + i as u32 * self.board.tile_stride + self.left_counts.nudges[i],
+        )
+    }
+}
+
+impl Versioned for Layout {
+    const VERSION: u32 = LAYOUT_VERSION;
+    const DIR: &'static str = LAYOUT_DIR;
+    const FILE: &'static str = LAYOUT_FILE;
+    const NAME: &'static str = "Layout";
+
+    fn version(&self) -> u32 {
+        self.version
+    }
+
+    // Everything has to land inside the window, and each strip has to hold all of its digits.
+    // The schema can't say any of this, so it's checked here.
+    fn check(&self) -> Result<()> {
+        let (width, height) = self.window.size;
+        let inside = |name: &str, (x, y): (u32, u32)| match x < width && y < height {
+            true => Ok(()),
+            false => Err(anyhow!(
+                "{name} at {x},{y} is outside the {width}x{height} window"
+            )),
+        };
+        let fits = |name: &str, region: &Region| {
+            let (x, y) = region.end();
+            match x <= width && y <= height {
+                true => Ok(()),
+                false => Err(anyhow!("{name} runs past the {width}x{height} window")),
+            }
+        };
+
+        let board = &self.board;
+        if board.tile_stride == 0 || board.tile_size == 0 || board.tile_size > board.tile_stride {
+            return Err(anyhow!("Tiles have to be at most a stride wide"));
+        }
+        let span = 7 * board.tile_stride + board.tile_size;
+        if board.region.size.0 < span || board.region.size.1 < span {
+            return Err(anyhow!(
+                "Board is too small for 8 tiles of stride {}",
+                board.tile_stride
+            ));
+        }
+        for (name, (x, y)) in [
+            ("Sample point", board.sample_point),
+            ("Click point", board.click_point),
+        ] {
+            if x >= board.tile_size || y >= board.tile_size {
+                return Err(anyhow!("{name} {x},{y} is outside the tile"));
+            }
+        }
+        fits("Board", &board.region)?;
+        fits("Board picture", &board.picture)?;
+
+        for (name, strip, along) in [
+            ("Top counts", &self.top_counts, 0),
+            ("Left counts", &self.left_counts, 1),
+        ] {
+            fits(name, &strip.region)?;
+            let size = [strip.region.size.0, strip.region.size.1];
+            let digit = [DIGIT_SIZE.0, DIGIT_SIZE.1];
+            let nudge = strip.nudges.iter().max().copied().unwrap_or(0);
+            if size[along] < 7 * board.tile_stride + nudge + digit[along]
+                || size[1 - along] < digit[1 - along]
+            {
+                return Err(anyhow!("{name} is too small for 8 digits"));
+            }
+        }
+
+        fits("Seed", &self.seed.region)?;
+        if self.seed.digit_widths.contains(&0) {
+            return Err(anyhow!("Seed digits need a width"));
+        }
+
+        let controls = &self.controls;
+        let mut points = vec![
+            ("Random", controls.random),
+            ("Menu", controls.menu),
+            ("Reset", controls.reset),
+            ("Neutral", controls.neutral),
+        ];
+        points.extend(controls.seed_entry.map(|point| ("Seed entry", point)));
+        for (name, point) in points {
+            inside(name, point)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::Value;
+
+    use super::*;
+
+    // Every object in the layout has to be described by the schema, field for field
+    fn check_schema(value: &Value, schema: &Value, defs: &Value, path: &str) {
+        // Optional objects are a oneOf with the object first and null second
+        let schema = schema.get("oneOf").map_or(schema, |options| &options[0]);
+        let schema = match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => &defs[reference.trim_start_matches("#/$defs/")],
+            None => schema,
+        };
+        let Value::Object(fields) = value else {
+            return;
+        };
+        assert_eq!(schema["additionalProperties"], Value::Bool(false), "{path}");
+        let mut required: Vec<&str> = schema["required"]
+            .as_array()
+            .unwrap_or_else(|| panic!("{path} has no required list"))
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        let mut keys: Vec<&str> = fields.keys().map(String::as_str).collect();
+        required.sort();
+        keys.sort();
+        assert_eq!(keys, required, "{path}");
+        for (key, field) in fields {
+            check_schema(
+                field,
+                &schema["properties"][key],
+                defs,
+                &format!("{path}.{key}"),
+            );
+        }
+    }
+
+    #[test]
+    fn layout_file() {
+        let layout = Layout::load(&Layout::path()).unwrap();
+        assert_eq!(layout, Layout::default());
+        assert_eq!(layout.board.click_point(0, 0), (66, 191));
+        assert_eq!(layout.board.click_point(7, 7), (7 * 33 + 66, 7 * 33 + 191));
+    }
+
+    #[test]
+    fn matches_schema() {
+        let schema: Value =
+            serde_json::from_str(&fs::read_to_string("data/layout.schema.json").unwrap()).unwrap();
+        assert_eq!(schema["properties"]["version"]["const"], LAYOUT_VERSION);
+        check_schema(
+            &serde_json::to_value(Layout::default()).unwrap(),
+            &schema,
+            &schema["$defs"],
+            "layout",
+        );
+    }
+
+    #[test]
+    fn rejects_bad_layouts() {
+        let edit = |f: &dyn Fn(&mut Layout)| {
+            let mut layout = Layout::default();
+            f(&mut layout);
+            Layout::from_json(&serde_json::to_string(&layout).unwrap())
+        };
+        assert!(edit(&|_| ()).is_ok());
+        assert!(edit(&|l| l.version += 1).is_err());
+        assert!(edit(&|l| l.board.tile_stride = 40).is_err());
+        assert!(edit(&|l| l.board.sample_point = (32, 0)).is_err());
+        assert!(edit(&|l| l.seed.region.origin.1 = 455).is_err());
+        assert!(edit(&|l| l.board.picture.size.0 = 330).is_err());
+        assert!(edit(&|l| l.top_counts.region.size.0 = 200).is_err());
+        assert!(edit(&|l| l.controls.seed_entry = Some((400, 0))).is_err());
+        assert!(edit(&|l| l.controls.seed_entry = Some((10, 20))).is_ok());
+
+        let unknown = BUILT_IN.replacen("\"version\"", "\"colour\": 1, \"version\"", 1);
+        assert!(Layout::from_json(&unknown).is_err());
+    }
+
+    #[test]
+    fn tiles() {
+        let board = Layout::default().board;
+        assert_eq!(board.tile_at((49, 175)), Some((0, 0)));
+        assert_eq!(board.tile_at((49 + 32, 175)), None);
+        assert_eq!(board.tile_at((49 + 33, 175 + 7 * 33 + 31)), Some((1, 7)));
+        assert_eq!(board.tile_at((48, 175)), None);
+        assert_eq!(board.cell_at(board.click_point(3, 5)), (3.0, 5.0));
+    }
+}
@@ -0,0 +1,80 @@
+//! Boards, reading them off screenshots, solving them and storing them. None of this needs a
+//! game window, X11 or the game's files.
+//!
+//! ```
+//! use dungeon_crawler::{db, BackTracker, Puzzle, Solver, Tile};
+//!
+//! // A corridor down the left edge, past a column of monsters
+//! let puzzle: Puzzle = "  0 1 7 7 7 7 7 7
+//! 6 • M • • • • • •
+//! 6 • M • • • • • •
+//! 6 • M • • • • • •
+//! 6 • M • • • • • •
+//! 6 • M • • • • • •
+//! 6 • M • • • • • •
+//! 7 • • • • • • • •
+//! 0 • • • • • • • •"
+//!     .parse()?;
+//! assert_eq!(puzzle.tiles[0][1], Tile::Monster);
+//! assert_eq!(BackTracker.solve(&puzzle).len(), 1);
+//!
+//! // Text and the database both round trip
+//! assert_eq!(puzzle.to_string().parse::<Puzzle>()?, puzzle);
+//! let path = std::env::temp_dir().join("lib_doc_example.db");
+//! # let _ = std::fs::remove_file(&path);
+//! let (mut writer, _) = db::DbWriter::open(&path)?;
+//! writer.append(&puzzle)?;
+//! writer.sync()?;
+//! assert_eq!(db::read(&path)?.puzzles, [puzzle]);
+//! # std::fs::remove_file(&path)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod db;
+pub mod layout;
+pub mod puzzle;
+pub mod solve;
+pub mod symmetry;
+
+pub use puzzle::{Puzzle, Tile};
+pub use solve::{BackTracker, Solver};
+
+// The rest is what the dungeon-crawler binary is built from, and may change with it
+#[doc(hidden)]
+pub mod assets;
+#[doc(hidden)]
+pub mod backend;
+#[doc(hidden)]
+pub mod digits;
+#[doc(hidden)]
+pub mod dungeon_crawler;
+#[doc(hidden)]
+pub mod editor;
+#[doc(hidden)]
+pub mod input_plan;
+#[doc(hidden)]
+pub mod monster_search;
+#[doc(hidden)]
+pub mod monsters;
+#[doc(hidden)]
+pub mod play;
+mod scripts;
+#[doc(hidden)]
+pub mod seed_space;
+#[doc(hidden)]
+pub mod server;
+#[doc(hidden)]
+pub mod simulator;
+#[doc(hidden)]
+pub mod sprite_sheet;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod template;
+#[cfg(feature = "textures")]
+#[doc(hidden)]
+pub mod tex;
+#[doc(hidden)]
+pub mod timing;
+#[doc(hidden)]
+pub mod util;
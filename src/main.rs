@@ -1,172 +1,287 @@
-#![feature(array_windows)]
-#![feature(array_chunks)]
-#![feature(iter_array_chunks)]
-#![feature(path_file_prefix)]
-
 use std::{
-    collections::HashSet,
-    env,
-    fs::{self, OpenOptions},
-    io::{Cursor, Write},
-    path::Path,
-    process::exit,
-    thread,
-    time::{Duration, Instant},
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+#[cfg(feature = "automation")]
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
 
-mod dungeon_crawler;
-mod monster_search;
-mod puzzle;
-mod scripts;
-mod solve;
-mod tex;
-mod util;
+mod cli;
 
 use anyhow::Result;
-use puzzle::Puzzle;
-use solve::Solver;
+use anyhow::{anyhow, Context};
+use dungeon_crawler::assets::{AssetLocator, AssetOverrides};
+use dungeon_crawler::dungeon_crawler::{solve_boards, DungeonCrawler};
+use dungeon_crawler::input_plan::RetryPolicy;
+#[cfg(feature = "textures")]
+use dungeon_crawler::tex;
+#[cfg(feature = "automation")]
+use dungeon_crawler::timing;
+use dungeon_crawler::util::Versioned;
+use dungeon_crawler::{
+    db, editor, layout, monster_search, play, seed_space, server, simulator, sprite_sheet, stats,
+};
+use rand::seq::SliceRandom;
 
-const DB_PATH: &str = "data";
-const DB_FILE: &str = "puzzles.db";
 const PUZZLES_PER_BATCH: usize = 4700;
-#[allow(dead_code)]
-fn collect_puzzles() -> Result<()> {
-    // Create db file if it doesn't exist
-    let db_path = Path::new(DB_PATH).join(DB_FILE);
-    if !db_path.exists() {
-        fs::create_dir_all(DB_PATH)?;
-    }
 
-    // Instantiate dungeon crawler and open db file in append mode
-    let mut dc = dungeon_crawler::DungeonCrawler::new()?;
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .create(true)
-        .open(db_path)
-        .expect("Database file should have been created");
+// Parse random boards into the database until `boards` are saved or Ctrl-C is pressed. Picks up
+// after the last good record of an earlier run.
+#[cfg(feature = "automation")]
+fn collect_puzzles(boards: usize) -> Result<()> {
+    let path = db::path();
+    let (mut db, found) = db::DbWriter::open(&path)?;
+    println!("{}: {found}", path.display());
 
-    // Create buffer to serialize puzzles into
-    let buffer = Vec::new();
-    let mut cursor = Cursor::new(buffer);
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler = stop.clone();
+    ctrlc::set_handler(move || handler.store(true, Ordering::SeqCst))?;
 
+    let mut dc = DungeonCrawler::new()?;
+    let mut collector = db::Collector::new(&found.puzzles);
     let t0 = Instant::now();
+    let result = collector.run(&mut dc, &mut db, boards, &stop);
 
-    for _ in 0..PUZZLES_PER_BATCH {
-        dc.random_board();
-        let puzzle = dc.parse()?;
-        puzzle.serialize(&mut cursor)?;
-    }
-
-    // Append buffer to file
-    file.write_all(&cursor.into_inner())?;
-
+    let saved = collector.stats.saved;
     let elapsed = t0.elapsed();
+    if stop.load(Ordering::SeqCst) {
+        println!("Stopped");
+    }
     println!(
-        "{PUZZLES_PER_BATCH} parsed in {:.02}s. ({:.02}/s)",
+        "{saved} saved in {:.02}s ({:.02}/s), {} in {}",
         elapsed.as_secs_f32(),
-        PUZZLES_PER_BATCH as f32 / elapsed.as_secs_f32()
+        saved as f32 / elapsed.as_secs_f32(),
+        db.records(),
+        path.display()
     );
+    println!("{}", collector.stats);
+    result
+}
 
+// Distributions and per-cell heatmaps over every stored puzzle, as text, with the heatmaps also
+// written as PNGs into `out`
+fn print_db_stats(out: &Path) -> Result<()> {
+    let path = db::path();
+    let scan = db::read(&path)?;
+    if scan.dropped > 0 || scan.damaged > 0 {
+        println!("{}: {scan}", path.display());
+    }
+    let stats = stats::DbStats::new(&scan.puzzles);
+    println!("{stats}");
+    for file in stats.save_heatmaps(out)? {
+        println!("saved {file}");
+    }
     Ok(())
 }
 
-#[allow(dead_code)]
-fn read_puzzles() -> Result<Vec<Puzzle>> {
-    let db_path = Path::new(DB_PATH).join(DB_FILE);
-    let buffer = fs::read(db_path)?;
-
-    let puzzles = buffer
-        .array_chunks::<26>()
-        .map(|chunk| puzzle::Puzzle::deserialize(chunk))
-        .collect::<Result<Vec<puzzle::Puzzle>>>()?;
-
-    Ok(puzzles)
+// Estimate how many seeds RANDOM can give from the order they were collected in, and what the
+// next batch of `boards` should turn up
+fn print_seed_space(boards: usize) -> Result<()> {
+    let scan = db::read(&db::path())?;
+    let seeds: Vec<u32> = scan
+        .puzzles
+        .iter()
+        .filter_map(|puzzle| puzzle.seed)
+        .collect();
+    let space = seed_space::SeedSpace::new(&seeds);
+    println!("{}", seed_space::Report(&space, boards));
+    Ok(())
 }
 
-#[allow(dead_code)]
-fn print_db_info() -> Result<()> {
-    let puzzles = read_puzzles()?;
-
-    let seeds: HashSet<u32> = HashSet::from_iter(
-        puzzles
+// Play a stored board in the terminal, the one with `seed` if given or else any of them
+fn play(seed: Option<u32>) -> Result<()> {
+    let path = db::path();
+    let scan = db::read(&path)?;
+    let puzzle = match seed {
+        Some(seed) => scan
+            .puzzles
             .iter()
-            .map(|puzzle| puzzle.seed.unwrap_or(10000000u32)),
-    );
+            .find(|puzzle| puzzle.seed == Some(seed))
+            .ok_or_else(|| anyhow!("No board with seed {seed} in {}", path.display()))?,
+        None => scan
+            .puzzles
+            .choose(&mut rand::thread_rng())
+            .ok_or_else(|| anyhow!("No boards in {}, collect some first", path.display()))?,
+    };
+    let play = play::run(*puzzle, io::stdin().lock(), io::stdout())?;
+    if play.solved() {
+        println!("Solved!");
+    }
+    Ok(())
+}
 
-    println!("Puzzle database");
-    println!("  count: {}", puzzles.len());
-    println!("  unique: {}", seeds.len());
+// Answer parse, solve and lookup requests from other local tools until killed
+fn serve(port: u16, db_path: PathBuf) -> Result<()> {
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+    println!("Listening on http://{}", listener.local_addr()?);
+    server::serve(listener, db_path)
+}
 
+// Design a board in the terminal, starting from the puzzle in `file` if given. Saves go to `out`,
+// or back to `file`, or designed.txt.
+fn edit(file: Option<&Path>, out: Option<PathBuf>, db_path: PathBuf) -> Result<()> {
+    let text_path = out
+        .or_else(|| file.map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("designed.txt"));
+    let editor = match file.filter(|file| file.exists()) {
+        Some(file) => {
+            let puzzle = fs::read_to_string(file)?
+                .parse()
+                .with_context(|| format!("Couldn't read a puzzle from {}", file.display()))?;
+            editor::Editor::load(puzzle, text_path, db_path)
+        }
+        None => editor::Editor::new(Default::default(), text_path, db_path),
+    };
+    editor::run(editor, io::stdin().lock(), io::stdout())?;
     Ok(())
 }
 
+#[cfg(feature = "automation")]
 #[allow(dead_code)]
 fn parse() -> Result<()> {
-    let mut dc = dungeon_crawler::DungeonCrawler::new()?;
+    let mut dc = DungeonCrawler::new()?;
     dc.parse()?;
     Ok(())
 }
 
-fn solve() -> Result<()> {
-    let mut dc = dungeon_crawler::DungeonCrawler::new()?;
-    let bt = solve::BackTracker {};
-
-    loop {
-        let puzzle = dc.parse()?;
-        println!("{puzzle}");
-        println!("seed: {:?}", puzzle.seed);
-        let solutions = bt.solve(&puzzle);
-
-        // output dir
-        // let path = Path::new("script_output").join("solve_bt");
-        // let mut count = 0;
-        // for solution in solutions.into_iter().step_by(5000) {
-        // for solution in solutions.into_iter() {
-        // dc.enter_solution(solution)?;
-        // dc.save_board_image(None, &path.join(format!("{count:>04}.png")))?;
-        // dc.reset_solution()?;
-        // count += 1;
-        // }
-
-        match solutions.len() {
-            0 => println!("  no solution"),
-            1 => {
-                dc.enter_solution(*solutions.last().unwrap()).unwrap();
-            }
-            _ => {
-                println!("  multiple solutions");
-                for sol in solutions {
-                    println!("    {:064b}", sol);
-                }
-            }
-        }
+#[cfg(feature = "automation")]
+fn solve(retry: RetryPolicy) -> Result<()> {
+    let mut dc = DungeonCrawler::new()?;
+    dc.retry = retry;
+    let pause = dc.timing().board_pause();
+    solve_boards(&mut dc, None, pause)
+}
 
-        thread::sleep(Duration::from_millis(2500));
-        dc.random_board()
-    }
+// Measure the game's redraw times and save them for next time
+#[cfg(feature = "automation")]
+fn calibrate(samples: usize) -> Result<()> {
+    let mut dc = DungeonCrawler::new()?;
+    dc.parse()?;
+    let profile = dc.calibrate(samples)?;
+    let path = timing::TimingProfile::path();
+    profile.save(&path)?;
+    println!("{profile:#?}");
+    println!("saved to {}", path.display());
+    Ok(())
+}
+
+// Run the solve loop against the simulator instead of the game
+fn simulate(boards: usize, seed: u64, retry: RetryPolicy) -> Result<()> {
+    let sim = simulator::SimulatedGame::from_screenshots(Path::new("monster_refs"), seed)?;
+    let mut dc = DungeonCrawler::with_backend(sim.screen(), sim.input())?;
+    dc.retry = retry;
+    solve_boards(&mut dc, Some(boards), Duration::ZERO)?;
+    println!(
+        "{} of {boards} boards solved in {} clicks",
+        sim.wins(),
+        sim.clicks()
+    );
+    Ok(())
 }
 
 fn main() -> Result<()> {
-    if env::args().count() > 1 {
-        tex::decode_all_textures();
-        exit(0);
+    let args = cli::Args::parse(env::args().skip(1))?;
+    let assets = AssetLocator::resolve(AssetOverrides {
+        game_dir: args.value("--game-dir").map(Into::into),
+        output_dir: args.value("--out").map(Into::into),
+    })?
+    .install()?;
+    if let Some(path) = args.value("--layout") {
+        layout::Layout::load(Path::new(path))?.install()?;
     }
 
-    // parse()?;
-    // collect_puzzles()?;
-    // print_db_info()?;
-    // do_stuff();
+    let retry = RetryPolicy {
+        retries: args
+            .parsed("--retries")?
+            .unwrap_or(RetryPolicy::default().retries),
+        ..Default::default()
+    };
 
-    solve()?;
+    match args.command.as_deref() {
+        #[cfg(feature = "automation")]
+        None => {
+            // parse()?;
+            // collect_puzzles()?;
+            // do_stuff();
 
-    Ok(())
+            solve(retry)
+        }
+        #[cfg(feature = "textures")]
+        Some("decode") => tex::decode_all_textures(assets),
+        #[cfg(feature = "textures")]
+        Some("inspect") => tex::inspect_textures(assets, args.switch("--json")),
+        #[cfg(feature = "textures")]
+        Some("extract") => tex::extract_texture(
+            assets,
+            args.positional(0, "name")?,
+            args.parsed("--frame")?,
+            args.value("-o").map(Path::new),
+        ),
+        Some("offsets") => monster_search::find_monster_offsets(assets).map(|_| ()),
+        Some("sprites") => {
+            let monster = args.positional.first().map(String::as_str);
+            let out = match args.value("-o") {
+                Some(out) => PathBuf::from(out),
+                None => assets.decoded(format!("{}.gif", monster.unwrap_or("sprites"))),
+            };
+            let defaults = sprite_sheet::ExportOptions::default();
+            let options = sprite_sheet::ExportOptions {
+                scale: args.parsed("--scale")?.unwrap_or(defaults.scale),
+                delay_ms: args.parsed("--delay")?.unwrap_or(defaults.delay_ms),
+            };
+            sprite_sheet::export_sprites(assets, monster, &out, options)
+        }
+        #[cfg(feature = "automation")]
+        Some("collect") => collect_puzzles(args.parsed("--boards")?.unwrap_or(PUZZLES_PER_BATCH)),
+        Some("db") => match args.positional(0, "db command")? {
+            "stats" => print_db_stats(
+                args.value("-o")
+                    .map_or(Path::new("script_output").join("db_stats"), PathBuf::from)
+                    .as_path(),
+            ),
+            "seeds" => print_seed_space(args.parsed("--boards")?.unwrap_or(PUZZLES_PER_BATCH)),
+            other => Err(anyhow!("Unknown db command '{other}'\n\n{}", cli::USAGE)),
+        },
+        Some("play") => play(args.parsed("--seed")?),
+        Some("edit") => edit(
+            args.positional.first().map(Path::new),
+            args.value("-o").map(PathBuf::from),
+            args.value("--db")
+                .map_or_else(editor::designed_db_path, PathBuf::from),
+        ),
+        Some("serve") => serve(
+            args.parsed("--port")?.unwrap_or(server::DEFAULT_PORT),
+            args.value("--db").map_or_else(db::path, PathBuf::from),
+        ),
+        #[cfg(feature = "automation")]
+        Some("calibrate") => calibrate(args.parsed("--samples")?.unwrap_or(5)),
+        Some("simulate") => simulate(
+            args.parsed("--boards")?.unwrap_or(10),
+            args.parsed("--seed")?.unwrap_or(0),
+            retry,
+        ),
+        #[cfg(not(feature = "automation"))]
+        None | Some("collect" | "calibrate") => Err(anyhow!(
+            "Built without the automation feature, so there's no game window to work with"
+        )),
+        #[cfg(not(feature = "textures"))]
+        Some("decode" | "inspect" | "extract") => Err(anyhow!(
+            "Built without the textures feature, so .tex files can't be decoded"
+        )),
+        Some(command) => Err(anyhow!("Unknown command '{command}'\n\n{}", cli::USAGE)),
+    }
 }
 
 #[allow(dead_code)]
 fn do_stuff() {
-    // scripts::get_large_digit_discriminant();
-    // scripts::tile_bg_colors();
-    // scripts::find_monster_sample_offset();
-    // scripts::print_background_pixels();
+    // scripts::get_large_digit_discriminant(AssetLocator::global());
+    // scripts::tile_bg_colors(AssetLocator::global());
+    // scripts::find_monster_sample_offset(AssetLocator::global());
+    // scripts::print_background_pixels(AssetLocator::global());
 }
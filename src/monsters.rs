@@ -0,0 +1,260 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use image::{GenericImageView, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use crate::assets::AssetLocator;
+use crate::sprite_sheet;
+use crate::util::Versioned;
+
+// Written by the offsets command, read when parsing boards and exporting sprites
+pub const OFFSETS_DIR: &str = "data";
+pub const OFFSETS_FILE: &str = "sprite_offsets.json";
+
+// Bump this when the meaning of a field changes. Files from other versions are rejected rather
+// than misread.
+pub const OFFSETS_VERSION: u32 = 1;
+
+const TILE_SIZE: u32 = 32;
+
+// Fraction of a template's opaque pixels that have to match exactly. Neighbouring sprites can
+// overlap a tile, so this can't demand all of them.
+const MIN_SCORE: f32 = 0.5;
+
+// Where a species' sprite is drawn relative to its tile, and the frame that offset was
+// measured from. Frames are padded to a common size, so the offset holds for all of them.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpriteOffset {
+    pub offset: (i32, i32),
+    pub best_frame: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpriteOffsets {
+    pub version: u32,
+    pub monsters: BTreeMap<String, SpriteOffset>,
+}
+
+impl Default for SpriteOffsets {
+    fn default() -> Self {
+        Self {
+            version: OFFSETS_VERSION,
+            monsters: BTreeMap::new(),
+        }
+    }
+}
+
+impl Versioned for SpriteOffsets {
+    const VERSION: u32 = OFFSETS_VERSION;
+    const DIR: &'static str = OFFSETS_DIR;
+    const FILE: &'static str = OFFSETS_FILE;
+    const NAME: &'static str = "Sprite offsets";
+    const WRITTEN_BY: Option<&'static str> = Some("offsets");
+
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonsterMatch {
+    pub species: String,
+    pub frame: usize,
+    // Fraction of the template's opaque pixels that matched, from 0 to 1
+    pub score: f32,
+}
+
+// One frame of a sprite cut down to the tile it stands on. Pixels the sprite doesn't cover
+// are transparent and ignored when matching.
+struct Template {
+    species: String,
+    frame: usize,
+    tile: RgbaImage,
+    opaque: u32,
+}
+
+pub struct MonsterTemplates {
+    templates: Vec<Template>,
+}
+
+impl MonsterTemplates {
+    // Every frame of every species listed in the offsets file
+    pub fn load(assets: &AssetLocator, offsets: &SpriteOffsets) -> Result<Self> {
+        let mut species = BTreeMap::new();
+        for name in offsets.monsters.keys() {
+            species.insert(
+                name.clone(),
+                sprite_sheet::load_frames(&assets.sprites(name))?,
+            );
+        }
+        Self::from_frames(&species, offsets)
+    }
+
+    pub fn from_frames(
+        species: &BTreeMap<String, Vec<RgbaImage>>,
+        offsets: &SpriteOffsets,
+    ) -> Result<Self> {
+        let mut templates = Vec::new();
+        for (name, frames) in species {
+            let Some(offset) = offsets.monsters.get(name) else {
+                continue;
+            };
+            let max_w = frames.iter().map(|f| f.width()).max().unwrap_or(0) as i32;
+            let max_h = frames.iter().map(|f| f.height()).max().unwrap_or(0) as i32;
+            for (i, frame) in frames.iter().enumerate() {
+                // Same alignment as the sprite sheet exporter: shared bottom right corner,
+                // drawn at -offset from the tile's corner
+                let x = max_w - frame.width() as i32 - offset.offset.0;
+                let y = max_h - frame.height() as i32 - offset.offset.1;
+                let tile = RgbaImage::from_fn(TILE_SIZE, TILE_SIZE, |tx, ty| {
+                    let (fx, fy) = (tx as i32 - x, ty as i32 - y);
+                    if fx < 0 || fy < 0 || fx >= frame.width() as i32 || fy >= frame.height() as i32
+                    {
+                        Rgba([0, 0, 0, 0])
+                    } else {
+                        *frame.get_pixel(fx as u32, fy as u32)
+                    }
+                });
+                let opaque = tile.pixels().filter(|p| p[3] == 255).count() as u32;
+                if opaque > 0 {
+                    templates.push(Template {
+                        species: name.clone(),
+                        frame: i,
+                        tile,
+                        opaque,
+                    });
+                }
+            }
+        }
+
+        if templates.is_empty() {
+            return Err(anyhow!("No monster sprites to match against"));
+        }
+        Ok(Self { templates })
+    }
+
+    // Work out which monster (if any) is standing on the tile whose top left corner is at
+    // (x, y) in img
+    pub fn identify<I>(&self, img: &I, x: u32, y: u32) -> Option<MonsterMatch>
+    where
+        I: GenericImageView<Pixel = Rgba<u8>>,
+    {
+        let mut best: Option<MonsterMatch> = None;
+        for template in &self.templates {
+            let matching = template
+                .tile
+                .enumerate_pixels()
+                .filter(|(tx, ty, p)| {
+                    p[3] == 255
+                        && x + tx < img.width()
+                        && y + ty < img.height()
+                        && img.get_pixel(x + tx, y + ty) == **p
+                })
+                .count();
+            let score = matching as f32 / template.opaque as f32;
+            if best.as_ref().is_none_or(|b| score > b.score) {
+                best = Some(MonsterMatch {
+                    species: template.species.clone(),
+                    frame: template.frame,
+                    score,
+                });
+            }
+        }
+        best.filter(|b| b.score >= MIN_SCORE)
+    }
+}
+
+// The sprite templates are only built once per run. None if there's no offsets file or the
+// sprites haven't been extracted.
+pub fn templates() -> Option<&'static MonsterTemplates> {
+    static TEMPLATES: OnceLock<Option<MonsterTemplates>> = OnceLock::new();
+    TEMPLATES
+        .get_or_init(|| {
+            let offsets = SpriteOffsets::load(&SpriteOffsets::path()).ok()?;
+            MonsterTemplates::load(AssetLocator::global().ok()?, &offsets).ok()
+        })
+        .as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::layout::Layout;
+
+    #[test]
+    fn offsets_file() {
+        let mut offsets = SpriteOffsets::default();
+        offsets.monsters.insert(
+            String::from("demon"),
+            SpriteOffset {
+                offset: (3, 17),
+                best_frame: 2,
+            },
+        );
+        let path = std::env::temp_dir().join(format!("offsets_{}.json", std::process::id()));
+        offsets.save(&path).unwrap();
+        assert_eq!(SpriteOffsets::load(&path).unwrap(), offsets);
+        fs::remove_file(&path).unwrap();
+
+        // Missing until the offsets command has been run
+        let error = SpriteOffsets::load(&path).unwrap_err().to_string();
+        assert!(error.contains("offsets command"), "{error}");
+    }
+
+    #[test]
+    fn offsets_version() {
+        let json = r#"{ "version": 0, "monsters": {} }"#;
+        assert!(SpriteOffsets::from_json(json).is_err());
+    }
+
+    #[test]
+    fn identify_tiles() {
+        // A stand-in for a decoded sprite, made from a monster on a reference screenshot: its
+        // tile's background cleared and the rest padded out to a frame bigger than the tile, the
+        // way the game's sprites are. A second, bigger frame moves where the first one is drawn.
+        let board = image::open("monster_refs/insectoid.png")
+            .unwrap()
+            .to_rgba8();
+        let tiles = Layout::default().board;
+        let (x, y) = tiles.tile_corner(6, 0);
+        let (empty_x, empty_y) = tiles.tile_corner(0, 0);
+        let (sprite_x, sprite_y) = (5, 3);
+        let mut frame = RgbaImage::new(40, 40);
+        for (tx, ty, p) in board.view(x, y, TILE_SIZE, TILE_SIZE).pixels() {
+            if p != *board.get_pixel(empty_x + tx, empty_y + ty) {
+                frame.put_pixel(sprite_x + tx, sprite_y + ty, p);
+            }
+        }
+        let mut bigger = RgbaImage::new(48, 44);
+        image::imageops::replace(&mut bigger, &image::imageops::flip_vertical(&frame), 0, 0);
+        let species = BTreeMap::from([(String::from("insectoid"), vec![frame, bigger])]);
+        let templates = |offset| {
+            let mut offsets = SpriteOffsets::default();
+            offsets.monsters.insert(
+                String::from("insectoid"),
+                SpriteOffset {
+                    offset,
+                    best_frame: 0,
+                },
+            );
+            MonsterTemplates::from_frames(&species, &offsets).unwrap()
+        };
+
+        // Frames share their bottom right corner, so the first sits 8 right and 4 down of the
+        // bigger one's top left
+        let offset = (sprite_x as i32 + 8, sprite_y as i32 + 4);
+        let found = templates(offset).identify(&board, x, y).unwrap();
+        assert_eq!(found.species, "insectoid");
+        assert_eq!(found.frame, 0);
+        assert_eq!(found.score, 1.0);
+
+        // Misaligned, or on an empty tile, nothing matches
+        for offset in [(offset.0 + 2, offset.1), (offset.0, offset.1 - 2)] {
+            assert_eq!(templates(offset).identify(&board, x, y), None);
+        }
+        assert_eq!(templates(offset).identify(&board, empty_x, empty_y), None);
+    }
+}
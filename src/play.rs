@@ -0,0 +1,525 @@
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+};
+
+use anyhow::Result;
+
+use crate::puzzle::{bit, Puzzle, Tile};
+use crate::solve::{self, BackTracker, Solver};
+
+pub const HELP: &str = "\
+w a s d: move   x: wall   o: mark open   ?: hint   r: reveal   c: clear   q: quit
+Type any number of keys, then Enter";
+
+// Plain ANSI escapes, so any terminal will do
+pub(crate) const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+pub(crate) const RESET: &str = "\x1b[0m";
+const MET: &str = "\x1b[32m";
+const OVER: &str = "\x1b[31m";
+pub(crate) const BROKEN: &str = "\x1b[41m";
+
+// Marked open, as opposed to not decided yet
+const OPEN_GLYPH: &str = "_";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Wall,
+    Open,
+    Hint,
+    Reveal,
+    Clear,
+    Quit,
+}
+
+impl Key {
+    pub fn from_char(c: char) -> Option<Key> {
+        Some(match c.to_ascii_lowercase() {
+            'w' => Key::Up,
+            's' => Key::Down,
+            'a' => Key::Left,
+            'd' => Key::Right,
+            'x' => Key::Wall,
+            'o' => Key::Open,
+            '?' | 'h' => Key::Hint,
+            'r' => Key::Reveal,
+            'c' => Key::Clear,
+            'q' => Key::Quit,
+            _ => return None,
+        })
+    }
+}
+
+// First cell of a mask in reading order
+pub(crate) fn first_cell(mask: u64) -> Option<(usize, usize)> {
+    let i = mask.leading_zeros() as usize;
+    (i < 64).then_some((i % 8, i / 8))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Hint {
+    NoSolution,
+    // The tile at (x, y), and what to do about it
+    Tile((usize, usize), &'static str),
+    // Nothing certain left to fill in
+    Done,
+}
+
+impl Display for Hint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Hint::NoSolution => write!(f, "This board has no solution"),
+            Hint::Tile((x, y), what) => write!(f, "Hint: column {x}, row {y} {what}"),
+            Hint::Done => write!(f, "Nothing certain left to fill in"),
+        }
+    }
+}
+
+// A board being played. Walls and open marks are solution style masks, and only ever cover
+// empty tiles.
+pub struct Play {
+    pub puzzle: Puzzle,
+    pub walls: u64,
+    pub open: u64,
+    pub cursor: (usize, usize),
+    pub message: String,
+    // Worked out the first time a hint or reveal needs them
+    solutions: Option<Vec<u64>>,
+}
+
+impl Play {
+    pub fn new(puzzle: Puzzle) -> Self {
+        Self {
+            puzzle,
+            walls: 0,
+            open: 0,
+            cursor: (0, 0),
+            message: String::new(),
+            solutions: None,
+        }
+    }
+
+    pub(crate) fn empty_cells(&self) -> u64 {
+        let mut mask = 0;
+        for (y, row) in self.puzzle.tiles.iter().enumerate() {
+            for (x, &tile) in row.iter().enumerate() {
+                if tile == Tile::Empty {
+                    mask |= bit(x, y);
+                }
+            }
+        }
+        mask
+    }
+
+    // The puzzle with the player's walls on it, the way the validator wants it
+    pub fn board(&self) -> Puzzle {
+        let mut board = self.puzzle;
+        for (y, row) in board.tiles.iter_mut().enumerate() {
+            for (x, tile) in row.iter_mut().enumerate() {
+                if self.walls & bit(x, y) != 0 {
+                    *tile = Tile::Wall;
+                }
+            }
+        }
+        board
+    }
+
+    // Walls placed in each column and row
+    pub fn wall_counts(&self) -> ([u8; 8], [u8; 8]) {
+        let mut columns = [0; 8];
+        let mut rows = [0; 8];
+        for i in 0..64 {
+            if self.walls & bit(i % 8, i / 8) != 0 {
+                columns[i % 8] += 1;
+                rows[i / 8] += 1;
+            }
+        }
+        (columns, rows)
+    }
+
+    fn counts_met(&self) -> bool {
+        self.wall_counts() == (self.puzzle.top_nums, self.puzzle.left_nums)
+    }
+
+    pub fn solved(&self) -> bool {
+        self.counts_met() && solve::violations(&self.board()) == 0
+    }
+
+    // Cells breaking a rule. While walls are still going in, only what can't be fixed by more
+    // walls: monsters walled in, or with two tiles marked open next to them. Once the counts
+    // are met, everything the validator checks.
+    pub fn violations(&self) -> u64 {
+        if self.counts_met() {
+            return solve::violations(&self.board());
+        }
+        let mut bad = 0;
+        for (y, row) in self.puzzle.tiles.iter().enumerate() {
+            for (x, &tile) in row.iter().enumerate() {
+                if tile != Tile::Monster {
+                    continue;
+                }
+                let neighbours: Vec<u64> = [
+                    (x > 0).then(|| bit(x - 1, y)),
+                    (x < 7).then(|| bit(x + 1, y)),
+                    (y > 0).then(|| bit(x, y - 1)),
+                    (y < 7).then(|| bit(x, y + 1)),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                let walled_in = neighbours
+                    .iter()
+                    .all(|&b| self.walls & b != 0 || self.empty_cells() & b == 0);
+                let open = neighbours.iter().filter(|&&b| self.open & b != 0).count();
+                if walled_in || open > 1 {
+                    bad |= bit(x, y);
+                }
+            }
+        }
+        bad
+    }
+
+    fn solutions(&mut self) -> &[u64] {
+        let puzzle = self.puzzle;
+        self.solutions
+            .get_or_insert_with(|| BackTracker.solve(&puzzle))
+    }
+
+    // Returns false once the player quits
+    pub fn press(&mut self, key: Key) -> bool {
+        self.message.clear();
+        let (x, y) = self.cursor;
+        match key {
+            Key::Up => self.cursor.1 = y.saturating_sub(1),
+            Key::Down => self.cursor.1 = (y + 1).min(7),
+            Key::Left => self.cursor.0 = x.saturating_sub(1),
+            Key::Right => self.cursor.0 = (x + 1).min(7),
+            Key::Wall | Key::Open if self.empty_cells() & bit(x, y) == 0 => {
+                self.message = "Only empty tiles take walls or marks".into();
+            }
+            Key::Wall => {
+                self.walls ^= bit(x, y);
+                self.open &= !bit(x, y);
+            }
+            Key::Open => {
+                self.open ^= bit(x, y);
+                self.walls &= !bit(x, y);
+            }
+            Key::Hint => self.hint(),
+            Key::Reveal => self.reveal(),
+            Key::Clear => {
+                self.walls = 0;
+                self.open = 0;
+            }
+            Key::Quit => return false,
+        }
+        if self.message.is_empty() && self.solved() {
+            self.message = "Solved!".into();
+        }
+        true
+    }
+
+    // A tile that's wrong, or failing that one that's certain but not filled in yet. With
+    // several solutions only tiles they all agree on count.
+    pub fn find_hint(&mut self) -> Hint {
+        let empty = self.empty_cells();
+        let (walls, open) = (self.walls, self.open);
+        let solutions = self.solutions();
+        if solutions.is_empty() {
+            return Hint::NoSolution;
+        }
+        let must_wall = solutions.iter().fold(u64::MAX, |acc, s| acc & s);
+        let must_open = solutions.iter().fold(empty, |acc, s| acc & !s);
+
+        let hints = [
+            (walls & must_open, "shouldn't be a wall"),
+            (open & must_wall, "should be a wall, not open"),
+            (must_wall & !walls, "should be a wall"),
+            (must_open & !open, "is open"),
+        ];
+        hints
+            .into_iter()
+            .find_map(|(mask, what)| first_cell(mask).map(|cell| Hint::Tile(cell, what)))
+            .unwrap_or(Hint::Done)
+    }
+
+    fn hint(&mut self) {
+        let hint = self.find_hint();
+        if let Hint::Tile(cell, _) = hint {
+            self.cursor = cell;
+        }
+        self.message = hint.to_string();
+    }
+
+    fn reveal(&mut self) {
+        let empty = self.empty_cells();
+        let solutions = self.solutions();
+        let Some(&solution) = solutions.first() else {
+            self.message = "This board has no solution".into();
+            return;
+        };
+        let count = solutions.len();
+        self.walls = solution;
+        self.open = empty & !solution;
+        if count > 1 {
+            self.message = format!("One of {count} solutions");
+        }
+    }
+
+    // Laid out like `impl Display for Puzzle`, with counts coloured when met or over, broken
+    // tiles highlighted and the cursor in brackets
+    pub fn render(&self) -> String {
+        let (columns, rows) = self.wall_counts();
+        let count = |placed: u8, wanted: u8| {
+            let style = match placed.cmp(&wanted) {
+                std::cmp::Ordering::Less => "",
+                std::cmp::Ordering::Equal => MET,
+                std::cmp::Ordering::Greater => OVER,
+            };
+            match style {
+                "" => wanted.to_string(),
+                style => format!("{style}{wanted}{RESET}"),
+            }
+        };
+        let bad = self.violations();
+        let top = std::array::from_fn(|x| count(columns[x], self.puzzle.top_nums[x]));
+        let left = std::array::from_fn(|y| count(rows[y], self.puzzle.left_nums[y]));
+        draw_board(&top, &left, self.cursor, |x, y| {
+            let glyph = match self.puzzle.tiles[y][x] {
+                _ if self.walls & bit(x, y) != 0 => Tile::Wall.to_str(),
+                _ if self.open & bit(x, y) != 0 => OPEN_GLYPH,
+                tile => tile.to_str(),
+            };
+            highlight(glyph, bad & bit(x, y) != 0, BROKEN)
+        })
+    }
+}
+
+pub(crate) fn highlight(text: &str, on: bool, style: &str) -> String {
+    match on {
+        true => format!("{style}{text}{RESET}"),
+        false => text.to_string(),
+    }
+}
+
+// Laid out like `impl Display for Puzzle` from counts and tiles that are already styled, with
+// the cursor's tile in brackets
+pub(crate) fn draw_board(
+    top: &[String; 8],
+    left: &[String; 8],
+    cursor: (usize, usize),
+    tile: impl Fn(usize, usize) -> String,
+) -> String {
+    let mut out = String::from(" ");
+    for count in top {
+        out += &format!(" {count}");
+    }
+    out.push('\n');
+    for (y, count) in left.iter().enumerate() {
+        out += count;
+        for x in 0..8 {
+            out.push(match (cursor == (x, y), x > 0 && cursor == (x - 1, y)) {
+                (true, _) => '[',
+                (_, true) => ']',
+                _ => ' ',
+            });
+            out += &tile(x, y);
+        }
+        if cursor == (7, y) {
+            out.push(']');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// Play in the terminal until the player quits or input runs out. Each line of input is read as
+// a run of keys.
+pub fn run(puzzle: Puzzle, input: impl BufRead, mut output: impl Write) -> Result<Play> {
+    let mut play = Play::new(puzzle);
+    let mut lines = input.lines();
+    loop {
+        write!(output, "{CLEAR_SCREEN}")?;
+        if let Some(seed) = play.puzzle.seed {
+            writeln!(output, "seed {seed}")?;
+        }
+        writeln!(output, "{}\n{}\n\n{HELP}", play.render(), play.message)?;
+        output.flush()?;
+
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(play);
+        };
+        for c in line.chars().filter(|c| !c.is_whitespace()) {
+            match Key::from_char(c) {
+                Some(key) => {
+                    if !play.press(key) {
+                        return Ok(play);
+                    }
+                }
+                None => play.message = format!("Unknown key '{c}'"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::puzzle::corridor;
+
+    fn press(play: &mut Play, keys: &str) {
+        for c in keys.chars() {
+            play.press(Key::from_char(c).unwrap());
+        }
+    }
+
+    #[test_case("", (0, 0))]
+    #[test_case("ddds", (3, 1))]
+    #[test_case("aaaww", (0, 0))]
+    #[test_case("dddddddddd", (7, 0))]
+    #[test_case("ssssssssssa", (0, 7))]
+    fn cursor(keys: &str, at: (usize, usize)) {
+        let mut play = Play::new(corridor().0);
+        press(&mut play, keys);
+        assert_eq!(play.cursor, at);
+    }
+
+    #[test]
+    fn walls_and_marks() {
+        let mut play = Play::new(corridor().0);
+        press(&mut play, "ddx");
+        assert_eq!(play.walls, bit(2, 0));
+        assert_eq!(play.wall_counts().0[2], 1);
+
+        // Marking open takes the wall away, and walls take marks away
+        press(&mut play, "o");
+        assert_eq!((play.walls, play.open), (0, bit(2, 0)));
+        press(&mut play, "x");
+        assert_eq!((play.walls, play.open), (bit(2, 0), 0));
+
+        // Monsters can't be walled
+        press(&mut play, "ax");
+        assert_eq!(play.walls, bit(2, 0));
+        assert!(!play.message.is_empty());
+
+        press(&mut play, "c");
+        assert_eq!((play.walls, play.open), (0, 0));
+    }
+
+    #[test]
+    fn live_violations() {
+        let mut play = Play::new(corridor().0);
+        assert_eq!(play.violations(), 0);
+
+        // Walls either side of the top monster box it in, the monster below isn't a way out
+        press(&mut play, "xddx");
+        assert_eq!(play.violations(), bit(1, 0));
+        press(&mut play, "x");
+        assert_eq!(play.violations(), 0);
+
+        // Two ways out marked open
+        let mut play = Play::new(corridor().0);
+        press(&mut play, "oddo");
+        assert_eq!(play.violations(), bit(1, 0));
+    }
+
+    #[test]
+    fn full_check_once_counts_are_met() {
+        let (puzzle, solution) = corridor();
+        let mut play = Play::new(puzzle);
+        play.walls = solution;
+        assert!(play.solved());
+
+        // A wall across the corridor, with counts to match. The monster beside it has no way
+        // out and the top of the corridor is cut off.
+        play.walls = solution | bit(0, 3);
+        (play.puzzle.top_nums, play.puzzle.left_nums) = play.wall_counts();
+        assert!(!play.solved());
+        assert_eq!(
+            play.violations(),
+            bit(1, 3) | bit(0, 0) | bit(0, 1) | bit(0, 2)
+        );
+    }
+
+    #[test]
+    fn hint_and_reveal() {
+        let (puzzle, solution) = corridor();
+        let mut play = Play::new(puzzle);
+
+        // Nothing wrong yet, so the first missing wall
+        press(&mut play, "?");
+        assert_eq!(play.cursor, (2, 0));
+        assert!(
+            play.message.ends_with("should be a wall"),
+            "{}",
+            play.message
+        );
+
+        // A wrong wall comes first
+        play.cursor = (0, 3);
+        press(&mut play, "x?");
+        assert_eq!(play.cursor, (0, 3));
+        assert!(
+            play.message.ends_with("shouldn't be a wall"),
+            "{}",
+            play.message
+        );
+
+        press(&mut play, "r");
+        assert_eq!(play.walls, solution);
+        assert_eq!(play.open, play.empty_cells() & !solution);
+        assert!(play.solved());
+        assert_eq!(play.message, "Solved!");
+
+        press(&mut play, "?");
+        assert_eq!(play.message, "Nothing certain left to fill in");
+    }
+
+    #[test]
+    fn render() {
+        let (puzzle, _) = corridor();
+        let mut play = Play::new(puzzle);
+        press(&mut play, "sxo");
+        let text = play.render();
+        let lines: Vec<&str> = text.lines().collect();
+        // A count of 0 is met before any walls go in
+        assert_eq!(lines[0], format!("  {MET}0{RESET} 1 7 7 7 7 7 7"));
+        assert_eq!(lines[1], "6 • M • • • • • •");
+        assert_eq!(lines[2], "6[_]M • • • • • •");
+
+        // Counts go green when met and red when over
+        play.cursor = (0, 7);
+        press(&mut play, "x");
+        let text = play.render();
+        assert!(text.contains(&format!("{OVER}0{RESET}[W]")), "{text}");
+        assert!(text.starts_with(&format!("  {OVER}0{RESET} 1")), "{text}");
+
+        // Monster with two tiles marked open
+        play.cursor = (0, 0);
+        press(&mut play, "oddo");
+        assert!(play.render().contains(&format!(" _ {BROKEN}M{RESET}[_]")));
+    }
+
+    #[test]
+    fn runs_on_plain_io() {
+        let (puzzle, solution) = corridor();
+        let mut output = Vec::new();
+        let play = run(puzzle, Cursor::new("dd z\nr\nq\nd\n"), &mut output).unwrap();
+        assert_eq!(play.walls, solution);
+        // Quit before the last line was read
+        assert_eq!(play.cursor, (2, 0));
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Unknown key 'z'"));
+        assert!(output.contains("Solved!"));
+        assert!(output.contains(HELP));
+    }
+}
@@ -1,39 +1,38 @@
 use std::{
     fmt::{Display, Write},
     io::{Cursor, Read},
+    path::Path,
+    str::FromStr,
 };
 
 use byteorder::{ReadBytesExt, LE};
+use image::{open, DynamicImage, GenericImageView, Rgba, RgbaImage, SubImage};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "automation")]
 use thiserror::Error;
-use xcap::{
-    image::{open, DynamicImage, GenericImageView, Rgba, RgbaImage, SubImage},
-    XCapError,
-};
+#[cfg(feature = "automation")]
+use xcap::XCapError;
 
 use anyhow::anyhow;
 use anyhow::Result;
 
-use crate::util::draw_rect;
-
-const TILE_STRIDE: u32 = 33;
-const TILE_SIZE: u32 = 32;
-const TILE_SAMPLE_POINT: (u32, u32) = (16, 12);
-
-const BOARD_BASE: (u32, u32) = (49, 175);
-const BOARD_SIZE: (u32, u32) = (264, 265);
+use crate::digits::{self, DigitTemplates, DIGIT_OFFSETS};
+use crate::layout::{Board, Layout, SeedStrip};
+use crate::monsters::{self, MonsterTemplates};
+use crate::util::{debug_images, draw_rect, save_debug_image};
 
-const TOP_NUMS_BASE: (u32, u32) = (55, 138);
-const TOP_NUMS_SIZE: (u32, u32) = (263, 32);
-const TOP_NUMS_OFFSETS: [u32; 8] = [1, 0, 0, 0, 0, 0, 0, 0];
+// Seed digits are drawn with this red channel, and told apart by the pattern of ink in their
+// first column (top pixel is the high bit)
+pub(crate) const SEED_INK_RED: u8 = 52;
+pub(crate) const SEED_COLUMN_HASHES: [u32; 10] = [28, 33, 17, 18, 8, 122, 62, 16, 54, 50];
 
-const LEFT_NUMS_BASE: (u32, u32) = (19, 174);
-const LEFT_NUMS_SIZE: (u32, u32) = (32, 263);
-const LEFT_NUMS_OFFSETS: [u32; 8] = [0, 2, 2, 1, 1, 2, 2, 1];
-
-const SEED_BASE: (u32, u32) = (109, 103);
-const SEED_SIZE: (u32, u32) = (63, 7);
-const SEED_OFFSETS: [u32; 10] = [8, 5, 8, 8, 7, 8, 8, 8, 8, 8];
+// Serialized seeds only need 27 bits, the rest of the seed word is used for flags and the
+// overflow of the packed wall counts.
+const SEED_MASK: u32 = (1 << 27) - 1;
+const PACKED_COUNTS_SHIFT: u32 = 27;
+const PACKED_COUNTS_FLAG: u32 = 1 << 31;
 
+#[cfg(feature = "automation")]
 #[derive(Debug, Error)]
 pub enum ParseError {
     #[error("xcap")]
@@ -41,7 +40,7 @@ pub enum ParseError {
 }
 
 #[rustfmt::skip]
-const BACKGROUND_PIXELS: [[[u8; 4]; 8]; 8] = [
+pub(crate) const BACKGROUND_PIXELS: [[[u8; 4]; 8]; 8] = [
     [[176, 128, 93, 255], [55, 58, 59, 255], [125, 113, 90, 255], [54, 58, 55, 255], [176, 128, 93, 255], [55, 58, 59, 255], [125, 113, 90, 255], [54, 58, 55, 255], ],
     [[ 55,  58, 59, 255], [56, 59, 55, 255], [ 54,  58, 55, 255], [54, 58, 55, 255], [55, 58, 59, 255], [55, 58, 59, 255], [54, 58, 55, 255], [54, 58, 55, 255], ],
     [[ 57,  59, 55, 255], [55, 58, 59, 255], [ 54,  58, 55, 255], [54, 56, 58, 255], [54, 58, 55, 255], [54, 58, 55, 255], [57, 59, 55, 255], [55, 58, 59, 255], ],
@@ -51,9 +50,9 @@ const BACKGROUND_PIXELS: [[[u8; 4]; 8]; 8] = [
     [[ 57,  59, 55, 255], [55, 58, 59, 255], [ 54,  58, 55, 255], [54, 56, 58, 255], [54, 58, 55, 255], [54, 58, 55, 255], [57, 59, 55, 255], [55, 58, 59, 255], ],
     [[ 55,  58, 59, 255], [55, 58, 59, 255], [ 54,  58, 55, 255], [54, 58, 55, 255], [54, 58, 55, 255], [54, 58, 55, 255], [55, 58, 59, 255], [55, 58, 59, 255], ],
 ];
-const TREASURE_COLOR: [u8; 4] = [220, 170, 109, 255];
+pub(crate) const TREASURE_COLOR: [u8; 4] = [220, 170, 109, 255];
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Tile {
     #[default]
     Empty,
@@ -63,7 +62,7 @@ pub enum Tile {
 }
 
 impl Tile {
-    const fn to_str(&self) -> &str {
+    pub(crate) const fn to_str(self) -> &'static str {
         match self {
             Tile::Empty => "•",
             Tile::Wall => "W",
@@ -72,6 +71,17 @@ impl Tile {
         }
     }
 
+    // Also takes . for empty, which is easier to type
+    pub(crate) fn from_glyph(glyph: &str) -> Option<Tile> {
+        Some(match glyph {
+            "•" | "." => Tile::Empty,
+            "W" => Tile::Wall,
+            "T" => Tile::Treasure,
+            "M" => Tile::Monster,
+            _ => return None,
+        })
+    }
+
     pub fn is_monster(self) -> u64 {
         if self == Tile::Monster {
             1
@@ -81,7 +91,41 @@ impl Tile {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Default)]
+// The tile at (x, y) in a wall or solution mask, the first tile in the top bit
+pub(crate) fn bit(x: usize, y: usize) -> u64 {
+    1 << (63 - (y * 8 + x))
+}
+
+// One solution the backtracker finds quickly, even in a debug build: a corridor down the left
+// and along the bottom, with a column of monsters next to it. Comes with that solution.
+#[cfg(test)]
+pub(crate) fn corridor() -> (Puzzle, u64) {
+    let mut puzzle = Puzzle {
+        top_nums: [0, 1, 7, 7, 7, 7, 7, 7],
+        left_nums: [6, 6, 6, 6, 6, 6, 7, 0],
+        ..Default::default()
+    };
+    for row in &mut puzzle.tiles[..6] {
+        row[1] = Tile::Monster;
+    }
+    let walls = (0..6).fold(0u64, |acc, _| acc << 8 | 0b0011_1111);
+    (puzzle, walls << 16 | 0b0111_1111 << 8)
+}
+
+// The puzzle with walls drawn in wherever the mask has one
+#[cfg(test)]
+pub(crate) fn with_walls(puzzle: &Puzzle, walls: u64) -> Puzzle {
+    let mut puzzle = *puzzle;
+    for (i, tile) in puzzle.tiles.as_flattened_mut().iter_mut().enumerate() {
+        if walls & bit(i % 8, i / 8) != 0 {
+            *tile = Tile::Wall;
+        }
+    }
+    puzzle
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Puzzle {
     pub tiles: [[Tile; 8]; 8],
     pub top_nums: [u8; 8],
@@ -91,79 +135,41 @@ pub struct Puzzle {
 
 impl Puzzle {
     pub fn from_image(img: SubImage<&RgbaImage>) -> Result<Self> {
-        // Crop regions for the board, seed, and wall counts
-        let tiles = img.view(BOARD_BASE.0, BOARD_BASE.1, BOARD_SIZE.0, BOARD_SIZE.1);
-        let top_nums = img.view(
-            TOP_NUMS_BASE.0,
-            TOP_NUMS_BASE.1,
-            TOP_NUMS_SIZE.0,
-            TOP_NUMS_SIZE.1,
-        );
-        let left_nums = img.view(
-            LEFT_NUMS_BASE.0,
-            LEFT_NUMS_BASE.1,
-            LEFT_NUMS_SIZE.0,
-            LEFT_NUMS_SIZE.1,
-        );
-        let seed = img.view(SEED_BASE.0, SEED_BASE.1, SEED_SIZE.0, SEED_SIZE.1);
+        Self::from_image_with(img, Layout::global()?)
+    }
 
-        #[cfg(debug_assertions)]
-        {
-            tiles
-                .to_image()
-                .save("script_output/segmentation/tiles_img.png")?;
-            top_nums
-                .to_image()
-                .save("script_output/segmentation/top_nums_img.png")?;
-            left_nums
-                .to_image()
-                .save("script_output/segmentation/left_nums_img.png")?;
-            seed.to_image()
-                .save("script_output/segmentation/seed.png")?;
+    pub fn from_image_with(img: SubImage<&RgbaImage>, layout: &Layout) -> Result<Self> {
+        // Crop regions for the board, seed, and wall counts
+        let tiles = layout.board.region.view(&img);
+        let top_nums = layout.top_counts.region.view(&img);
+        let left_nums = layout.left_counts.region.view(&img);
+        let seed = layout.seed.region.view(&img);
+
+        if debug_images() {
+            let dir = Path::new("script_output/segmentation");
+            save_debug_image(&tiles.to_image(), dir.join("tiles_img.png"));
+            save_debug_image(&top_nums.to_image(), dir.join("top_nums_img.png"));
+            save_debug_image(&left_nums.to_image(), dir.join("left_nums_img.png"));
+            save_debug_image(&seed.to_image(), dir.join("seed.png"));
 
             let mut img = img.to_image();
-            draw_rect(
-                &mut img,
-                BOARD_BASE.0,
-                BOARD_BASE.1,
-                BOARD_SIZE.0,
-                BOARD_SIZE.1,
-                [255, 0, 0, 128],
-            );
-
-            draw_rect(
-                &mut img,
-                TOP_NUMS_BASE.0,
-                TOP_NUMS_BASE.1,
-                TOP_NUMS_SIZE.0,
-                TOP_NUMS_SIZE.1,
-                [0, 128, 255, 128],
-            );
-
-            draw_rect(
-                &mut img,
-                LEFT_NUMS_BASE.0,
-                LEFT_NUMS_BASE.1,
-                LEFT_NUMS_SIZE.0,
-                LEFT_NUMS_SIZE.1,
-                [0, 128, 255, 128],
-            );
-
-            draw_rect(
-                &mut img,
-                SEED_BASE.0,
-                SEED_BASE.1,
-                SEED_SIZE.0,
-                SEED_SIZE.1,
-                [0, 255, 0, 128],
-            );
+            for (region, col) in [
+                (layout.board.region, [255, 0, 0, 128]),
+                (layout.top_counts.region, [0, 128, 255, 128]),
+                (layout.left_counts.region, [0, 128, 255, 128]),
+                (layout.seed.region, [0, 255, 0, 128]),
+            ] {
+                let (x, y, w, h) = region.rect();
+                draw_rect(&mut img, x, y, w, h, col);
+            }
 
-            img.save("script_output/segmentation/overlay.png")?;
+            save_debug_image(&img, dir.join("overlay.png"));
         }
 
-        let tiles = parse_tiles(tiles);
-        let (top_nums, left_nums) = parse_wall_counts(top_nums, left_nums)?;
-        let seed = parse_seed(seed)?;
+        let tiles = parse_tiles(tiles, &layout.board, monsters::templates())?;
+        let (top_nums, left_nums) =
+            parse_wall_counts(top_nums, left_nums, layout, digits::templates())?;
+        let seed = parse_seed(seed, &layout.seed)?;
 
         Ok(Self {
             tiles,
@@ -173,56 +179,57 @@ impl Puzzle {
         })
     }
 
-    pub fn draw_parsing_overlay(&self, img: SubImage<&RgbaImage>) {
+    pub fn draw_parsing_overlay(&self, img: SubImage<&RgbaImage>, layout: &Layout) {
         // Create mutable clone of the image view
         let mut img = img.to_image();
 
-        #[cfg(debug_assertions)]
-        let mut hash: u64 = 0;
         for tile_y in 0..8 {
             for tile_x in 0..8 {
-                #[cfg(debug_assertions)]
-                let hash_bit = tile_y * 8 + tile_x;
                 let col = match self.tiles[tile_y][tile_x] {
                     Tile::Treasure => [0, 255, 0, 64],
                     Tile::Monster => [255, 0, 0, 64],
                     _ => [0, 0, 0, 0],
                 };
 
-                #[cfg(debug_assertions)]
-                if col != [0, 0, 0, 0] {
-                    hash |= 1 << hash_bit;
-                }
-
-                draw_rect(
-                    &mut img,
-                    tile_x as u32 * TILE_STRIDE + BOARD_BASE.0,
-                    tile_y as u32 * TILE_STRIDE + BOARD_BASE.1,
-                    TILE_SIZE,
-                    TILE_SIZE,
-                    col,
-                );
+                let (x, y, w, h) = layout.board.tile_rect(tile_x, tile_y);
+                draw_rect(&mut img, x, y, w, h, col);
             }
         }
 
-        const DIGIT_OFFSETS: &[u32] = &[4, 32, 60, 88, 116, 144, 172, 200, 228];
-        let nums_overlay = open("nums_overlay.png").unwrap();
+        // The digit overlay is optional, the tile overlay is still useful without it
+        if let Ok(nums_overlay) = open("nums_overlay.png") {
+            for i in 0..8 {
+                let src_x = DIGIT_OFFSETS[self.top_nums[i] as usize];
+                let (dst_x, dst_y) = layout.top_count_corner(i);
+                overlay_img(&nums_overlay, &mut img, src_x, 0, dst_x, dst_y, 24, 28);
 
-        for i in 0..8 {
-            let src_x = DIGIT_OFFSETS[self.top_nums[i] as usize];
-            let dst_x = TOP_NUMS_BASE.0 + TOP_NUMS_OFFSETS[i] + i as u32 * TILE_STRIDE;
-            let dst_y = TOP_NUMS_BASE.1;
-            overlay_img(&nums_overlay, &mut img, src_x, 0, dst_x, dst_y, 24, 28);
-
-            let src_x = DIGIT_OFFSETS[self.left_nums[i] as usize];
-            let dst_x = LEFT_NUMS_BASE.0;
-            let dst_y = LEFT_NUMS_BASE.1 + LEFT_NUMS_OFFSETS[i] + i as u32 * TILE_STRIDE;
-            overlay_img(&nums_overlay, &mut img, src_x, 0, dst_x, dst_y, 24, 28);
+                let src_x = DIGIT_OFFSETS[self.left_nums[i] as usize];
+                let (dst_x, dst_y) = layout.left_count_corner(i);
+                overlay_img(&nums_overlay, &mut img, src_x, 0, dst_x, dst_y, 24, 28);
+            }
         }
 
-        #[cfg(debug_assertions)]
-        img.save(format!("script_output/parsing/{hash}.png"))
-            .unwrap();
+        save_debug_image(
+            &img,
+            format!("script_output/parsing/{:016x}.png", self.board_hash()),
+        );
+    }
+
+    // Walls the player has placed, as a solution mask. Only tiles that were empty when the
+    // puzzle was parsed can take a wall, and a wall covers the tile's sample point.
+    pub fn placed_walls(&self, img: SubImage<&RgbaImage>, board: &Board) -> u64 {
+        let mut walls = 0;
+        for (tile_y, row) in self.tiles.iter().enumerate() {
+            for (tile_x, tile) in row.iter().enumerate() {
+                let (px, py) = board.sample_point(tile_x, tile_y);
+                if *tile == Tile::Empty
+                    && img.get_pixel(px, py).0 != BACKGROUND_PIXELS[tile_y][tile_x]
+                {
+                    walls |= bit(tile_x, tile_y);
+                }
+            }
+        }
+        walls
     }
 
     // The generic parameter T lets me serialize to a buffer or directly to a file
@@ -230,11 +237,13 @@ impl Puzzle {
     where
         T: std::io::Write + byteorder::WriteBytesExt,
     {
-        // Concatenate top and left numbers, fold them 3 bits at a time into a u64
+        // Concatenate top and left numbers and fold them into a u64 as base 9 digits, so counts
+        // of 0-8 all fit. This takes 51 bits: the low 48 are stored with the wall counts and
+        // the top 3 are tucked into the seed word.
         let wall_counts: u64 = [self.top_nums, self.left_nums]
             .concat()
             .iter()
-            .fold(0, |acc, &x| (acc << 3) + x.min(7) as u64);
+            .fold(0, |acc, &x| acc * 9 + x.min(8) as u64);
 
         // Iterate over each tile, mapping each monster to 1, else 0. Fold into a u64
         let monster_locations = self
@@ -252,12 +261,16 @@ impl Puzzle {
             .map(|tile| if let Tile::Treasure = tile { 1 } else { 0 })
             .fold(0, |acc, x| (acc << 1) + x as u64);
 
-        // Seed is 0 for None or (seed + 1) for Some
-        if let Some(seed) = self.seed {
-            cursor.write_u32::<LE>(seed + 1)?;
-        } else {
-            cursor.write_u32::<LE>(0)?;
-        }
+        // Seed is 0 for None or (seed + 1) for Some. The flag marks the base 9 wall counts,
+        // records without it use the old 3 bits per count layout.
+        let seed = match self.seed {
+            None => 0,
+            Some(seed) if seed < SEED_MASK => seed + 1,
+            Some(seed) => return Err(anyhow!("Seed {seed} is too big to store")),
+        };
+        let seed_word =
+            PACKED_COUNTS_FLAG | ((wall_counts >> 48) as u32) << PACKED_COUNTS_SHIFT | seed;
+        cursor.write_u32::<LE>(seed_word)?;
         cursor.write_all(&wall_counts.to_le_bytes()[0..6])?;
         cursor.write_u64::<LE>(monster_locations)?;
         cursor.write_u64::<LE>(treasure_locations)?;
@@ -268,33 +281,46 @@ impl Puzzle {
     pub fn deserialize(bytes: &[u8; 26]) -> Result<Self> {
         let mut cursor = Cursor::new(bytes);
         // Read the seed first. Subtract 1 if it was non-zero.
-        let seed = cursor.read_u32::<LE>()?;
+        let seed_word = cursor.read_u32::<LE>()?;
+        let packed = seed_word & PACKED_COUNTS_FLAG != 0;
+        let seed = seed_word & SEED_MASK;
         let seed = if seed == 0 { None } else { Some(seed - 1) };
 
         // Read the packed wall counts into an 8 byte buffer and convert to a u64
         let mut wall_counts: [u8; 8] = Default::default();
         cursor.read_exact(&mut wall_counts[0..6])?;
-        let wall_counts = u64::from_le_bytes(wall_counts);
-
-        // Decode the top and left counts with reverse shift and mask operations
-        let top_nums =
-            [0, 1, 2, 3, 4, 5, 6, 7].map(|i| (wall_counts >> (45 - (i * 3))) as u8 & 0b111);
-        let left_nums =
-            [0, 1, 2, 3, 4, 5, 6, 7].map(|i| (wall_counts >> (21 - (i * 3))) as u8 & 0b111);
+        let mut wall_counts = u64::from_le_bytes(wall_counts);
+
+        let mut top_nums = [0; 8];
+        let mut left_nums = [0; 8];
+        if packed {
+            // Restore the top bits from the seed word, then peel off base 9 digits in reverse
+            wall_counts |= (((seed_word >> PACKED_COUNTS_SHIFT) & 0b111) as u64) << 48;
+            for count in left_nums.iter_mut().rev().chain(top_nums.iter_mut().rev()) {
+                *count = (wall_counts % 9) as u8;
+                wall_counts /= 9;
+            }
+        } else {
+            // Decode the top and left counts with reverse shift and mask operations
+            top_nums =
+                [0, 1, 2, 3, 4, 5, 6, 7].map(|i| (wall_counts >> (45 - (i * 3))) as u8 & 0b111);
+            left_nums =
+                [0, 1, 2, 3, 4, 5, 6, 7].map(|i| (wall_counts >> (21 - (i * 3))) as u8 & 0b111);
+        }
 
         // Read and unpack the monster/treasure locations
         let monster_locations = cursor.read_u64::<LE>()?;
         let treasure_locations = cursor.read_u64::<LE>()?;
         let mut tiles = [[Tile::Empty; 8]; 8];
-        for y in 0..8 {
-            for x in 0..8 {
+        for (y, row) in tiles.iter_mut().enumerate() {
+            for (x, tile) in row.iter_mut().enumerate() {
                 let i = y * 8 + x;
                 let monster = monster_locations & (1 << (63 - i)) > 0;
                 let treasure = treasure_locations & (1 << (63 - i)) > 0;
                 if monster {
-                    tiles[y][x] = Tile::Monster;
+                    *tile = Tile::Monster;
                 } else if treasure {
-                    tiles[y][x] = Tile::Treasure;
+                    *tile = Tile::Treasure;
                 }
             }
         }
@@ -323,15 +349,25 @@ fn count_to_digit(count: usize) -> Result<u8> {
     })
 }
 
-fn parse_tiles(img: SubImage<&RgbaImage>) -> [[Tile; 8]; 8] {
+// Tiles are told apart by a single sample point. When the monster sprites are available,
+// anything that isn't background or treasure also has to match one of them.
+fn parse_tiles(
+    img: SubImage<&RgbaImage>,
+    board: &Board,
+    templates: Option<&MonsterTemplates>,
+) -> Result<[[Tile; 8]; 8]> {
     // Input image is a cropped view of only the tiles
     let mut tiles = [[Tile::Empty; 8]; 8];
     for tile_y in 0..8usize {
         for tile_x in 0..8usize {
             // Lookup the background color for the current tile
             let bg_color = BACKGROUND_PIXELS[tile_y][tile_x];
-            let px = tile_x as u32 * TILE_STRIDE + TILE_SAMPLE_POINT.0;
-            let py = tile_y as u32 * TILE_STRIDE + TILE_SAMPLE_POINT.1;
+            let corner = (
+                tile_x as u32 * board.tile_stride,
+                tile_y as u32 * board.tile_stride,
+            );
+            let px = corner.0 + board.sample_point.0;
+            let py = corner.1 + board.sample_point.1;
 
             // Fetch the color at the tile's sample point
             let sample = img.get_pixel(px, py).0;
@@ -341,61 +377,87 @@ fn parse_tiles(img: SubImage<&RgbaImage>) -> [[Tile; 8]; 8] {
                 tiles[tile_y][tile_x] = Tile::Empty;
             } else if sample == TREASURE_COLOR {
                 tiles[tile_y][tile_x] = Tile::Treasure;
+            } else if templates
+                .is_some_and(|templates| templates.identify(&*img, corner.0, corner.1).is_none())
+            {
+                // Not background or treasure, but none of the sprites either, so something is
+                // in the way or the offsets are out of date
+                return Err(anyhow!(
+                    "Tile {tile_x},{tile_y} matches none of the monster sprites"
+                ));
             } else {
                 tiles[tile_y][tile_x] = Tile::Monster;
             }
         }
     }
 
-    tiles
+    Ok(tiles)
 }
-// Pass in two subimages cropped to the numbers on the top and left sides
+// Pass in two subimages cropped to the numbers on the top and left sides. Digits are matched
+// against the font sheet templates when they're available, otherwise fall back to the pixel
+// count discriminant, which only knows 0-7.
 fn parse_wall_counts(
     top_img: SubImage<&RgbaImage>,
     left_img: SubImage<&RgbaImage>,
+    layout: &Layout,
+    templates: Option<&DigitTemplates>,
 ) -> Result<([u8; 8], [u8; 8])> {
     let mut top_nums = [0; 8];
     let mut left_nums = [0; 8];
+    let stride = layout.board.tile_stride;
     for i in 0..8 {
-        // Crop the image to a single digit, threshold, and count pixels
-        let top_x = i * TILE_STRIDE + 3 + TOP_NUMS_OFFSETS[i as usize];
-        let top_y = 11;
-        let top_count = top_img
-            .view(top_x, top_y, 4, 2)
-            .pixels()
-            .map(|(_, _, p)| p.0)
-            .filter(|&[_r, g, b, _a]| g == 91 && b >= 69 && b <= 78)
-            .count();
-
-        // Repeat for the numbers on the left side
-        let left_x = 3;
-        let left_y = i * TILE_STRIDE + 11 + LEFT_NUMS_OFFSETS[i as usize];
-        let left_count = left_img
-            .view(left_x, left_y, 4, 2)
-            .pixels()
-            .map(|(_, _, p)| p.0)
-            .filter(|&[_r, g, b, _a]| g == 91 && b >= 69 && b <= 78)
-            .count();
+        // Top left corner of each digit's cell
+        let top = (i * stride + layout.top_counts.nudges[i as usize], 0);
+        let left = (0, i * stride + layout.left_counts.nudges[i as usize]);
 
-        // Map the counts to digits using the discriminant values
-        top_nums[i as usize] = count_to_digit(top_count)?;
-        left_nums[i as usize] = count_to_digit(left_count)?;
+        if let Some(templates) = templates {
+            top_nums[i as usize] = templates.recognise(&*top_img, top.0, top.1)?.digit;
+            left_nums[i as usize] = templates.recognise(&*left_img, left.0, left.1)?.digit;
+        } else {
+            top_nums[i as usize] = discriminate_digit(&top_img, top)?;
+            left_nums[i as usize] = discriminate_digit(&left_img, left)?;
+        }
     }
 
     Ok((top_nums, left_nums))
 }
-fn parse_seed(img: SubImage<&RgbaImage>) -> Result<Option<u32>> {
-    let mut seed = 0u32;
-    let mut seed_present = false;
+
+// Crop the image to a small window of the digit, threshold, and count pixels
+fn discriminate_digit(img: &SubImage<&RgbaImage>, cell: (u32, u32)) -> Result<u8> {
+    let count = img
+        .view(cell.0 + 3, cell.1 + 11, 4, 2)
+        .pixels()
+        .filter(|(_, _, p)| digits::ink_colour(p.0).is_some())
+        .count();
+
+    // Map the counts to digits using the discriminant values
+    count_to_digit(count)
+}
+
+fn parse_seed(img: SubImage<&RgbaImage>, strip: &SeedStrip) -> Result<Option<u32>> {
+    let digits = seed_digits(&img, strip)?;
+
+    // If there was no seed, return None (for curated puzzles with no seed)
+    Ok((!digits.is_empty()).then(|| {
+        digits
+            .iter()
+            .fold(0, |seed, &(_, digit)| seed * 10 + digit as u32)
+    }))
+}
+
+// Each digit of the seed and the column it starts at, from the left
+pub(crate) fn seed_digits(img: &SubImage<&RgbaImage>, strip: &SeedStrip) -> Result<Vec<(u32, u8)>> {
+    let mut digits = Vec::new();
     let mut x = 0;
+    let (width, height) = strip.region.size;
 
     // Scan from the left
-    while x < SEED_SIZE.0 {
+    while x < width {
         // Compute the hash
         let hash = img
-            .view(x, 0, 1, SEED_SIZE.1)
+            .view(x, 0, 1, height)
             .pixels()
-            .map(|(_x, _y, Rgba([r, _g, _b, _a]))| if r == 52 { 1 } else { 0 })
+            .map(|(_x, _y, Rgba([r, _g, _b, _a]))| if r == SEED_INK_RED { 1 } else { 0 })
             .fold(0, |acc, x| (acc << 1) + x);
 
         // Column was empty, move to the next column
@@ -404,30 +466,17 @@ fn parse_seed(img: SubImage<&RgbaImage>) -> Result<Option<u32>> {
             continue;
         }
 
-        seed_present = true;
-
         // Map the hash back to a digit
-        let digit = match hash {
-            28 => 0,
-            33 => 1,
-            17 => 2,
-            18 => 3,
-            8 => 4,
-            122 => 5,
-            62 => 6,
-            16 => 7,
-            54 => 8,
-            50 => 9,
-            _ => return Err(anyhow!("Seed parse error")),
-        };
+        let digit = SEED_COLUMN_HASHES
+            .iter()
+            .position(|&h| h == hash)
+            .ok_or_else(|| anyhow!("Seed parse error"))?;
 
-        // Keep running tally of the seed as we find each digit
-        seed = seed * 10 + digit;
-        x += SEED_OFFSETS[digit as usize];
+        digits.push((x, digit as u8));
+        x += strip.digit_widths[digit];
     }
 
-    // If there was no seed, return None (for curated puzzles with no seed)
-    Ok(seed_present.then_some(seed))
+    Ok(digits)
 }
 
 impl Display for Puzzle {
@@ -451,6 +500,59 @@ impl Display for Puzzle {
     }
 }
 
+// Reads what Display writes: the column counts, then a line per row starting with its count.
+// There's no seed in the text, so it comes back as None.
+impl FromStr for Puzzle {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let mut puzzle = Puzzle::default();
+
+        let header = lines.next().ok_or_else(|| anyhow!("No column counts"))?;
+        let counts: Vec<u8> = header
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| anyhow!("Bad column counts '{header}'"))?;
+        puzzle.top_nums = counts
+            .try_into()
+            .map_err(|_| anyhow!("Expected 8 column counts in '{header}'"))?;
+
+        for y in 0..8 {
+            let line = lines
+                .next()
+                .ok_or_else(|| anyhow!("Expected 8 rows, found {y}"))?;
+            let mut fields = line.split_whitespace();
+            puzzle.left_nums[y] = fields
+                .next()
+                .and_then(|count| count.parse().ok())
+                .ok_or_else(|| anyhow!("Bad row count in '{line}'"))?;
+            let tiles: Vec<Tile> = fields
+                .map(|glyph| {
+                    Tile::from_glyph(glyph).ok_or_else(|| anyhow!("Unknown tile '{glyph}'"))
+                })
+                .collect::<Result<_>>()?;
+            puzzle.tiles[y] = tiles
+                .try_into()
+                .map_err(|_| anyhow!("Expected 8 tiles in '{line}'"))?;
+        }
+        if let Some(extra) = lines.next() {
+            return Err(anyhow!("Unexpected line after the board '{extra}'"));
+        }
+        if puzzle
+            .top_nums
+            .iter()
+            .chain(&puzzle.left_nums)
+            .any(|&n| n > 8)
+        {
+            return Err(anyhow!("Wall counts go up to 8"));
+        }
+        Ok(puzzle)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn overlay_img(
     src: &DynamicImage,
     dst: &mut RgbaImage,
@@ -476,11 +578,15 @@ fn overlay_img(
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::{collections::BTreeMap, path::Path};
 
+    use image::imageops::{flip_horizontal, replace};
     use test_case::test_case;
 
     use super::*;
+    use crate::digits::DIGIT_SIZE;
+    use crate::monsters::{SpriteOffset, SpriteOffsets};
+    use crate::util::Versioned;
 
     fn get_reference_puzzle(monster: &str) -> Puzzle {
         use Tile::*;
@@ -796,6 +902,143 @@ mod tests {
         assert!(p == get_reference_puzzle(monster), "{monster} => {p:?},")
     }
 
+    #[test]
+    fn unmatched_monsters() {
+        // Sprites that match nothing on the board, as if the offsets were out of date
+        let offsets = SpriteOffsets {
+            monsters: BTreeMap::from([(
+                String::from("insectoid"),
+                SpriteOffset {
+                    offset: (0, 0),
+                    best_frame: 0,
+                },
+            )]),
+            ..Default::default()
+        };
+        let frame = RgbaImage::from_pixel(32, 32, Rgba([255, 0, 255, 255]));
+        let species = BTreeMap::from([(String::from("insectoid"), vec![frame])]);
+        let templates = MonsterTemplates::from_frames(&species, &offsets).unwrap();
+
+        // The board doesn't get read with monsters nobody recognises
+        let img = open("monster_refs/insectoid.png").unwrap().to_rgba8();
+        let img = img.view(0, 0, img.width(), img.height());
+        let layout = Layout::default();
+        let tiles = parse_tiles(
+            layout.board.region.view(&img),
+            &layout.board,
+            Some(&templates),
+        );
+        let error = tiles.unwrap_err().to_string();
+        assert!(
+            error.contains("matches none of the monster sprites"),
+            "{error}"
+        );
+
+        // Without sprites the sample colours decide alone
+        let tiles = parse_tiles(layout.board.region.view(&img), &layout.board, None);
+        assert_eq!(tiles.unwrap(), get_reference_puzzle("insectoid").tiles);
+    }
+
+    // Stand-in for the game's font sheet, built from digits cropped out of the reference
+    // screenshots so the template matcher can be tested without the extracted textures
+    fn reference_font_sheet() -> RgbaImage {
+        let mut sheet = RgbaImage::new(DIGIT_OFFSETS[8] + DIGIT_SIZE.0, 32 + DIGIT_SIZE.1);
+
+        // The insectoid's top row has every red digit from 1 to 7
+        let layout = Layout::default();
+        let img = open("monster_refs/insectoid.png").unwrap().to_rgba8();
+        for (i, &digit) in get_reference_puzzle("insectoid")
+            .top_nums
+            .iter()
+            .enumerate()
+        {
+            let (x, y) = layout.top_count_corner(i);
+            let cell = img.view(x, y, DIGIT_SIZE.0, DIGIT_SIZE.1);
+            replace(&mut sheet, &*cell, DIGIT_OFFSETS[digit as usize] as i64, 0);
+        }
+
+        // The kobold has a brown 0 in its third row
+        let img = open("monster_refs/kobold.png").unwrap().to_rgba8();
+        let (x, y) = layout.left_count_corner(2);
+        let cell = img.view(x, y, DIGIT_SIZE.0, DIGIT_SIZE.1);
+        replace(&mut sheet, &*cell, DIGIT_OFFSETS[0] as i64, 32);
+
+        sheet
+    }
+
+    #[test_case("bear")]
+    #[test_case("chest")]
+    #[test_case("cultist")]
+    #[test_case("demon")]
+    #[test_case("goat")]
+    #[test_case("goblin")]
+    #[test_case("golem")]
+    #[test_case("imp")]
+    #[test_case("insectoid")]
+    #[test_case("king")]
+    #[test_case("kobold")]
+    #[test_case("lich")]
+    #[test_case("lookseer")]
+    #[test_case("minotaur")]
+    #[test_case("ogre")]
+    #[test_case("skeleton")]
+    #[test_case("slime")]
+    #[test_case("squid")]
+    /// Tests that the template matcher reads the same wall counts as the reference results.
+    fn wall_counts_from_templates(monster: &str) {
+        let templates = DigitTemplates::from_font_sheet(&reference_font_sheet()).unwrap();
+
+        let path = Path::new("monster_refs").join(format!("{monster}.png"));
+        let img = open(path).unwrap().to_rgba8();
+        let img = img.view(0, 0, img.width(), img.height());
+        let layout = Layout::default();
+        let top = layout.top_counts.region.view(&img);
+        let left = layout.left_counts.region.view(&img);
+        let counts = parse_wall_counts(top, left, &layout, Some(&templates)).unwrap();
+
+        let reference = get_reference_puzzle(monster);
+        assert_eq!(
+            counts,
+            (reference.top_nums, reference.left_nums),
+            "{monster}"
+        );
+    }
+
+    #[test]
+    /// No reference screenshot has a full row or column, so none shows an 8. A stand-in made of
+    /// a 3 and its mirror image goes on the sheet and on the board, and has to be read back.
+    fn wall_count_eight() {
+        let mut sheet = reference_font_sheet();
+        let three = sheet
+            .view(DIGIT_OFFSETS[3], 0, DIGIT_SIZE.0, DIGIT_SIZE.1)
+            .to_image();
+        let mut eight = three.clone();
+        for (x, y, p) in flip_horizontal(&three).enumerate_pixels() {
+            if digits::ink_colour(p.0).is_some() {
+                eight.put_pixel(x, y, *p);
+            }
+        }
+        replace(&mut sheet, &eight, DIGIT_OFFSETS[8] as i64, 0);
+        let templates = DigitTemplates::from_font_sheet(&sheet).unwrap();
+
+        let layout = Layout::default();
+        let mut img = open("monster_refs/insectoid.png").unwrap().to_rgba8();
+        let (x, y) = layout.top_count_corner(2);
+        replace(&mut img, &eight, x as i64, y as i64);
+        let img = img.view(0, 0, img.width(), img.height());
+        let top = layout.top_counts.region.view(&img);
+        let left = layout.left_counts.region.view(&img);
+        let (top_nums, left_nums) =
+            parse_wall_counts(top, left, &layout, Some(&templates)).unwrap();
+
+        let mut expected = get_reference_puzzle("insectoid");
+        expected.top_nums[2] = 8;
+        assert_eq!(
+            (top_nums, left_nums),
+            (expected.top_nums, expected.left_nums)
+        );
+    }
+
     #[test_case("bear")]
     #[test_case("chest")]
     #[test_case("cultist")]
@@ -836,4 +1079,136 @@ mod tests {
             "Original:\n{original}\nDeserialized:\n{deserialized}"
         );
     }
+
+    #[test]
+    /// Counts of 8 (a full row or column of walls) used to be clamped to 7 on the way out
+    fn serialization_eight() {
+        let mut original = get_reference_puzzle("bear");
+        original.top_nums = [8, 3, 3, 1, 6, 3, 5, 0];
+        original.left_nums = [8, 4, 7, 1, 4, 2, 3, 8];
+
+        let mut cursor = Cursor::new(Vec::new());
+        original.serialize(&mut cursor).unwrap();
+        let buffer = cursor.into_inner();
+        assert_eq!(buffer.len(), 26);
+
+        let deserialized = Puzzle::deserialize(buffer.first_chunk::<26>().unwrap()).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    /// Seeds only get 27 bits, anything bigger can't be written
+    fn serialization_big_seed() {
+        let mut puzzle = get_reference_puzzle("bear");
+        puzzle.seed = Some(SEED_MASK - 1);
+        let mut cursor = Cursor::new(Vec::new());
+        puzzle.serialize(&mut cursor).unwrap();
+        let buffer = cursor.into_inner();
+        let deserialized = Puzzle::deserialize(buffer.first_chunk::<26>().unwrap()).unwrap();
+        assert_eq!(deserialized, puzzle);
+
+        puzzle.seed = Some(SEED_MASK);
+        assert!(puzzle.serialize(&mut Cursor::new(Vec::new())).is_err());
+    }
+
+    #[test]
+    /// Records written before counts were packed in base 9 should still be readable
+    fn deserialize_legacy() {
+        let original = get_reference_puzzle("goblin");
+        let wall_counts: u64 = [original.top_nums, original.left_nums]
+            .concat()
+            .iter()
+            .fold(0, |acc, &x| (acc << 3) + x as u64);
+
+        let mut bytes = Vec::new();
+        bytes.extend((original.seed.unwrap() + 1).to_le_bytes());
+        bytes.extend(&wall_counts.to_le_bytes()[0..6]);
+        let mut cursor = Cursor::new(vec![0; 26]);
+        original.serialize(&mut cursor).unwrap();
+        bytes.extend(&cursor.into_inner()[10..]);
+
+        let deserialized = Puzzle::deserialize(bytes.first_chunk::<26>().unwrap()).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test_case("goblin")]
+    #[test_case("insectoid")]
+    #[test_case("lookseer")]
+    /// Display and FromStr round trip, apart from the seed which the text doesn't have
+    fn text_round_trip(monster: &str) {
+        let mut original = get_reference_puzzle(monster);
+        original.tiles[6][6] = Tile::Wall;
+        let parsed: Puzzle = original.to_string().parse().unwrap();
+        original.seed = None;
+        assert_eq!(parsed, original);
+
+        let first = format!("  {}", original.top_nums[0]);
+        let too_big = original.to_string().replacen(&first, "  9", 1);
+        assert!(too_big.parse::<Puzzle>().is_err(), "{too_big}");
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("  0 1 2 3 4 5 6\n" ; "short header")]
+    #[test_case("  0 1 2 3 4 5 6 7\n0 . . . . . . . .\n" ; "missing rows")]
+    fn text_errors(text: &str) {
+        assert!(text.parse::<Puzzle>().is_err());
+    }
+
+    #[test]
+    fn placed_walls() {
+        let board = Layout::default().board;
+        let mut img = open("monster_refs/goblin.png").unwrap().to_rgba8();
+        let puzzle = get_reference_puzzle("goblin");
+        assert_eq!(
+            puzzle.placed_walls(img.view(0, 0, img.width(), img.height()), &board),
+            0
+        );
+
+        // Cover an empty tile and a monster. Only the empty tile counts as a wall.
+        let (x, y) = (2, 0);
+        assert_eq!(puzzle.tiles[y][x], Tile::Empty);
+        let (mx, my) = (1, 0);
+        assert_eq!(puzzle.tiles[my][mx], Tile::Monster);
+        for (tx, ty) in [(x, y), (mx, my)] {
+            let size = board.tile_size;
+            let tile = RgbaImage::from_pixel(size, size, Rgba([90, 80, 70, 255]));
+            let corner = board.tile_corner(tx, ty);
+            replace(&mut img, &tile, corner.0 as i64, corner.1 as i64);
+        }
+        assert_eq!(
+            puzzle.placed_walls(img.view(0, 0, img.width(), img.height()), &board),
+            bit(x, y)
+        );
+    }
+
+    #[test]
+    /// A game update that moves everything should only need a new layout
+    fn moved_layout() {
+        let (dx, dy) = (13, 7);
+        let original = open("monster_refs/goblin.png").unwrap().to_rgba8();
+        let mut img = RgbaImage::new(original.width() + dx, original.height() + dy);
+        replace(&mut img, &original, dx as i64, dy as i64);
+
+        let mut layout = Layout::default();
+        layout.window.size = img.dimensions();
+        for region in [
+            &mut layout.board.region,
+            &mut layout.top_counts.region,
+            &mut layout.left_counts.region,
+            &mut layout.seed.region,
+        ] {
+            region.origin = (region.origin.0 + dx, region.origin.1 + dy);
+        }
+        let layout = Layout::from_json(&serde_json::to_string(&layout).unwrap()).unwrap();
+
+        let view = img.view(0, 0, img.width(), img.height());
+        assert_eq!(
+            Puzzle::from_image_with(view, &layout).unwrap(),
+            get_reference_puzzle("goblin")
+        );
+        assert_ne!(
+            Puzzle::from_image_with(view, &Layout::default()).ok(),
+            Some(get_reference_puzzle("goblin"))
+        );
+    }
 }
@@ -1,12 +1,15 @@
 use std::{collections::HashSet, fs};
 
-use xcap::image::{self, GenericImageView, Rgba, RgbaImage};
+use image::{self, GenericImageView, Rgba, RgbaImage};
+
+use crate::assets::AssetLocator;
+use crate::digits::{DIGIT_OFFSETS, LARGE_FONT_PATH};
 
 const TILE_SIZE: u32 = 33;
 
 #[allow(dead_code)]
-pub fn tile_bg_colors() {
-    let tiles_img = image::open(BACKGROUND_SPRITE_PATH).unwrap();
+pub fn tile_bg_colors(assets: &AssetLocator) {
+    let tiles_img = image::open(assets.decoded(BACKGROUND_SPRITE_PATH)).unwrap();
     let supermask_img = image::open("supermask.png").unwrap();
     let mut out_img = RgbaImage::new(tiles_img.width(), tiles_img.height());
 
@@ -34,12 +37,12 @@ pub fn tile_bg_colors() {
     out_img.save("collated.png").unwrap();
 }
 
-const SPRITE_PATH: &str = "tokyo/resized";
+const SPRITE_PATH: &str = "resized";
 const TREASURE_SPRITE_PATH: &str = "treasure.png";
 const BACKGROUND_SPRITE_PATH: &str = "Content/Packed/textures/tokyo/tiles_grid.png";
 #[allow(dead_code)]
-pub fn find_monster_sample_offset() {
-    let background_img = image::open(BACKGROUND_SPRITE_PATH).unwrap();
+pub fn find_monster_sample_offset(assets: &AssetLocator) {
+    let background_img = image::open(assets.decoded(BACKGROUND_SPRITE_PATH)).unwrap();
     let treasure_img = image::open(TREASURE_SPRITE_PATH).unwrap();
     let mut background_pixels: [[HashSet<Rgba<u8>>; 32]; 32] = Default::default();
     let mut monster_pixels: [[HashSet<Rgba<u8>>; 32]; 32] = Default::default();
@@ -59,16 +62,16 @@ pub fn find_monster_sample_offset() {
     }
 
     println!("background pixel counts");
-    for y in 0..32 {
+    for row in &background_pixels {
         print!("  ");
-        for x in 0..32 {
-            print!("{: >4}", background_pixels[y][x].len());
+        for pixels in row {
+            print!("{: >4}", pixels.len());
         }
         println!();
     }
 
     let mut frame_count = 0;
-    for monster in fs::read_dir(SPRITE_PATH).unwrap() {
+    for monster in fs::read_dir(assets.sprites(SPRITE_PATH)).unwrap() {
         let monster = monster.unwrap();
         for frame in fs::read_dir(monster.path()).unwrap() {
             let frame = frame.unwrap();
@@ -91,27 +94,27 @@ pub fn find_monster_sample_offset() {
     }
 
     println!("monster pixel counts: ({frame_count} frames)");
-    for y in 0..32 {
+    for row in &monster_pixels {
         print!("  ");
-        for x in 0..32 {
-            print!("{: >4}", monster_pixels[y][x].len());
+        for pixels in row {
+            print!("{: >4}", pixels.len());
         }
         println!();
     }
 
     println!("valid samples pixel counts: ({frame_count} frames)");
-    for y in 0..32 {
+    for row in &valid_sample {
         print!("  ");
-        for x in 0..32 {
-            print!("{: >6}", valid_sample[y][x]);
+        for valid in row {
+            print!("{: >6}", valid);
         }
         println!();
     }
 }
 
 #[allow(dead_code)]
-pub fn print_background_pixels() {
-    let background_img = image::open(BACKGROUND_SPRITE_PATH).unwrap();
+pub fn print_background_pixels(assets: &AssetLocator) {
+    let background_img = image::open(assets.decoded(BACKGROUND_SPRITE_PATH)).unwrap();
     println!("const BACKGROUND_PIXELS: [[[u8; 4]; 8]; 8] = [");
     for y in 0..8 {
         print!("    [");
@@ -125,13 +128,11 @@ pub fn print_background_pixels() {
     println!("];");
 }
 
-const LARGE_FONT_PATH: &str = "tokyo/fonts/numbers.png";
-const DIGIT_OFFSETS: &[u32] = &[4, 32, 60, 88, 116, 144, 172, 200, 228];
 const MAX_PATTERN_WIDTH: u32 = 22;
 const MAX_PATTERN_HEIGHT: u32 = 16;
 #[allow(dead_code)]
-pub fn get_large_digit_discriminant() {
-    let digits_img = image::open(LARGE_FONT_PATH).unwrap();
+pub fn get_large_digit_discriminant(assets: &AssetLocator) {
+    let digits_img = image::open(assets.sprites(LARGE_FONT_PATH)).unwrap();
 
     // Extract individual digits (brown 0, red 1-7)
     let digits_imgs = [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
@@ -171,43 +172,40 @@ pub fn get_large_digit_discriminant() {
                     });
                     // Check that the counts are unique
                     let unique_count = HashSet::from(counts).len();
-                    if unique_count >= 8 {
-                        if width * height < best_size {
-                            best_size = width * height;
-                            println!("{width}x{height}+{x}+{y}");
-                            // println!("  {counts:?}");
-                            // create image for blog
-                            let mut img =
-                                RgbaImage::new(MAX_PATTERN_WIDTH * 8 + 8, MAX_PATTERN_HEIGHT);
-                            for (i, digit) in digits_imgs.iter().enumerate() {
-                                for (xx, yy, p) in digit.pixels() {
-                                    let col = if p.0[1] == 91 && p.0[2] >= 69 && p.0[2] <= 78 {
-                                        [255, 255, 255, 255]
-                                    } else {
-                                        [0, 0, 0, 0]
-                                    };
-                                    img.get_pixel_mut(
-                                        xx + i as u32 * MAX_PATTERN_WIDTH + i as u32,
-                                        yy,
-                                    )
-                                    .0 = col;
-                                    crate::util::draw_rect(
-                                        &mut img,
-                                        x + i as u32 * MAX_PATTERN_WIDTH + i as u32,
-                                        y,
-                                        width,
-                                        height,
-                                        [0, 128, 255, 64],
-                                    );
-                                }
-                            }
-                            let save_amount = if k == 32 { 10 } else { 1 };
-                            for _ in 0..save_amount {
-                                img.save(format!("script_output/digit_discrim/full_{k:02}.webp"))
-                                    .unwrap();
-                                k += 1;
+                    if unique_count >= 8 && width * height < best_size {
+                        best_size = width * height;
+                        println!("{width}x{height}+{x}+{y}");
+                        // println!("  {counts:?}");
+                        // create image for blog
+                        let mut img = RgbaImage::new(MAX_PATTERN_WIDTH * 8 + 8, MAX_PATTERN_HEIGHT);
+                        for (i, digit) in digits_imgs.iter().enumerate() {
+                            for (xx, yy, p) in digit.pixels() {
+                                let col = if p.0[1] == 91 && p.0[2] >= 69 && p.0[2] <= 78 {
+                                    [255, 255, 255, 255]
+                                } else {
+                                    [0, 0, 0, 0]
+                                };
+                                img.get_pixel_mut(
+                                    xx + i as u32 * MAX_PATTERN_WIDTH + i as u32,
+                                    yy,
+                                )
+                                .0 = col;
+                                crate::util::draw_rect(
+                                    &mut img,
+                                    x + i as u32 * MAX_PATTERN_WIDTH + i as u32,
+                                    y,
+                                    width,
+                                    height,
+                                    [0, 128, 255, 64],
+                                );
                             }
                         }
+                        let save_amount = if k == 32 { 10 } else { 1 };
+                        for _ in 0..save_amount {
+                            img.save(format!("script_output/digit_discrim/full_{k:02}.webp"))
+                                .unwrap();
+                            k += 1;
+                        }
                     }
                 }
             }
@@ -0,0 +1,308 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+// Uniformity is checked by splitting the seen range into buckets with at least this many seeds
+// expected in each, so the chi-squared approximation holds
+const MIN_EXPECTED_PER_BUCKET: usize = 5;
+const MAX_BUCKETS: usize = 20;
+
+// How far the chi-squared statistic can sit from its mean, in standard deviations, before the
+// seeds stop looking uniform
+const UNIFORM_Z_LIMIT: f64 = 3.0;
+
+// Estimates of how many seeds RANDOM can produce, from the order they were collected in. Every
+// estimate assumes each click draws uniformly from a fixed population, which `uniformity`
+// checks as far as it can.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedSpace {
+    // Seeded boards collected, in order
+    pub draws: usize,
+    pub distinct: usize,
+    // Seeds seen exactly once and exactly twice
+    pub singletons: usize,
+    pub doubletons: usize,
+    // Pairs of draws that gave the same seed
+    pub collisions: usize,
+    // Distinct seeds in the first and second half of the draws, and in both
+    pub halves: (usize, usize, usize),
+    pub uniformity: Option<Uniformity>,
+}
+
+// Chi-squared test of the distinct seeds against a flat distribution over the range they span
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Uniformity {
+    pub buckets: usize,
+    pub chi_squared: f64,
+    // Wilson-Hilferty normal approximation of the statistic
+    pub z: f64,
+}
+
+impl Uniformity {
+    pub fn looks_uniform(&self) -> bool {
+        self.z.abs() < UNIFORM_Z_LIMIT
+    }
+}
+
+impl SeedSpace {
+    pub fn new(seeds: &[u32]) -> Self {
+        let mut counts: HashMap<u32, usize> = HashMap::new();
+        for &seed in seeds {
+            *counts.entry(seed).or_default() += 1;
+        }
+        let with_count = |k| counts.values().filter(|&&n| n == k).count();
+
+        let (first, second) = seeds.split_at(seeds.len() / 2);
+        let first: HashSet<u32> = first.iter().copied().collect();
+        let second: HashSet<u32> = second.iter().copied().collect();
+
+        let mut distinct: Vec<u32> = counts.keys().copied().collect();
+        distinct.sort_unstable();
+
+        Self {
+            draws: seeds.len(),
+            distinct: counts.len(),
+            singletons: with_count(1),
+            doubletons: with_count(2),
+            collisions: counts.values().map(|&n| n * (n - 1) / 2).sum(),
+            halves: (
+                first.len(),
+                second.len(),
+                first.intersection(&second).count(),
+            ),
+            uniformity: uniformity(&distinct),
+        }
+    }
+
+    // Chao1: a lower bound on the population from how many seeds were only seen once or twice.
+    // Seeds seen once mean plenty haven't been seen at all.
+    pub fn chao1(&self) -> f64 {
+        let (f1, f2) = (self.singletons as f64, self.doubletons as f64);
+        match self.doubletons {
+            0 => self.distinct as f64 + f1 * (f1 - 1.0) / 2.0,
+            _ => self.distinct as f64 + f1 * f1 / (2.0 * f2),
+        }
+    }
+
+    // Birthday problem: n draws from N seeds give n(n-1)/2N matching pairs on average.
+    // None until there's been a repeat.
+    pub fn collision_estimate(&self) -> Option<f64> {
+        let n = self.draws as f64;
+        (self.collisions > 0).then(|| n * (n - 1.0) / (2.0 * self.collisions as f64))
+    }
+
+    // Capture-recapture with the first half of the draws as the marked sample and the second
+    // as the recapture, using Chapman's form so no overlap doesn't divide by zero
+    pub fn chapman(&self) -> f64 {
+        let (n1, n2, m) = self.halves;
+        (n1 as f64 + 1.0) * (n2 as f64 + 1.0) / (m as f64 + 1.0) - 1.0
+    }
+
+    // The population the other figures are based on. Chao1 holds up best when most seeds have
+    // only been seen once, which is where collection is for a long while.
+    pub fn population(&self) -> f64 {
+        self.chao1().max(self.distinct as f64)
+    }
+
+    // Good-Turing: the chance the next board is a seed not seen yet
+    pub fn new_seed_chance(&self) -> f64 {
+        match self.draws {
+            0 => 1.0,
+            draws => self.singletons as f64 / draws as f64,
+        }
+    }
+
+    // Distinct new seeds expected from `boards` more, drawing uniformly from the estimated
+    // population
+    pub fn expected_new(&self, boards: usize) -> f64 {
+        let population = self.population();
+        if population <= 0.0 {
+            return boards as f64;
+        }
+        let unseen = (population - self.distinct as f64).max(0.0);
+        unseen * (1.0 - (1.0 - 1.0 / population).powf(boards as f64))
+    }
+
+    pub fn coverage(&self) -> f64 {
+        match self.population() {
+            0.0 => 0.0,
+            population => self.distinct as f64 / population,
+        }
+    }
+}
+
+// Split the seen range into equal buckets and compare how many seeds landed in each with a
+// flat spread. None when there are too few seeds for at least two buckets.
+fn uniformity(sorted: &[u32]) -> Option<Uniformity> {
+    let buckets = (sorted.len() / MIN_EXPECTED_PER_BUCKET).min(MAX_BUCKETS);
+    let (&min, &max) = (sorted.first()?, sorted.last()?);
+    if buckets < 2 || min == max {
+        return None;
+    }
+
+    let span = (max - min) as f64 + 1.0;
+    let mut observed = vec![0usize; buckets];
+    for &seed in sorted {
+        let bucket = ((seed - min) as f64 / span * buckets as f64) as usize;
+        observed[bucket.min(buckets - 1)] += 1;
+    }
+    let expected = sorted.len() as f64 / buckets as f64;
+    let chi_squared = observed
+        .iter()
+        .map(|&o| (o as f64 - expected).powi(2) / expected)
+        .sum::<f64>();
+
+    let dof = (buckets - 1) as f64;
+    let spread = 2.0 / (9.0 * dof);
+    let z = ((chi_squared / dof).cbrt() - (1.0 - spread)) / spread.sqrt();
+    Some(Uniformity {
+        buckets,
+        chi_squared,
+        z,
+    })
+}
+
+// `boards` is the size of the next batch to plan for
+pub struct Report<'a>(pub &'a SeedSpace, pub usize);
+
+impl Display for Report<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Report(space, boards) = *self;
+        writeln!(f, "Seed space")?;
+        writeln!(f, "  seeded boards: {}", space.draws)?;
+        writeln!(f, "  distinct seeds: {}", space.distinct)?;
+        writeln!(
+            f,
+            "  seen once: {}, twice: {}, repeated pairs: {}",
+            space.singletons, space.doubletons, space.collisions
+        )?;
+
+        writeln!(f, "\nPopulation estimates")?;
+        writeln!(f, "  chao1: {:.0}", space.chao1())?;
+        match space.collision_estimate() {
+            Some(n) => writeln!(f, "  collisions: {n:.0}")?,
+            None => writeln!(f, "  collisions: no repeats yet")?,
+        }
+        let (n1, n2, m) = space.halves;
+        writeln!(
+            f,
+            "  capture-recapture: {:.0} ({n1} then {n2} distinct, {m} in both)",
+            space.chapman()
+        )?;
+        writeln!(f, "  coverage: {:.2}%", space.coverage() * 100.0)?;
+
+        writeln!(f, "\nNext batch of {boards}")?;
+        writeln!(
+            f,
+            "  chance the next board is new: {:.2}%",
+            space.new_seed_chance() * 100.0
+        )?;
+        writeln!(f, "  expected new seeds: {:.0}", space.expected_new(boards))?;
+
+        writeln!(f, "\nUniformity")?;
+        match space.uniformity {
+            Some(u) => write!(
+                f,
+                "  chi-squared {:.1} over {} buckets (z = {:.2}): {}",
+                u.chi_squared,
+                u.buckets,
+                u.z,
+                match u.looks_uniform() {
+                    true => "looks uniform",
+                    false => "not uniform, the estimates above are off",
+                }
+            ),
+            None => write!(f, "  too few seeds to tell"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use test_case::test_case;
+
+    use super::*;
+
+    fn draws(population: u32, n: usize, seed: u64) -> Vec<u32> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..n)
+            .map(|_| rng.gen_range(0..population) * 7 + 11)
+            .collect()
+    }
+
+    #[test]
+    fn counts() {
+        let space = SeedSpace::new(&[1, 2, 2, 3, 3, 3, 4, 1]);
+        assert_eq!(space.draws, 8);
+        assert_eq!(space.distinct, 4);
+        assert_eq!((space.singletons, space.doubletons), (1, 2));
+        // 1 + 1 + 3
+        assert_eq!(space.collisions, 5);
+        assert_eq!(space.halves, (3, 3, 2));
+    }
+
+    #[test_case(1000, 1500, 1)]
+    #[test_case(1000, 3000, 2)]
+    #[test_case(20000, 5000, 3)]
+    fn estimates_population(population: u32, n: usize, seed: u64) {
+        let space = SeedSpace::new(&draws(population, n, seed));
+        let population = population as f64;
+        for estimate in [
+            space.chao1(),
+            space.collision_estimate().unwrap(),
+            space.chapman(),
+        ] {
+            assert!(
+                (estimate / population - 1.0).abs() < 0.2,
+                "{estimate} for {population}"
+            );
+        }
+    }
+
+    #[test]
+    fn expected_new_matches_another_batch() {
+        let all = draws(2000, 4000, 4);
+        let space = SeedSpace::new(&all[..2000]);
+        let seen: HashSet<u32> = all[..2000].iter().copied().collect();
+        let new: HashSet<u32> = all[2000..]
+            .iter()
+            .filter(|s| !seen.contains(s))
+            .copied()
+            .collect();
+
+        let expected = space.expected_new(2000);
+        assert!(
+            (expected / new.len() as f64 - 1.0).abs() < 0.2,
+            "{expected} vs {}",
+            new.len()
+        );
+        // Nothing left to find once the whole population has been seen
+        let saturated = SeedSpace::new(&draws(50, 5000, 5));
+        assert!(saturated.expected_new(1000) < 0.5);
+        assert!(saturated.coverage() > 0.99);
+    }
+
+    #[test]
+    fn uniformity() {
+        let uniform = SeedSpace::new(&draws(1_000_000, 2000, 6));
+        assert!(uniform.uniformity.unwrap().looks_uniform());
+
+        // Squared pushes most seeds toward the bottom of the range
+        let skewed: Vec<u32> = draws(1000, 2000, 7).iter().map(|s| s * s / 1000).collect();
+        let skewed = SeedSpace::new(&skewed);
+        assert!(!skewed.uniformity.unwrap().looks_uniform());
+
+        assert_eq!(SeedSpace::new(&[1, 2, 3]).uniformity, None);
+    }
+
+    #[test]
+    fn report() {
+        let space = SeedSpace::new(&[5, 6, 7]);
+        let report = Report(&space, 100).to_string();
+        assert!(report.contains("collisions: no repeats yet"), "{report}");
+        assert!(report.contains("too few seeds to tell"), "{report}");
+        assert!(report.contains("Next batch of 100"), "{report}");
+    }
+}
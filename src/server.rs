@@ -0,0 +1,659 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use image::{load_from_memory_with_format, GenericImageView, ImageFormat};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::db;
+use crate::play::{first_cell, Hint, Play};
+use crate::puzzle::{Puzzle, Tile};
+use crate::solve::{BackTracker, Bounded, Budget};
+
+// Local tools only, so nothing listens beyond this machine
+pub const DEFAULT_PORT: u16 = 7878;
+
+// Request line and headers together
+const MAX_HEAD_LEN: usize = 8 * 1024;
+// A 4K screenshot as PNG is a few MB
+const MAX_IMAGE_LEN: usize = 16 * 1024 * 1024;
+// A puzzle as JSON is well under 2KB, and text under 200 bytes
+const MAX_PUZZLE_LEN: usize = 16 * 1024;
+// Boards with few monsters can have a huge number of solutions. The search stops once it has
+// found more than this many, and only this many are sent back.
+const MAX_SOLUTIONS_SENT: usize = 10;
+// Around a second of searching in a release build, then the clues are turned away
+const SOLVE_STEPS: u64 = 2_000_000;
+// Connections being answered at once, past this they get a 503 straight away
+const MAX_CONNECTIONS: usize = 8;
+// The 503 is sent without handing off to a thread, so it mustn't wait on the client for long
+const BUSY_TIMEOUT: Duration = Duration::from_millis(100);
+// How long a client can take to send its request or read the response
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+// Pause after a failed accept, so running out of file handles doesn't turn into a busy loop
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(50);
+
+const HELP: &str = "\
+POST /parse            a PNG screenshot of the board, returns the puzzle on it
+POST /solve            a puzzle as JSON or text, returns up to 10 solutions and whether there's one
+GET  /puzzles/<seed>   the board with that seed from the database
+GET  /hint?seed=<seed>[&walls=<mask>][&open=<mask>]
+                       a hint for that board, given walls placed and tiles marked open so far
+                       as 16 hex digit masks, bit 63 - (y * 8 + x) for the tile at (x, y)";
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+// JSON body, or {"error": ...} for anything but 200
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    fn ok(body: impl Serialize) -> Self {
+        match serde_json::to_value(body) {
+            Ok(body) => Self { status: 200, body },
+            Err(e) => Self::error(500, e),
+        }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            411 => "Length Required",
+            413 => "Content Too Large",
+            422 => "Unprocessable Content",
+            431 => "Request Header Fields Too Large",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+
+    fn write_to(&self, mut out: impl Write) -> io::Result<()> {
+        let body = self.body.to_string();
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            self.status,
+            self.reason(),
+            body.len()
+        )?;
+        out.flush()
+    }
+}
+
+// The largest body each route takes. Checked against Content-Length before any of the body is
+// read.
+fn body_limit(method: &str, path: &str) -> usize {
+    match (method, path) {
+        ("POST", "/parse") => MAX_IMAGE_LEN,
+        ("POST", _) => MAX_PUZZLE_LEN,
+        _ => 0,
+    }
+}
+
+// Read one request, or the response explaining why it couldn't be
+pub fn read_request(input: &mut impl BufRead) -> Result<Request, Response> {
+    let mut head = Vec::new();
+    loop {
+        let start = head.len();
+        input
+            .by_ref()
+            .take((MAX_HEAD_LEN + 1 - start) as u64)
+            .read_until(b'\n', &mut head)
+            .map_err(|e| Response::error(408, e))?;
+        if head.len() > MAX_HEAD_LEN {
+            return Err(Response::error(431, "Request head too long"));
+        }
+        match &head[start..] {
+            [] => return Err(Response::error(400, "Request cut short")),
+            b"\r\n" | b"\n" if start > 0 => break,
+            _ => {}
+        }
+    }
+    let head = String::from_utf8(head).map_err(|_| Response::error(400, "Head isn't UTF-8"))?;
+    let mut lines = head.lines();
+
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(_version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(Response::error(400, "Malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (key.to_string(), value.to_string())
+        })
+        .collect();
+
+    let mut content_length = None;
+    for line in lines.take_while(|line| !line.is_empty()) {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| Response::error(400, format!("Malformed header '{line}'")))?;
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            let length = value
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| Response::error(400, "Bad Content-Length"))?;
+            content_length = Some(length);
+        }
+    }
+
+    let limit = body_limit(method, path);
+    let length = match content_length {
+        None if method == "POST" => return Err(Response::error(411, "Content-Length needed")),
+        None => 0,
+        Some(length) if length > limit => {
+            return Err(Response::error(
+                413,
+                format!("Body of {length} bytes, {method} {path} takes at most {limit}"),
+            ))
+        }
+        Some(length) => length,
+    };
+    let mut body = vec![0; length];
+    input
+        .read_exact(&mut body)
+        .map_err(|_| Response::error(400, "Body shorter than Content-Length"))?;
+
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        body,
+    })
+}
+
+// Solving is only meaningful from clues, so walls in the tiles and counts that can't fit are
+// turned away rather than handed to the solver
+fn read_clues(body: &[u8]) -> Result<Puzzle, Response> {
+    let text = std::str::from_utf8(body).map_err(|_| Response::error(400, "Body isn't UTF-8"))?;
+    let puzzle: Puzzle = match text.trim_start().starts_with('{') {
+        true => serde_json::from_str(text).map_err(|e| Response::error(400, e))?,
+        false => text.parse().map_err(|e| Response::error(400, e))?,
+    };
+    if puzzle
+        .tiles
+        .iter()
+        .flatten()
+        .any(|&tile| tile == Tile::Wall)
+    {
+        return Err(Response::error(
+            422,
+            "Walls are what's solved for, leave them out",
+        ));
+    }
+    if puzzle
+        .top_nums
+        .iter()
+        .chain(&puzzle.left_nums)
+        .any(|&n| n > 8)
+    {
+        return Err(Response::error(422, "Wall counts go up to 8"));
+    }
+    Ok(puzzle)
+}
+
+fn parse_image(body: &[u8]) -> Response {
+    let img = match load_from_memory_with_format(body, ImageFormat::Png) {
+        Ok(img) => img.to_rgba8(),
+        Err(e) => return Response::error(400, format!("Not a PNG: {e}")),
+    };
+    match Puzzle::from_image(img.view(0, 0, img.width(), img.height())) {
+        Ok(puzzle) => Response::ok(puzzle),
+        Err(e) => Response::error(422, format!("No board found: {e}")),
+    }
+}
+
+fn solve(puzzle: &Puzzle, steps: u64) -> Response {
+    let budget = Budget {
+        solutions: MAX_SOLUTIONS_SENT + 1,
+        steps,
+    };
+    let (solutions, more) = match BackTracker.solve_bounded(puzzle, budget) {
+        Bounded::All(solutions) => (solutions, false),
+        Bounded::AtLeast(solutions) => (solutions, true),
+        Bounded::GaveUp(_) => {
+            return Response::error(
+                422,
+                format!("Gave up after {steps} steps, the clues leave too much open"),
+            )
+        }
+    };
+    let boards: Vec<Puzzle> = solutions
+        .iter()
+        .take(MAX_SOLUTIONS_SENT)
+        .map(|&walls| {
+            let mut play = Play::new(*puzzle);
+            play.walls = walls;
+            play.board()
+        })
+        .collect();
+    // With `more` set the count is only how many were found before stopping
+    Response::ok(json!({
+        "count": solutions.len(),
+        "more": more,
+        "unique": solutions.len() == 1 && !more,
+        "solutions": boards,
+    }))
+}
+
+fn find_seeded(db_path: &Path, seed: &str) -> Result<Puzzle, Response> {
+    let seed: u32 = seed
+        .parse()
+        .map_err(|_| Response::error(400, format!("Bad seed '{seed}'")))?;
+    let scan = db::read(db_path).map_err(|e| Response::error(500, e))?;
+    scan.puzzles
+        .into_iter()
+        .find(|puzzle| puzzle.seed == Some(seed))
+        .ok_or_else(|| Response::error(404, format!("No board with seed {seed}")))
+}
+
+fn mask(query: &HashMap<String, String>, name: &str) -> Result<u64, Response> {
+    match query.get(name) {
+        Some(hex) => u64::from_str_radix(hex, 16)
+            .map_err(|_| Response::error(400, format!("Bad {name} mask '{hex}'"))),
+        None => Ok(0),
+    }
+}
+
+fn hint(db_path: &Path, query: &HashMap<String, String>) -> Result<Response, Response> {
+    let seed = query
+        .get("seed")
+        .ok_or_else(|| Response::error(400, "Which board? Needs seed=<seed>"))?;
+    let mut play = Play::new(find_seeded(db_path, seed)?);
+    play.walls = mask(query, "walls")?;
+    play.open = mask(query, "open")?;
+    // Play only ever puts walls and open marks on empty tiles, and never both on one
+    if let Some((x, y)) = first_cell((play.walls | play.open) & !play.empty_cells()) {
+        return Err(Response::error(
+            422,
+            format!("Tile {x},{y} isn't empty, so it can't be a wall or open"),
+        ));
+    }
+    if let Some((x, y)) = first_cell(play.walls & play.open) {
+        return Err(Response::error(
+            422,
+            format!("Tile {x},{y} can't be both a wall and open"),
+        ));
+    }
+    let hint = play.find_hint();
+    let body = match hint {
+        Hint::Tile((x, y), _) => json!({ "x": x, "y": y, "hint": hint.to_string() }),
+        _ => json!({ "hint": hint.to_string() }),
+    };
+    Ok(Response::ok(body))
+}
+
+pub fn handle(request: &Request, db_path: &Path) -> Response {
+    let route = (request.method.as_str(), request.path.as_str());
+    let response = match route {
+        ("GET", "/") => Ok(Response::ok(
+            json!({ "routes": HELP.lines().collect::<Vec<_>>() }),
+        )),
+        ("POST", "/parse") => Ok(parse_image(&request.body)),
+        ("POST", "/solve") => read_clues(&request.body).map(|puzzle| solve(&puzzle, SOLVE_STEPS)),
+        ("GET", "/hint") => hint(db_path, &request.query),
+        ("GET", path) if path.starts_with("/puzzles/") => {
+            find_seeded(db_path, &path["/puzzles/".len()..]).map(Response::ok)
+        }
+        (_, "/" | "/parse" | "/solve" | "/hint") => Err(Response::error(405, "Wrong method")),
+        (_, path) if path.starts_with("/puzzles/") => Err(Response::error(405, "Wrong method")),
+        (_, path) => Err(Response::error(404, format!("Nothing at {path}\n\n{HELP}"))),
+    };
+    response.unwrap_or_else(|error| error)
+}
+
+fn connection(stream: TcpStream, db_path: &Path) -> io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut input = BufReader::new(&stream);
+    let response = match read_request(&mut input) {
+        Ok(request) => handle(&request, db_path),
+        Err(error) => error,
+    };
+    response.write_to(&stream)?;
+    // Closing with unread input resets the connection, which can lose the response before the
+    // client reads it. Let the client finish sending what little is left first.
+    stream.shutdown(Shutdown::Write)?;
+    io::copy(&mut input.take(MAX_HEAD_LEN as u64), &mut io::sink())?;
+    Ok(())
+}
+
+// Holds one of the MAX_CONNECTIONS places until its connection is done
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    fn take(open: &Arc<AtomicUsize>) -> Option<Slot> {
+        open.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+            (n < MAX_CONNECTIONS).then_some(n + 1)
+        })
+        .ok()
+        .map(|_| Slot(open.clone()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Turned away without reading the request. As in `connection`, whatever the client has already
+// sent is drained so the 503 isn't lost to a reset.
+fn busy(stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(BUSY_TIMEOUT))?;
+    stream.set_write_timeout(Some(BUSY_TIMEOUT))?;
+    Response::error(503, "Too many connections, try again shortly").write_to(&stream)?;
+    stream.shutdown(Shutdown::Write)?;
+    let _ = io::copy(&mut (&stream).take(MAX_HEAD_LEN as u64), &mut io::sink());
+    Ok(())
+}
+
+// Answer requests for as long as the process runs, each connection on its own thread so a slow
+// solve doesn't hold up the rest, up to MAX_CONNECTIONS at once
+pub fn serve(listener: TcpListener, db_path: PathBuf) -> Result<()> {
+    let db_path = Arc::new(db_path);
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        // Accepting can fail for one connection, e.g. it was reset before it got here, or for
+        // a while, e.g. out of file handles. Neither is a reason to stop serving.
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("Couldn't accept a connection: {e}");
+                thread::sleep(ACCEPT_BACKOFF);
+                continue;
+            }
+        };
+        let Some(slot) = Slot::take(&open) else {
+            if let Err(e) = busy(stream) {
+                println!("Connection dropped: {e}");
+            }
+            continue;
+        };
+        let db_path = db_path.clone();
+        thread::spawn(move || {
+            if let Err(e) = connection(stream, &db_path) {
+                println!("Connection dropped: {e}");
+            }
+            drop(slot);
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, net::SocketAddr};
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::db::DbWriter;
+    use crate::puzzle::{self, bit};
+    use crate::solve::Solver;
+
+    // The corridor, with a seed to look it up by
+    fn corridor() -> Puzzle {
+        Puzzle {
+            seed: Some(5),
+            ..puzzle::corridor().0
+        }
+    }
+
+    // A server on a free port, with the corridor in its database
+    fn start(name: &str) -> SocketAddr {
+        let db_path = std::env::temp_dir().join(format!("{name}_{}.db", std::process::id()));
+        let _ = fs::remove_file(&db_path);
+        let (mut db, _) = DbWriter::open(&db_path).unwrap();
+        db.append(&corridor()).unwrap();
+        db.sync().unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, db_path));
+        addr
+    }
+
+    // Send raw bytes, then read the status and JSON body of the reply
+    fn send(addr: SocketAddr, request: &[u8]) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request).unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        let (head, body) = reply.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn get(addr: SocketAddr, target: &str) -> (u16, Value) {
+        send(
+            addr,
+            format!("GET {target} HTTP/1.1\r\nHost: x\r\n\r\n").as_bytes(),
+        )
+    }
+
+    fn post(addr: SocketAddr, target: &str, body: &[u8]) -> (u16, Value) {
+        let mut request = format!(
+            "POST {target} HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(body);
+        send(addr, &request)
+    }
+
+    #[test]
+    fn parses_screenshots() {
+        let addr = start("serve_parse");
+        let png = fs::read("monster_refs/goblin.png").unwrap();
+        let (status, body) = post(addr, "/parse", &png);
+        assert_eq!(status, 200, "{body}");
+
+        let img = image::open("monster_refs/goblin.png").unwrap().to_rgba8();
+        let expected = Puzzle::from_image(img.view(0, 0, img.width(), img.height())).unwrap();
+        assert_eq!(serde_json::from_value::<Puzzle>(body).unwrap(), expected);
+
+        let (status, _) = post(addr, "/parse", b"not a png");
+        assert_eq!(status, 400);
+    }
+
+    #[test]
+    fn solves() {
+        let addr = start("serve_solve");
+        let puzzle = corridor();
+        let mut solved = Play::new(puzzle);
+        solved.walls = BackTracker.solve(&puzzle)[0];
+
+        let json = serde_json::to_vec(&puzzle).unwrap();
+        let text = puzzle.to_string();
+        for body in [&json[..], text.as_bytes()] {
+            let (status, body) = post(addr, "/solve", body);
+            assert_eq!(status, 200, "{body}");
+            assert_eq!(body["count"], 1);
+            assert_eq!(body["more"], false);
+            assert_eq!(body["unique"], true);
+            let boards: Vec<Puzzle> = serde_json::from_value(body["solutions"].clone()).unwrap();
+            // Text doesn't carry the seed
+            assert_eq!(boards[0].tiles, solved.board().tiles);
+        }
+    }
+
+    #[test_case("serve_wrong_type", b"{\"tiles\": 3}", 400)]
+    #[test_case("serve_not_json", b"nonsense", 400)]
+    #[test_case("serve_extra_field", b"{\"tiles\": [], \"extra\": 1}", 400)]
+    fn rejects_bad_puzzles(name: &str, body: &[u8], status: u16) {
+        let addr = start(name);
+        assert_eq!(post(addr, "/solve", body).0, status);
+    }
+
+    #[test]
+    fn rejects_walls_and_big_counts() {
+        let addr = start("serve_clues");
+        let mut walled = corridor();
+        walled.tiles[0][0] = Tile::Wall;
+        let mut big = corridor();
+        big.top_nums[0] = 9;
+        for puzzle in [walled, big] {
+            let (status, _) = post(addr, "/solve", &serde_json::to_vec(&puzzle).unwrap());
+            assert_eq!(status, 422);
+        }
+    }
+
+    #[test]
+    fn bounded_solves() {
+        // Nothing but counts has thousands of solutions, the search stops just past what's sent
+        let open = Puzzle {
+            top_nums: [2; 8],
+            left_nums: [2; 8],
+            ..Default::default()
+        };
+        let response = solve(&open, SOLVE_STEPS);
+        assert_eq!(response.status, 200, "{}", response.body);
+        assert_eq!(response.body["count"], MAX_SOLUTIONS_SENT + 1);
+        assert_eq!(response.body["more"], true);
+        assert_eq!(response.body["unique"], false);
+        assert_eq!(
+            response.body["solutions"].as_array().unwrap().len(),
+            MAX_SOLUTIONS_SENT
+        );
+
+        // Out of steps before the last row
+        assert_eq!(solve(&corridor(), 7).status, 422);
+        assert_eq!(solve(&corridor(), SOLVE_STEPS).status, 200);
+    }
+
+    #[test]
+    fn limits_connections() {
+        let addr = start("serve_busy");
+        let held: Vec<_> = (0..MAX_CONNECTIONS)
+            .map(|_| TcpStream::connect(addr).unwrap())
+            .collect();
+        let (status, body) = get(addr, "/");
+        assert_eq!(status, 503, "{body}");
+
+        // Places free up as connections finish
+        drop(held);
+        let freed = (0..50).any(|_| {
+            thread::sleep(Duration::from_millis(20));
+            get(addr, "/").0 == 200
+        });
+        assert!(freed);
+    }
+
+    #[test]
+    fn looks_up_seeds() {
+        let addr = start("serve_seeds");
+        let (status, body) = get(addr, "/puzzles/5");
+        assert_eq!(status, 200);
+        assert_eq!(serde_json::from_value::<Puzzle>(body).unwrap(), corridor());
+        assert_eq!(get(addr, "/puzzles/6").0, 404);
+        assert_eq!(get(addr, "/puzzles/five").0, 400);
+    }
+
+    #[test]
+    fn hints() {
+        let addr = start("serve_hints");
+        let (status, body) = get(addr, "/hint?seed=5");
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            json!({ "x": 2, "y": 0, "hint": "Hint: column 2, row 0 should be a wall" })
+        );
+
+        // A wall on the corridor is the first thing to fix
+        let (_, body) = get(addr, "/hint?seed=5&walls=8000000000000000");
+        assert_eq!((&body["x"], &body["y"]), (&json!(0), &json!(0)));
+
+        let solution = BackTracker.solve(&corridor())[0];
+        let monsters = (0..6).fold(0, |acc, y| acc | bit(1, y));
+        let open = !solution & !monsters;
+        let (_, body) = get(
+            addr,
+            &format!("/hint?seed=5&walls={solution:016x}&open={open:016x}"),
+        );
+        assert_eq!(body, json!({ "hint": "Nothing certain left to fill in" }));
+
+        assert_eq!(get(addr, "/hint").0, 400);
+        assert_eq!(get(addr, "/hint?seed=5&walls=zz").0, 400);
+        // A wall on a monster, and a tile marked both ways
+        let (status, body) = get(addr, &format!("/hint?seed=5&walls={:016x}", bit(1, 0)));
+        assert_eq!(status, 422);
+        assert_eq!(
+            body["error"],
+            "Tile 1,0 isn't empty, so it can't be a wall or open"
+        );
+        let both = format!("{:016x}", bit(0, 0));
+        assert_eq!(
+            get(addr, &format!("/hint?seed=5&walls={both}&open={both}")).0,
+            422
+        );
+        assert_eq!(get(addr, "/hint?seed=6").0, 404);
+    }
+
+    #[test]
+    fn size_limits() {
+        let addr = start("serve_limits");
+        // Turned away on the header alone, before any of the body is sent
+        let too_big = format!(
+            "POST /solve HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_PUZZLE_LEN + 1
+        );
+        assert_eq!(send(addr, too_big.as_bytes()).0, 413);
+        let too_big = format!(
+            "POST /parse HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_IMAGE_LEN + 1
+        );
+        assert_eq!(send(addr, too_big.as_bytes()).0, 413);
+
+        let long_head = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_HEAD_LEN));
+        assert_eq!(send(addr, long_head.as_bytes()).0, 431);
+        assert_eq!(send(addr, b"POST /solve HTTP/1.1\r\n\r\n").0, 411);
+    }
+
+    #[test]
+    fn routes() {
+        let addr = start("serve_routes");
+        assert_eq!(get(addr, "/").0, 200);
+        assert_eq!(get(addr, "/nowhere").0, 404);
+        assert_eq!(get(addr, "/solve").0, 405);
+        assert_eq!(post(addr, "/puzzles/5", b"").0, 405);
+        assert_eq!(send(addr, b"nonsense\r\n\r\n").0, 400);
+    }
+}
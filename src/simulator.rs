@@ -0,0 +1,809 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use anyhow::Result;
+use image::{imageops, GenericImageView, RgbaImage};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::backend::{InputSink, Key, ScreenSource};
+use crate::digits::{self, DIGIT_SIZE};
+use crate::layout::{Controls, Layout};
+use crate::puzzle::{self, Puzzle, Tile, BACKGROUND_PIXELS, SEED_INK_RED, TREASURE_COLOR};
+use crate::solve;
+
+// Anything that isn't a tile's background or treasure reads as occupied
+const MONSTER_COLOR: [u8; 4] = [150, 40, 40, 255];
+const WALL_COLOR: [u8; 4] = [96, 88, 80, 255];
+const VICTORY_COLOR: [u8; 4] = [80, 200, 80, 255];
+const MENU_COLOR: [u8; 4] = [30, 30, 60, 255];
+const MARK_COLOR: [u8; 4] = [200, 200, 200, 255];
+
+// The open menu, drawn centred on the reset button. It stays clear of the seed and wall counts.
+const MENU_PANEL_SIZE: (u32, u32) = (60, 18);
+
+// Marks go in the bottom left of a tile, away from the point parsing looks at
+const MARK_RECT: (u32, u32, u32, u32) = (4, 24, 6, 6);
+
+// Buttons react to clicks within this many pixels of their centre
+const BUTTON_RADIUS: u32 = 8;
+
+// The victory banner goes in the strip below the board so it doesn't upset parsing
+const VICTORY_BANNER_GAP: u32 = 4;
+const VICTORY_BANNER_HEIGHT: u32 = 12;
+
+// Digits cut out of real screenshots: wall count cells and seed digit strips
+pub struct Glyphs {
+    counts: BTreeMap<u8, RgbaImage>,
+    seed: BTreeMap<u8, RgbaImage>,
+    // Where the first seed digit starts
+    seed_x: u32,
+}
+
+impl Glyphs {
+    // Parse each screenshot and keep the first example of every digit found
+    pub fn from_screenshots(screenshots: &[RgbaImage], layout: &Layout) -> Result<Self> {
+        let mut glyphs = Self {
+            counts: BTreeMap::new(),
+            seed: BTreeMap::new(),
+            seed_x: u32::MAX,
+        };
+        for img in screenshots {
+            let view = img.view(0, 0, img.width(), img.height());
+            let Ok(puzzle) = Puzzle::from_image_with(view, layout) else {
+                continue;
+            };
+            for i in 0..8 {
+                let (x, y) = layout.top_count_corner(i);
+                glyphs
+                    .counts
+                    .entry(puzzle.top_nums[i])
+                    .or_insert_with(|| img.view(x, y, DIGIT_SIZE.0, DIGIT_SIZE.1).to_image());
+                let (x, y) = layout.left_count_corner(i);
+                glyphs
+                    .counts
+                    .entry(puzzle.left_nums[i])
+                    .or_insert_with(|| img.view(x, y, DIGIT_SIZE.0, DIGIT_SIZE.1).to_image());
+            }
+
+            let strip = &layout.seed;
+            let seed = strip.region.view(&view);
+            let digits = puzzle::seed_digits(&seed, strip)?;
+            if let Some(&(x, _)) = digits.first() {
+                glyphs.seed_x = glyphs.seed_x.min(x);
+            }
+            let (strip_width, height) = strip.region.size;
+            for (x, digit) in digits {
+                let width = strip.digit_widths[digit as usize].min(strip_width - x);
+                glyphs
+                    .seed
+                    .entry(digit)
+                    .or_insert_with(|| seed.view(x, 0, width, height).to_image());
+            }
+        }
+
+        if glyphs.counts.is_empty() {
+            return Err(anyhow!("No readable screenshots to take digits from"));
+        }
+        Ok(glyphs)
+    }
+}
+
+// Unlike util::draw_rect this doesn't blend, the parser wants exact colours
+fn fill_rect(img: &mut RgbaImage, x: u32, y: u32, w: u32, h: u32, col: [u8; 4]) {
+    for j in y..y + h {
+        for i in x..x + w {
+            img.put_pixel(i, j, col.into());
+        }
+    }
+}
+
+fn near(click: (u32, u32), button: (u32, u32)) -> bool {
+    click.0.abs_diff(button.0) <= BUTTON_RADIUS && click.1.abs_diff(button.1) <= BUTTON_RADIUS
+}
+
+struct Game {
+    layout: Layout,
+    // A screenshot with the board, counts and seed wiped
+    blank: RgbaImage,
+    glyphs: Glyphs,
+    puzzles: Vec<Puzzle>,
+    rng: StdRng,
+    current: usize,
+    walls: u64,
+    // Cells marked as empty
+    marks: u64,
+    menu_open: bool,
+    // What's been typed into the seed entry so far
+    typed: Option<String>,
+    won: bool,
+    wins: usize,
+    clicks: usize,
+    // Whether a drag paints walls on every empty tile it covers
+    drag_painting: bool,
+    // Inputs with less time than this between them get dropped
+    required_delay: Duration,
+    delay: Duration,
+    // How long inputs take to show up, and what's on screen until then
+    latency: Duration,
+    stale: Option<(Instant, RgbaImage)>,
+    // Once the window's gone, every capture and input fails
+    closed: bool,
+}
+
+impl Game {
+    fn show(&mut self, index: usize) {
+        self.current = index;
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.walls = 0;
+        self.marks = 0;
+        self.won = false;
+    }
+
+    // Keep showing the screen from before an input until the latency has passed
+    fn hold_screen(&mut self) {
+        if self.latency.is_zero() {
+            return;
+        }
+        let shown = match self.stale.take() {
+            Some((_, img)) => Some(img),
+            None => self.render().ok(),
+        };
+        self.stale = shown.map(|img| (Instant::now() + self.latency, img));
+    }
+
+    fn window(&mut self) -> Result<&mut Self> {
+        match self.closed {
+            true => Err(anyhow!("Game window closed")),
+            false => Ok(self),
+        }
+    }
+
+    fn capture(&mut self) -> Result<RgbaImage> {
+        match self.stale.take() {
+            Some((until, img)) if Instant::now() < until => {
+                self.stale = Some((until, img.clone()));
+                Ok(img)
+            }
+            _ => self.render(),
+        }
+    }
+
+    fn click(&mut self, x: u32, y: u32) {
+        self.hold_screen();
+        self.clicks += 1;
+        if self.delay < self.required_delay {
+            return;
+        }
+        let controls = self.layout.controls;
+        let menu_was_open = std::mem::take(&mut self.menu_open);
+        self.typed = None;
+
+        if controls.seed_entry.is_some_and(|entry| near((x, y), entry)) {
+            self.typed = Some(String::new());
+        } else if near((x, y), controls.random) {
+            // Never the same board twice in a row, as good as true for the game's seed space.
+            // Some screenshots are of the same board, so compare puzzles rather than indices.
+            // Random boards always have a seed, curated ones are only reached from the menu.
+            let current = self.puzzles[self.current];
+            let differs = |i: &usize| self.puzzles[*i] != current;
+            let seeded: Vec<usize> = (0..self.puzzles.len())
+                .filter(|i| differs(i) && self.puzzles[*i].seed.is_some())
+                .collect();
+            let others = match seeded.is_empty() {
+                true => (0..self.puzzles.len()).filter(differs).collect(),
+                false => seeded,
+            };
+            if !others.is_empty() {
+                let index = others[self.rng.gen_range(0..others.len())];
+                self.show(index);
+            } else {
+                self.clear();
+            }
+        } else if near((x, y), controls.menu) {
+            self.menu_open = true;
+        } else if menu_was_open && near((x, y), controls.reset) {
+            self.clear();
+        } else if let Some((tile_x, tile_y)) = self.layout.board.tile_at((x, y)) {
+            self.toggle_walls(puzzle::bit(tile_x, tile_y));
+        }
+    }
+
+    fn right_click(&mut self, x: u32, y: u32) {
+        self.hold_screen();
+        self.clicks += 1;
+        let Some((tile_x, tile_y)) = self.layout.board.tile_at((x, y)) else {
+            return;
+        };
+        let bit = puzzle::bit(tile_x, tile_y);
+        if self.won
+            || self.walls & bit != 0
+            || self.puzzles[self.current].tiles[tile_y][tile_x] != Tile::Empty
+        {
+            return;
+        }
+        self.marks ^= bit;
+    }
+
+    fn key(&mut self, key: Key) {
+        self.hold_screen();
+        match (key, &mut self.typed) {
+            (Key::Char(c), Some(typed)) if c.is_ascii_digit() => typed.push(c),
+            (Key::Backspace, Some(typed)) => {
+                typed.pop();
+            }
+            (Key::Enter, Some(typed)) => {
+                let seed = typed.parse().ok();
+                self.typed = None;
+                if let Some(index) = self
+                    .puzzles
+                    .iter()
+                    .position(|p| seed.is_some() && p.seed == seed)
+                {
+                    self.show(index);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Painting sets walls on all the empty tiles in a line, or clears them if the first tile
+    // had one. Without it a press and release on different tiles does nothing.
+    fn drag(&mut self, from: (u32, u32), to: (u32, u32)) {
+        self.hold_screen();
+        self.clicks += 1;
+        if !self.drag_painting || self.delay < self.required_delay {
+            return;
+        }
+        let board = &self.layout.board;
+        let (Some(a), Some(b)) = (board.tile_at(from), board.tile_at(to)) else {
+            return;
+        };
+        if a.0 != b.0 && a.1 != b.1 {
+            return;
+        }
+        let mut line = 0u64;
+        for y in a.1.min(b.1)..=a.1.max(b.1) {
+            for x in a.0.min(b.0)..=a.0.max(b.0) {
+                line |= puzzle::bit(x, y);
+            }
+        }
+        let first = puzzle::bit(a.0, a.1);
+        match self.walls & first != 0 {
+            true => self.toggle_walls(self.walls & line),
+            false => self.toggle_walls(!self.walls & line),
+        }
+    }
+
+    // Flip walls on the given tiles, skipping any that can't take one
+    fn toggle_walls(&mut self, mask: u64) {
+        if self.won {
+            return;
+        }
+        let puzzle = &self.puzzles[self.current];
+        let empty = puzzle
+            .tiles
+            .as_flattened()
+            .iter()
+            .fold(0u64, |acc, &t| (acc << 1) | (t == Tile::Empty) as u64);
+        self.walls ^= mask & empty;
+        self.marks &= !self.walls;
+        if self.solved() {
+            self.won = true;
+            self.wins += 1;
+        }
+    }
+
+    // Judged the same way the solver judges its answers: the wall counts have to add up and the
+    // board has to pass the solver's rules check
+    fn solved(&self) -> bool {
+        let mut puzzle = self.puzzles[self.current];
+        for (i, tile) in puzzle.tiles.as_flattened_mut().iter_mut().enumerate() {
+            if self.walls & puzzle::bit(i % 8, i / 8) != 0 {
+                *tile = Tile::Wall;
+            }
+        }
+        let counts_match = (0..8).all(|i| {
+            let row = puzzle.tiles[i].iter().filter(|&&t| t == Tile::Wall).count();
+            let col = puzzle.tiles.iter().filter(|r| r[i] == Tile::Wall).count();
+            row == puzzle.left_nums[i] as usize && col == puzzle.top_nums[i] as usize
+        });
+        counts_match && solve::is_valid_solution(&puzzle, false)
+    }
+
+    fn render(&self) -> Result<RgbaImage> {
+        let puzzle = &self.puzzles[self.current];
+        let layout = &self.layout;
+        let mut img = self.blank.clone();
+
+        for (y, row) in puzzle.tiles.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                let bit = puzzle::bit(x, y);
+                let wall = self.walls & bit != 0;
+                if self.marks & bit != 0 {
+                    let (tx, ty) = layout.board.tile_corner(x, y);
+                    let (mx, my, w, h) = MARK_RECT;
+                    fill_rect(&mut img, tx + mx, ty + my, w, h, MARK_COLOR);
+                }
+                let col = match tile {
+                    _ if wall => WALL_COLOR,
+                    Tile::Empty | Tile::Wall => continue,
+                    Tile::Treasure => TREASURE_COLOR,
+                    Tile::Monster => MONSTER_COLOR,
+                };
+                let (tx, ty, w, h) = layout.board.tile_rect(x, y);
+                fill_rect(&mut img, tx, ty, w, h, col);
+            }
+        }
+
+        let glyph = |digit: u8| {
+            self.glyphs
+                .counts
+                .get(&digit)
+                .ok_or_else(|| anyhow!("No glyph for a wall count of {digit}"))
+        };
+        for i in 0..8 {
+            let (x, y) = layout.top_count_corner(i);
+            imageops::replace(&mut img, glyph(puzzle.top_nums[i])?, x as i64, y as i64);
+            let (x, y) = layout.left_count_corner(i);
+            imageops::replace(&mut img, glyph(puzzle.left_nums[i])?, x as i64, y as i64);
+        }
+
+        if let Some(seed) = puzzle.seed {
+            let (mut x, y) = layout.seed.region.origin;
+            x += self.glyphs.seed_x;
+            for digit in seed.to_string().bytes().map(|b| b - b'0') {
+                let strip = self
+                    .glyphs
+                    .seed
+                    .get(&digit)
+                    .ok_or_else(|| anyhow!("No glyph for seed digit {digit}"))?;
+                imageops::replace(&mut img, strip, x as i64, y as i64);
+                x += layout.seed.digit_widths[digit as usize];
+            }
+        }
+
+        if self.menu_open {
+            let (reset, (w, h)) = (layout.controls.reset, MENU_PANEL_SIZE);
+            fill_rect(&mut img, reset.0 - w / 2, reset.1 - h / 2, w, h, MENU_COLOR);
+        }
+
+        if self.won {
+            let board = layout.board.region;
+            let (x, y) = (
+                board.origin.0,
+                board.origin.1 + board.size.1 + VICTORY_BANNER_GAP,
+            );
+            fill_rect(
+                &mut img,
+                x,
+                y,
+                board.size.0,
+                VICTORY_BANNER_HEIGHT,
+                VICTORY_COLOR,
+            );
+        }
+
+        Ok(img)
+    }
+}
+
+// Wipe everything a board is drawn from out of a screenshot of the game
+fn blank_screenshot(screenshot: &RgbaImage, layout: &Layout) -> RgbaImage {
+    let mut img = screenshot.clone();
+
+    // Bare floor everywhere
+    for (y, row) in BACKGROUND_PIXELS.iter().enumerate() {
+        for (x, &col) in row.iter().enumerate() {
+            let (tx, ty, w, h) = layout.board.tile_rect(x, y);
+            fill_rect(&mut img, tx, ty, w, h, col);
+        }
+    }
+
+    // Paint over the ink of the wall counts and seed with their surroundings. Each region's
+    // first ink free pixel is used as its background.
+    let mut erase = |(x, y): (u32, u32), (w, h): (u32, u32), is_ink: &dyn Fn([u8; 4]) -> bool| {
+        let region = img.view(x, y, w, h).to_image();
+        let Some(bg) = region.pixels().find(|p| !is_ink(p.0)).copied() else {
+            return;
+        };
+        for (i, j, p) in region.enumerate_pixels() {
+            if is_ink(p.0) {
+                img.put_pixel(x + i, y + j, bg);
+            }
+        }
+    };
+    let count_ink = |p: [u8; 4]| digits::ink_colour(p).is_some();
+    for i in 0..8 {
+        erase(layout.top_count_corner(i), DIGIT_SIZE, &count_ink);
+        erase(layout.left_count_corner(i), DIGIT_SIZE, &count_ink);
+    }
+    let seed = layout.seed.region;
+    erase(seed.origin, seed.size, &|p| p[0] == SEED_INK_RED);
+
+    img
+}
+
+// A stand-in for the game that draws boards the way the parser expects to find them and reacts
+// to clicks the way the game does. Its window is exactly the DnD subwindow.
+pub struct SimulatedGame {
+    game: Rc<RefCell<Game>>,
+}
+
+impl SimulatedGame {
+    // Boards are drawn over the first screenshot, using digits cut from all of them. The first
+    // puzzle is shown to start with and RANDOM picks from the rest using a seeded generator,
+    // so runs are repeatable.
+    pub fn new(screenshots: &[RgbaImage], puzzles: Vec<Puzzle>, seed: u64) -> Result<Self> {
+        let first = screenshots
+            .first()
+            .ok_or_else(|| anyhow!("Need at least one screenshot"))?;
+        if puzzles.is_empty() {
+            return Err(anyhow!("Need at least one puzzle"));
+        }
+
+        let layout = Layout::global()?.clone();
+        let mut game = Game {
+            blank: blank_screenshot(first, &layout),
+            glyphs: Glyphs::from_screenshots(screenshots, &layout)?,
+            layout,
+            puzzles,
+            rng: StdRng::seed_from_u64(seed),
+            current: 0,
+            walls: 0,
+            marks: 0,
+            menu_open: false,
+            typed: None,
+            won: false,
+            wins: 0,
+            clicks: 0,
+            drag_painting: false,
+            required_delay: Duration::ZERO,
+            delay: Duration::ZERO,
+            latency: Duration::ZERO,
+            stale: None,
+            closed: false,
+        };
+        game.show(0);
+        Ok(Self {
+            game: Rc::new(RefCell::new(game)),
+        })
+    }
+
+    // Play the boards from the reference screenshots in a folder (e.g. monster_refs)
+    pub fn from_screenshots(dir: &Path, seed: u64) -> Result<Self> {
+        let screenshots = load_screenshots(dir)?;
+        let puzzles = screenshots
+            .iter()
+            .filter_map(|img| Puzzle::from_image(img.view(0, 0, img.width(), img.height())).ok())
+            .collect();
+        Self::new(&screenshots, puzzles, seed)
+    }
+
+    pub fn set_drag_painting(&self, enabled: bool) {
+        self.game.borrow_mut().drag_painting = enabled;
+    }
+
+    // Drop any input that comes with less than this delay, like a game that can't keep up
+    pub fn set_required_delay(&self, delay: Duration) {
+        self.game.borrow_mut().required_delay = delay;
+    }
+
+    // Move the buttons, or add the ones the built in layout leaves out, like the seed entry box
+    pub fn set_controls(&self, controls: Controls) {
+        self.game.borrow_mut().layout.controls = controls;
+    }
+
+    pub fn marks(&self) -> u64 {
+        self.game.borrow().marks
+    }
+
+    // Delay inputs showing up on screen, like a game that takes a while to redraw
+    pub fn set_latency(&self, latency: Duration) {
+        self.game.borrow_mut().latency = latency;
+    }
+
+    // Like the player quitting the game mid run
+    pub fn close(&self) {
+        self.game.borrow_mut().closed = true;
+    }
+
+    pub fn screen(&self) -> SimulatedScreen {
+        SimulatedScreen(self.game.clone())
+    }
+
+    pub fn input(&self) -> SimulatedInput {
+        SimulatedInput(self.game.clone())
+    }
+
+    pub fn puzzle(&self) -> Puzzle {
+        let game = self.game.borrow();
+        game.puzzles[game.current]
+    }
+
+    // Walls currently placed, in the solver's bit order
+    pub fn walls(&self) -> u64 {
+        self.game.borrow().walls
+    }
+
+    pub fn won(&self) -> bool {
+        self.game.borrow().won
+    }
+
+    // Boards solved so far
+    pub fn wins(&self) -> usize {
+        self.game.borrow().wins
+    }
+
+    pub fn clicks(&self) -> usize {
+        self.game.borrow().clicks
+    }
+}
+
+// Every png in a folder, in name order
+pub fn load_screenshots(dir: &Path) -> Result<Vec<RgbaImage>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "png"))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| Ok(image::open(path)?.to_rgba8()))
+        .collect()
+}
+
+pub struct SimulatedScreen(Rc<RefCell<Game>>);
+
+impl ScreenSource for SimulatedScreen {
+    fn capture(&mut self) -> Result<RgbaImage> {
+        self.0.borrow_mut().window()?.capture()
+    }
+}
+
+pub struct SimulatedInput(Rc<RefCell<Game>>);
+
+impl InputSink for SimulatedInput {
+    fn click(&mut self, x: u32, y: u32) -> Result<()> {
+        self.0.borrow_mut().window()?.click(x, y);
+        Ok(())
+    }
+
+    fn drag(&mut self, from: (u32, u32), to: (u32, u32)) -> Result<()> {
+        self.0.borrow_mut().window()?.drag(from, to);
+        Ok(())
+    }
+
+    fn right_click(&mut self, x: u32, y: u32) -> Result<()> {
+        self.0.borrow_mut().window()?.right_click(x, y);
+        Ok(())
+    }
+
+    fn key(&mut self, key: Key) -> Result<()> {
+        self.0.borrow_mut().window()?.key(key);
+        Ok(())
+    }
+
+    fn set_delay(&mut self, delay: Duration) {
+        self.0.borrow_mut().delay = delay;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon_crawler::DungeonCrawler;
+    use crate::input_plan::WallMismatch;
+    use crate::timing::SAFETY_MARGIN;
+
+    fn simulator() -> SimulatedGame {
+        SimulatedGame::from_screenshots(Path::new("monster_refs"), 7).unwrap()
+    }
+
+    #[test]
+    fn renders_parseable_boards() {
+        let sim = simulator();
+        let mut dc = DungeonCrawler::with_backend(sim.screen(), sim.input()).unwrap();
+        for _ in 0..4 {
+            assert_eq!(dc.parse().unwrap(), sim.puzzle());
+            dc.random_board().unwrap();
+        }
+    }
+
+    #[test]
+    fn walls_and_reset() {
+        let sim = simulator();
+        let mut dc = DungeonCrawler::with_backend(sim.screen(), sim.input()).unwrap();
+        let puzzle = sim.puzzle();
+
+        // Find an empty tile and an occupied one
+        let tiles = puzzle.tiles.as_flattened();
+        let empty = tiles.iter().position(|&t| t == Tile::Empty).unwrap();
+        let occupied = tiles.iter().position(|&t| t != Tile::Empty).unwrap();
+
+        dc.place_wall((empty % 8) as u8, (empty / 8) as u8).unwrap();
+        dc.place_wall((occupied % 8) as u8, (occupied / 8) as u8)
+            .unwrap();
+        assert_eq!(sim.walls(), puzzle::bit(empty % 8, empty / 8));
+
+        // Walls are drawn, and clicking again takes them away
+        let mut screen = sim.screen();
+        let (x, y) = dc.layout.board.sample_point(empty % 8, empty / 8);
+        assert_eq!(screen.capture().unwrap().get_pixel(x, y).0, WALL_COLOR);
+        dc.place_wall((empty % 8) as u8, (empty / 8) as u8).unwrap();
+        assert_eq!(sim.walls(), 0);
+
+        dc.place_wall((empty % 8) as u8, (empty / 8) as u8).unwrap();
+        dc.reset_solution().unwrap();
+        assert_eq!(sim.walls(), 0);
+    }
+
+    // The corridor, under a seed the screenshots have the digits for. Not every real board
+    // solves quickly in a debug build.
+    fn corridor() -> Puzzle {
+        Puzzle {
+            seed: Some(22398633),
+            ..puzzle::corridor().0
+        }
+    }
+
+    fn corridor_solution() -> u64 {
+        puzzle::corridor().1
+    }
+
+    #[test]
+    fn headless_solve() {
+        let screenshots = load_screenshots(Path::new("monster_refs")).unwrap();
+        let sim = SimulatedGame::new(&screenshots, vec![corridor()], 7).unwrap();
+        let mut dc = DungeonCrawler::with_backend(sim.screen(), sim.input()).unwrap();
+        crate::dungeon_crawler::solve_boards(&mut dc, Some(3), Duration::ZERO).unwrap();
+        assert_eq!(sim.wins(), 3);
+    }
+
+    #[test]
+    fn drag_detection() {
+        let screenshots = load_screenshots(Path::new("monster_refs")).unwrap();
+        for painting in [false, true] {
+            let sim = SimulatedGame::new(&screenshots, vec![corridor()], 7).unwrap();
+            sim.set_drag_painting(painting);
+            let mut dc = DungeonCrawler::with_backend(sim.screen(), sim.input()).unwrap();
+            dc.parse().unwrap();
+            assert_eq!(dc.detect_drag().unwrap(), painting);
+            // The board is left as it was found
+            assert_eq!(sim.walls(), 0);
+
+            let report = dc.enter_solution(corridor_solution()).unwrap();
+            assert_eq!(report.missed, 0);
+            assert!(sim.won());
+            assert_eq!(report.strokes < report.walls as usize, painting);
+        }
+    }
+
+    #[test]
+    fn slows_down_for_dropped_clicks() {
+        let screenshots = load_screenshots(Path::new("monster_refs")).unwrap();
+        let sim = SimulatedGame::new(&screenshots, vec![corridor()], 7).unwrap();
+        sim.set_required_delay(Duration::from_millis(30));
+        let mut dc = DungeonCrawler::with_backend(sim.screen(), sim.input()).unwrap();
+        dc.retry.settle = Duration::from_millis(100);
+        dc.parse().unwrap();
+
+        let report = dc.enter_solution(corridor_solution()).unwrap();
+        assert_eq!(report.missed, corridor_solution().count_ones());
+        assert_eq!(report.retries, 1);
+        assert!(sim.won());
+
+        // Second time round it goes in first time
+        dc.reset_solution().unwrap();
+        let report = dc.enter_solution(corridor_solution()).unwrap();
+        assert_eq!(report.missed, 0);
+        assert!(report.delay >= Duration::from_millis(30));
+        assert_eq!(sim.wins(), 2);
+    }
+
+    #[test]
+    fn waits_for_slow_redraws() {
+        let screenshots = load_screenshots(Path::new("monster_refs")).unwrap();
+        let sim = SimulatedGame::new(&screenshots, vec![corridor()], 7).unwrap();
+        // Much slower than the screenshot delay, so the first check still sees the old board
+        sim.set_latency(Duration::from_millis(80));
+        let mut dc = DungeonCrawler::with_backend(sim.screen(), sim.input()).unwrap();
+        dc.parse().unwrap();
+
+        let report = dc.enter_solution(corridor_solution()).unwrap();
+        assert_eq!(report.retries, 0);
+        assert_eq!(sim.walls(), corridor_solution());
+        assert!(sim.won());
+    }
+
+    #[test]
+    fn gives_up_with_diff() {
+        let screenshots = load_screenshots(Path::new("monster_refs")).unwrap();
+        let sim = SimulatedGame::new(&screenshots, vec![corridor()], 7).unwrap();
+        // Slower than the delay will ever go, so nothing registers
+        sim.set_required_delay(Duration::from_secs(1));
+        let mut dc = DungeonCrawler::with_backend(sim.screen(), sim.input()).unwrap();
+        dc.retry.retries = 2;
+        dc.retry.settle = Duration::from_millis(100);
+        dc.parse().unwrap();
+
+        let error = dc.enter_solution(corridor_solution()).unwrap_err();
+        let mismatch = error.downcast_ref::<WallMismatch>().unwrap();
+        assert_eq!(mismatch.placed, 0);
+        assert_eq!(mismatch.attempts, 3);
+        assert!(error
+            .to_string()
+            .ends_with("\n..++++++\n.+++++++\n........"));
+    }
+
+    #[test]
+    fn calibration() {
+        let latency = Duration::from_millis(15);
+        let sim = simulator();
+        sim.set_latency(latency);
+        let mut dc = DungeonCrawler::with_backend(sim.screen(), sim.input()).unwrap();
+        dc.parse().unwrap();
+
+        let profile = dc.calibrate(2).unwrap();
+        for ms in [
+            profile.click_delay_ms,
+            profile.screenshot_delay_ms,
+            profile.menu_delay_ms,
+        ] {
+            let delay = Duration::from_millis(ms);
+            assert!(delay >= latency.mul_f32(SAFETY_MARGIN), "{profile:?}");
+            assert!(delay < Duration::from_millis(1000), "{profile:?}");
+        }
+        assert_eq!(dc.timing(), profile);
+        // The calibration clicks leave no walls behind
+        assert_eq!(sim.walls(), 0);
+    }
+
+    #[test]
+    fn marks() {
+        let screenshots = load_screenshots(Path::new("monster_refs")).unwrap();
+        let sim = SimulatedGame::new(&screenshots, vec![corridor()], 7).unwrap();
+        let mut dc = DungeonCrawler::with_backend(sim.screen(), sim.input()).unwrap();
+        let board = dc.parse().unwrap();
+
+        // Marks don't get mistaken for walls, or anything else
+        dc.mark_empty(0, 0).unwrap();
+        assert_eq!(sim.marks(), 1 << 63);
+        assert_eq!(dc.parse().unwrap(), board);
+
+        // A wall takes the place of a mark, and can't be marked over
+        dc.place_wall(0, 0).unwrap();
+        dc.mark_empty(0, 0).unwrap();
+        assert_eq!((sim.walls(), sim.marks()), (1 << 63, 0));
+    }
+
+    #[test]
+    fn seed_entry() {
+        let sim = simulator();
+        let mut dc = DungeonCrawler::with_backend(sim.screen(), sim.input()).unwrap();
+        let puzzles: Vec<Puzzle> = load_screenshots(Path::new("monster_refs"))
+            .unwrap()
+            .iter()
+            .map(|img| Puzzle::from_image(img.view(0, 0, img.width(), img.height())).unwrap())
+            .collect();
+
+        assert!(dc.enter_seed(22398633).is_err());
+        let entry = dc.layout.board.click_point(0, 0);
+        dc.layout.controls.seed_entry = Some(entry);
+        sim.set_controls(dc.layout.controls);
+        let wanted = puzzles.iter().find(|p| p.seed == Some(22398633)).unwrap();
+        dc.enter_seed(22398633).unwrap();
+        assert_eq!(&dc.parse().unwrap(), wanted);
+    }
+}
@@ -1,6 +1,4 @@
-use std::time::Instant;
-
-use crate::puzzle::{Puzzle, Tile};
+use crate::puzzle::{bit, Puzzle, Tile};
 
 pub trait Solver {
     fn solve(&self, puzzle: &Puzzle) -> Vec<u64>;
@@ -9,18 +7,8 @@ pub struct BackTracker;
 
 impl Solver for BackTracker {
     fn solve(&self, puzzle: &Puzzle) -> Vec<u64> {
-        let mut solutions = Vec::new();
-
-        let now = Instant::now();
-        BackTracker::solve_recursive(puzzle, puzzle.top_nums, 0, &mut solutions);
-
-        println!(
-            "{} solutions found in {:.2}s",
-            solutions.len(),
-            now.elapsed().as_secs_f32()
-        );
-
-        return solutions;
+        self.solve_bounded(puzzle, Budget::UNLIMITED)
+            .into_solutions()
         // match solutions.len() {
         //     0 => Err(SolverError::None),
         //     1 => Ok(solutions),
@@ -29,13 +17,79 @@ impl Solver for BackTracker {
     }
 }
 
+// How far a search goes. Clues with few monsters can have millions of solutions and take
+// minutes to go through, so anything answering on request (the server, the editor) sets one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Budget {
+    // Stop once this many solutions are found
+    pub solutions: usize,
+    // Give up after placing this many rows of walls, across the whole search. A release build
+    // gets through a few million a second.
+    pub steps: u64,
+}
+
+impl Budget {
+    pub const UNLIMITED: Budget = Budget {
+        solutions: usize::MAX,
+        steps: u64::MAX,
+    };
+}
+
+// What a bounded search found
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bounded {
+    // Every solution there is
+    All(Vec<u64>),
+    // Stopped at the solution limit, there may be more
+    AtLeast(Vec<u64>),
+    // Ran out of steps, with whatever was found by then
+    GaveUp(Vec<u64>),
+}
+
+impl Bounded {
+    pub fn solutions(&self) -> &[u64] {
+        match self {
+            Bounded::All(solutions) | Bounded::AtLeast(solutions) | Bounded::GaveUp(solutions) => {
+                solutions
+            }
+        }
+    }
+
+    pub fn into_solutions(self) -> Vec<u64> {
+        match self {
+            Bounded::All(solutions) | Bounded::AtLeast(solutions) | Bounded::GaveUp(solutions) => {
+                solutions
+            }
+        }
+    }
+}
+
+struct Search {
+    solutions: Vec<u64>,
+    budget: Budget,
+    steps: u64,
+    gave_up: bool,
+}
+
 impl BackTracker {
-    pub fn solve_recursive(
-        puzzle: &Puzzle,
-        col_counts: [u8; 8],
-        depth: usize,
-        solutions: &mut Vec<u64>,
-    ) {
+    pub fn solve_bounded(&self, puzzle: &Puzzle, budget: Budget) -> Bounded {
+        let mut search = Search {
+            solutions: Vec::new(),
+            budget,
+            steps: 0,
+            gave_up: false,
+        };
+        BackTracker::solve_recursive(puzzle, puzzle.top_nums, 0, &mut search);
+        if search.gave_up {
+            Bounded::GaveUp(search.solutions)
+        } else if search.solutions.len() >= budget.solutions {
+            Bounded::AtLeast(search.solutions)
+        } else {
+            Bounded::All(search.solutions)
+        }
+    }
+
+    fn solve_recursive(puzzle: &Puzzle, col_counts: [u8; 8], depth: usize, search: &mut Search) {
         let row_wall_count = puzzle.left_nums[depth];
 
         let empty_cells_mask = puzzle.tiles[depth]
@@ -49,9 +103,11 @@ impl BackTracker {
 
         let mask = empty_cells_mask & needs_walls_mask;
 
-        if mask.count_ones() < row_wall_count.into() {
-            return;
-        } else if mask.count_ones() > row_wall_count.into() && depth == 7 {
+        // Too few places for this row's walls, or on the last row, more columns still short of
+        // walls than the row can give
+        if mask.count_ones() < row_wall_count.into()
+            || (mask.count_ones() > row_wall_count.into() && depth == 7)
+        {
             return;
         }
 
@@ -71,32 +127,41 @@ impl BackTracker {
                 last_mask = walls_to_place_mask;
             }
 
+            if search.solutions.len() >= search.budget.solutions {
+                return;
+            }
+            if search.steps >= search.budget.steps {
+                search.gave_up = true;
+                return;
+            }
+            search.steps += 1;
+
             // for _ in 0..depth {
             //     print!(" ");
             // }
             // println!("{walls_to_place_mask:08b}");
 
-            let mut puzzle = puzzle.clone();
-            let mut counts = col_counts.clone();
-            for i in 0..8 {
+            let mut puzzle = *puzzle;
+            let mut counts = col_counts;
+            for (i, (tile, count)) in puzzle.tiles[depth].iter_mut().zip(&mut counts).enumerate() {
                 if (walls_to_place_mask & (1 << (7 - i))) >= 1 {
-                    puzzle.tiles[depth][i] = Tile::Wall;
-                    counts[i] -= 1;
+                    *tile = Tile::Wall;
+                    *count -= 1;
                 }
             }
 
             if depth == 7 {
                 if is_valid_solution(&puzzle, false) {
                     let sol = walls_to_solution(&puzzle);
-                    if solutions.contains(&sol) {
+                    if search.solutions.contains(&sol) {
                         // println!("exists");
                     } else {
                         is_valid_solution(&puzzle, false);
-                        solutions.push(sol);
+                        search.solutions.push(sol);
                     }
                 }
             } else {
-                BackTracker::solve_recursive(&puzzle, counts, depth + 1, solutions);
+                BackTracker::solve_recursive(&puzzle, counts, depth + 1, search);
             }
         }
     }
@@ -116,7 +181,7 @@ fn print_solution(sol: u64) {
     }
 }
 
-fn is_valid_solution(puzzle: &Puzzle, debug: bool) -> bool {
+pub(crate) fn is_valid_solution(puzzle: &Puzzle, debug: bool) -> bool {
     // look for contradictions
     let monster_mask = puzzle.tiles.as_flattened().iter().fold(0u64, |acc, &tile| {
         (acc << 1) + if tile == Tile::Monster { 1 } else { 0 }
@@ -183,8 +248,8 @@ fn is_valid_solution(puzzle: &Puzzle, debug: bool) -> bool {
     // Find location of treasure rooms
     let e_mask = empty_mask | treasure_mask;
     let mut room_mask = e_mask;
-    room_mask = room_mask & ((e_mask << 1) & 0xfefefefefefefefe);
-    room_mask = room_mask & ((e_mask << 2) & 0xfcfcfcfcfcfcfcfc);
+    room_mask &= (e_mask << 1) & 0xfefefefefefefefe;
+    room_mask &= (e_mask << 2) & 0xfcfcfcfcfcfcfcfc;
     if debug {
         println!("empty mask");
         print_solution(e_mask);
@@ -207,3 +272,127 @@ fn is_valid_solution(puzzle: &Puzzle, debug: bool) -> bool {
 
     true
 }
+
+// The cells is_valid_solution objects to, so a board can show where it goes wrong: monsters
+// without exactly one empty tile next to them, open tiles cut off from the biggest open area,
+// and every treasure when the 3x3 rooms don't add up. Empty when is_valid_solution passes.
+pub(crate) fn violations(puzzle: &Puzzle) -> u64 {
+    let mask_of = |wanted: Tile| {
+        puzzle.tiles.as_flattened().iter().fold(0u64, |acc, &tile| {
+            (acc << 1) + if tile == wanted { 1 } else { 0 }
+        })
+    };
+    let monster_mask = mask_of(Tile::Monster);
+    let treasure_mask = mask_of(Tile::Treasure);
+    let empty_mask = mask_of(Tile::Empty);
+    let mut bad = 0;
+
+    for y in 0..8 {
+        for x in 0..8 {
+            if monster_mask & bit(x, y) == 0 {
+                continue;
+            }
+            let neighbours = [
+                (x > 0).then(|| bit(x - 1, y)),
+                (x < 7).then(|| bit(x + 1, y)),
+                (y > 0).then(|| bit(x, y - 1)),
+                (y < 7).then(|| bit(x, y + 1)),
+            ];
+            let empty = neighbours
+                .into_iter()
+                .flatten()
+                .filter(|&b| empty_mask & b != 0)
+                .count();
+            if empty != 1 {
+                bad |= bit(x, y);
+            }
+        }
+    }
+
+    // Everything outside the biggest connected area counts as cut off
+    let e_mask = empty_mask | treasure_mask;
+    let mut unvisited = e_mask;
+    let mut largest = 0u64;
+    while unvisited != 0 {
+        let mut flood_mask = 1u64 << unvisited.trailing_zeros();
+        loop {
+            let mut flood_mask2 = flood_mask;
+            flood_mask2 |= ((flood_mask << 1) & 0xfefefefefefefefe) & e_mask;
+            flood_mask2 |= ((flood_mask2 >> 1) & 0x7f7f7f7f7f7f7f7f) & e_mask;
+            flood_mask2 |= (flood_mask2 << 8) & e_mask;
+            flood_mask2 |= (flood_mask2 >> 8) & e_mask;
+            if flood_mask2 == flood_mask {
+                break;
+            }
+            flood_mask = flood_mask2;
+        }
+        if flood_mask.count_ones() > largest.count_ones() {
+            largest = flood_mask;
+        }
+        unvisited &= !flood_mask;
+    }
+    bad |= e_mask & !largest;
+
+    let mut room_mask = e_mask;
+    room_mask &= (e_mask << 1) & 0xfefefefefefefefe;
+    room_mask &= (e_mask << 2) & 0xfcfcfcfcfcfcfcfc;
+    room_mask &= (room_mask << 8) & (room_mask << 16);
+    if room_mask.count_ones() != treasure_mask.count_ones() {
+        bad |= treasure_mask;
+    }
+
+    bad
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn violations_agree_with_validator() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..2000 {
+            let mut puzzle = Puzzle::default();
+            for tile in puzzle.tiles.iter_mut().flatten() {
+                *tile = match rng.gen_range(0..20) {
+                    0..=1 => Tile::Monster,
+                    2 => Tile::Treasure,
+                    3..=9 => Tile::Wall,
+                    _ => Tile::Empty,
+                };
+            }
+            if puzzle.tiles.iter().flatten().all(|&t| t != Tile::Empty) {
+                continue;
+            }
+            assert_eq!(
+                violations(&puzzle) == 0,
+                is_valid_solution(&puzzle, false),
+                "\n{puzzle}"
+            );
+        }
+    }
+
+    #[test]
+    fn violations_point_at_the_problem() {
+        // A corridor along the top with a monster at the end, then walls
+        let mut puzzle = Puzzle::default();
+        for row in &mut puzzle.tiles[1..] {
+            *row = [Tile::Wall; 8];
+        }
+        puzzle.tiles[0][7] = Tile::Monster;
+        assert_eq!(violations(&puzzle), 0);
+
+        // Opening the tiles under the end gives the monster two ways out, and cuts nothing off
+        puzzle.tiles[1][6] = Tile::Empty;
+        puzzle.tiles[1][7] = Tile::Empty;
+        assert_eq!(violations(&puzzle), bit(7, 0));
+
+        // An open tile away from the corridor is cut off
+        puzzle.tiles[1][6] = Tile::Wall;
+        puzzle.tiles[1][7] = Tile::Wall;
+        puzzle.tiles[5][2] = Tile::Empty;
+        assert_eq!(violations(&puzzle), bit(2, 5));
+    }
+}
@@ -0,0 +1,325 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use image::{
+    self,
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::{self, FilterType},
+    Delay, RgbaImage,
+};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+};
+
+use crate::assets::AssetLocator;
+use crate::monsters::SpriteOffsets;
+use crate::util::Versioned;
+
+// Size of a board tile. Sprites are aligned so the tile they stand on lands at the same spot
+// in every frame.
+const TILE_SIZE: u32 = 32;
+
+// Monsters per row when they're all exported together
+const MONSTERS_PER_ROW: usize = 6;
+
+#[derive(Debug, Copy, Clone)]
+pub struct ExportOptions {
+    // Nearest neighbour upscaling, so pixels stay crisp
+    pub scale: u32,
+    // Time each frame is shown for in an animation
+    pub delay_ms: u32,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1,
+            delay_ms: 150,
+        }
+    }
+}
+
+// The decoded frames of a monster's array texture, in order. Frames are numbered from 0 and
+// the numbers are used as indices elsewhere, so a gap is an error.
+pub fn load_frames(dir: &Path) -> Result<Vec<RgbaImage>> {
+    let mut paths: Vec<(u32, std::path::PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "png"))
+        .filter_map(|p| Some((p.file_stem()?.to_str()?.parse().ok()?, p)))
+        .collect();
+    paths.sort();
+
+    let mut frames: Vec<RgbaImage> = Vec::new();
+    for (i, path) in paths {
+        if i as usize != frames.len() {
+            return Err(anyhow!(
+                "Frame {} missing in {}",
+                frames.len(),
+                dir.display()
+            ));
+        }
+        frames.push(image::open(path)?.to_rgba8());
+    }
+    if frames.is_empty() {
+        return Err(anyhow!("No frames in {}", dir.display()));
+    }
+    Ok(frames)
+}
+
+// Put every frame on a canvas of the same size, lined up the way the game draws them. Frames
+// share a bottom right corner, and the largest one sits at -offset from the tile's corner.
+// The canvas always covers the whole tile.
+pub fn align_frames(frames: &[RgbaImage], offset: (i32, i32)) -> Vec<RgbaImage> {
+    let max_w = frames.iter().map(|f| f.width()).max().unwrap_or(0) as i32;
+    let max_h = frames.iter().map(|f| f.height()).max().unwrap_or(0) as i32;
+
+    // Position of each frame relative to the tile corner
+    let positions: Vec<(i32, i32)> = frames
+        .iter()
+        .map(|f| {
+            (
+                max_w - f.width() as i32 - offset.0,
+                max_h - f.height() as i32 - offset.1,
+            )
+        })
+        .collect();
+
+    let tile = TILE_SIZE as i32;
+    let left = positions.iter().map(|p| p.0).chain([0]).min().unwrap();
+    let top = positions.iter().map(|p| p.1).chain([0]).min().unwrap();
+    let right = (max_w - offset.0).max(tile);
+    let bottom = (max_h - offset.1).max(tile);
+
+    frames
+        .iter()
+        .zip(positions)
+        .map(|(frame, (x, y))| {
+            let mut canvas = RgbaImage::new((right - left) as u32, (bottom - top) as u32);
+            imageops::overlay(&mut canvas, frame, (x - left) as i64, (y - top) as i64);
+            canvas
+        })
+        .collect()
+}
+
+// Lay equally sized images out in a grid, left to right then top to bottom
+pub fn tile_images(images: &[RgbaImage], columns: usize) -> RgbaImage {
+    let Some(first) = images.first() else {
+        return RgbaImage::new(0, 0);
+    };
+    let (w, h) = first.dimensions();
+    let columns = columns.clamp(1, images.len());
+    let rows = images.len().div_ceil(columns);
+
+    let mut sheet = RgbaImage::new(w * columns as u32, h * rows as u32);
+    for (i, img) in images.iter().enumerate() {
+        let (x, y) = ((i % columns) as u32 * w, (i / columns) as u32 * h);
+        imageops::replace(&mut sheet, img, x as i64, y as i64);
+    }
+    sheet
+}
+
+fn scale(img: &RgbaImage, factor: u32) -> RgbaImage {
+    match factor {
+        0 | 1 => img.clone(),
+        _ => imageops::resize(
+            img,
+            img.width() * factor,
+            img.height() * factor,
+            FilterType::Nearest,
+        ),
+    }
+}
+
+pub fn write_gif(path: &Path, frames: &[RgbaImage], options: ExportOptions) -> Result<()> {
+    let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+    encoder.set_repeat(Repeat::Infinite)?;
+    let delay = Delay::from_numer_denom_ms(options.delay_ms, 1);
+    encoder.encode_frames(
+        frames
+            .iter()
+            .map(|f| image::Frame::from_parts(scale(f, options.scale), 0, 0, delay)),
+    )?;
+    Ok(())
+}
+
+// Aligned frames for one monster. Monsters missing from the offsets file are drawn with their
+// bottom right corner on the tile's.
+fn monster_frames(
+    assets: &AssetLocator,
+    offsets: &SpriteOffsets,
+    monster: &str,
+) -> Result<Vec<RgbaImage>> {
+    let frames = load_frames(&assets.sprites(monster))?;
+    let offset = match offsets.monsters.get(monster) {
+        Some(found) => found.offset,
+        None => {
+            println!("No offset for {monster}, leaving it unaligned");
+            let w = frames.iter().map(|f| f.width()).max().unwrap_or(0);
+            let h = frames.iter().map(|f| f.height()).max().unwrap_or(0);
+            (w as i32 - TILE_SIZE as i32, h as i32 - TILE_SIZE as i32)
+        }
+    };
+    Ok(align_frames(&frames, offset))
+}
+
+// Export one monster, or every monster in the sprites folder when monster is None. A .gif
+// path gives an animation, anything else a sprite sheet with one row per monster. All the
+// monsters animate side by side in the combined gif, shorter animations loop early.
+pub fn export_sprites(
+    assets: &AssetLocator,
+    monster: Option<&str>,
+    out: &Path,
+    options: ExportOptions,
+) -> Result<()> {
+    let monsters: Vec<String> = match monster {
+        Some(monster) => vec![monster.to_string()],
+        None => {
+            let mut monsters: Vec<String> = fs::read_dir(assets.sprites(""))?
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().is_dir())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect();
+            monsters.sort();
+            monsters
+        }
+    };
+
+    // Offsets come from the offsets command
+    let offsets = SpriteOffsets::load(&SpriteOffsets::path())?;
+    let mut animations = Vec::new();
+    for monster in &monsters {
+        match monster_frames(assets, &offsets, monster) {
+            Ok(frames) => animations.push(frames),
+            // Fonts and the like live alongside the monsters
+            Err(e) if monsters.len() > 1 => println!("Skipping {monster}: {e}"),
+            Err(e) => return Err(e),
+        }
+    }
+
+    // Every monster gets a cell as big as the biggest one
+    let cell_w = animations.iter().flatten().map(|f| f.width()).max();
+    let cell_h = animations.iter().flatten().map(|f| f.height()).max();
+    let (Some(cell_w), Some(cell_h)) = (cell_w, cell_h) else {
+        return Err(anyhow!("Nothing to export"));
+    };
+    for frames in &mut animations {
+        for frame in frames.iter_mut() {
+            let mut cell = RgbaImage::new(cell_w, cell_h);
+            let (x, y) = (cell_w - frame.width(), cell_h - frame.height());
+            imageops::replace(&mut cell, frame, x as i64 / 2, y as i64 / 2);
+            *frame = cell;
+        }
+    }
+
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if out.extension().is_some_and(|ext| ext == "gif") {
+        let columns = animations.len().min(MONSTERS_PER_ROW);
+        let length = animations.iter().map(Vec::len).max().unwrap_or(0);
+        let frames: Vec<RgbaImage> = (0..length)
+            .map(|i| {
+                let cells: Vec<RgbaImage> = animations
+                    .iter()
+                    .map(|frames| frames[i % frames.len()].clone())
+                    .collect();
+                tile_images(&cells, columns)
+            })
+            .collect();
+        write_gif(out, &frames, options)?;
+    } else {
+        // One row per monster, padded out to the longest animation
+        let length = animations.iter().map(Vec::len).max().unwrap_or(0);
+        let cells: Vec<RgbaImage> = animations
+            .iter()
+            .flat_map(|frames| {
+                (0..length).map(|i| {
+                    frames
+                        .get(i)
+                        .cloned()
+                        .unwrap_or_else(|| RgbaImage::new(cell_w, cell_h))
+                })
+            })
+            .collect();
+        scale(&tile_images(&cells, length), options.scale).save(out)?;
+    }
+
+    println!("Wrote {} monster(s) to {}", animations.len(), out.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{codecs::gif::GifDecoder, AnimationDecoder, Rgba};
+
+    fn solid(w: u32, h: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(w, h, Rgba([value, value, value, 255]))
+    }
+
+    #[test]
+    fn align_to_tile() {
+        // A 38x48 sprite drawn 3 pixels left and 17 up from its tile, plus a smaller frame
+        let frames = [solid(38, 48, 1), solid(30, 40, 2)];
+        let aligned = align_frames(&frames, (3, 17));
+        assert!(aligned.iter().all(|f| f.dimensions() == (38, 49)));
+
+        // Largest frame fills the canvas apart from the row below it that the tile covers
+        assert_eq!(aligned[0].get_pixel(0, 0), &Rgba([1, 1, 1, 255]));
+        assert_eq!(aligned[0].get_pixel(37, 47), &Rgba([1, 1, 1, 255]));
+        assert_eq!(aligned[0].get_pixel(0, 48)[3], 0);
+
+        // The smaller one shares its bottom right corner
+        assert_eq!(aligned[1].get_pixel(8, 8), &Rgba([2, 2, 2, 255]));
+        assert_eq!(aligned[1].get_pixel(37, 47), &Rgba([2, 2, 2, 255]));
+        assert_eq!(aligned[1].get_pixel(7, 8)[3], 0);
+    }
+
+    #[test]
+    fn align_small_sprite() {
+        // A negative offset pushes the sprite into the tile, the canvas stays tile sized
+        let aligned = align_frames(&[solid(20, 10, 1)], (-2, -5));
+        assert_eq!(aligned[0].dimensions(), (TILE_SIZE, TILE_SIZE));
+        assert_eq!(aligned[0].get_pixel(2, 5), &Rgba([1, 1, 1, 255]));
+        assert_eq!(aligned[0].get_pixel(1, 5)[3], 0);
+    }
+
+    #[test]
+    fn tile_grid() {
+        let images: Vec<RgbaImage> = (0..5).map(|i| solid(4, 3, i)).collect();
+        let sheet = tile_images(&images, 2);
+        assert_eq!(sheet.dimensions(), (8, 9));
+        assert_eq!(sheet.get_pixel(4, 3), &Rgba([3, 3, 3, 255]));
+        assert_eq!(sheet.get_pixel(0, 6), &Rgba([4, 4, 4, 255]));
+        assert_eq!(sheet.get_pixel(4, 6)[3], 0);
+    }
+
+    #[test]
+    fn gif_frames() {
+        let path = std::env::temp_dir().join(format!("sprites_{}.gif", std::process::id()));
+        let frames = [solid(4, 4, 0), solid(4, 4, 255)];
+        let options = ExportOptions {
+            scale: 2,
+            delay_ms: 100,
+        };
+        write_gif(&path, &frames, options).unwrap();
+
+        let file = std::io::BufReader::new(File::open(&path).unwrap());
+        let decoded = GifDecoder::new(file)
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].buffer().dimensions(), (8, 8));
+        assert_eq!(
+            decoded[1].buffer().get_pixel(7, 7),
+            &Rgba([255, 255, 255, 255])
+        );
+        assert_eq!(decoded[1].delay().numer_denom_ms(), (100, 1));
+    }
+}
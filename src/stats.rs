@@ -0,0 +1,455 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{Display, Write},
+    fs,
+    path::Path,
+};
+
+use anyhow::Result;
+use image::{Rgba, RgbaImage};
+
+use crate::puzzle::{bit, Puzzle, Tile};
+
+// Seeds are shown with at most 8 digits. Coverage is counted in this many equal slices of that.
+pub const SEED_SPACE: u32 = 100_000_000;
+const SEED_BUCKETS: u32 = 10;
+
+// Pixels per cell in the PNG heatmaps, with a line of background between cells
+const HEATMAP_CELL: u32 = 24;
+const HEATMAP_GAP: u32 = 1;
+
+// Widest bar in the text histograms
+const BAR_WIDTH: usize = 40;
+
+// Groups of boards that are rotations or reflections of each other listed in the report
+const SYMMETRY_GROUPS_SHOWN: usize = 10;
+
+// Frequency of something on each cell of the board, out of `total` boards
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Heatmap {
+    pub counts: [[usize; 8]; 8],
+    pub total: usize,
+}
+
+impl Heatmap {
+    fn add(&mut self, hits: impl Fn(usize, usize) -> bool) {
+        for (y, row) in self.counts.iter_mut().enumerate() {
+            for (x, count) in row.iter_mut().enumerate() {
+                *count += hits(x, y) as usize;
+            }
+        }
+        self.total += 1;
+    }
+
+    pub fn frequency(&self, x: usize, y: usize) -> f32 {
+        match self.total {
+            0 => 0.0,
+            total => self.counts[y][x] as f32 / total as f32,
+        }
+    }
+
+    // One cell per tile, from black for never up through red and yellow to white for the
+    // busiest cell. Scaled to the busiest cell so the generator's bias shows even when every
+    // cell is rare.
+    pub fn to_image(&self) -> RgbaImage {
+        let size = 8 * (HEATMAP_CELL + HEATMAP_GAP) + HEATMAP_GAP;
+        let mut img = RgbaImage::from_pixel(size, size, Rgba([40, 40, 40, 255]));
+        let max = self.counts.iter().flatten().copied().max().unwrap_or(0);
+        for (y, row) in self.counts.iter().enumerate() {
+            for (x, &count) in row.iter().enumerate() {
+                let heat = match max {
+                    0 => 0.0,
+                    max => count as f32 / max as f32,
+                };
+                let x0 = HEATMAP_GAP + x as u32 * (HEATMAP_CELL + HEATMAP_GAP);
+                let y0 = HEATMAP_GAP + y as u32 * (HEATMAP_CELL + HEATMAP_GAP);
+                for j in y0..y0 + HEATMAP_CELL {
+                    for i in x0..x0 + HEATMAP_CELL {
+                        img.put_pixel(i, j, heat_color(heat));
+                    }
+                }
+            }
+        }
+        img
+    }
+}
+
+// Percentages, one row of the board per line
+impl Display for Heatmap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "   ")?;
+        for x in 0..8 {
+            write!(f, "{x:>6} ")?;
+        }
+        for y in 0..8 {
+            write!(f, "\n{y:>3}")?;
+            for x in 0..8 {
+                write!(f, "{:>6.1}%", self.frequency(x, y) * 100.0)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn heat_color(heat: f32) -> Rgba<u8> {
+    let channel = |start: f32| ((heat * 3.0 - start).clamp(0.0, 1.0) * 255.0) as u8;
+    Rgba([channel(0.0), channel(1.0), channel(2.0), 255])
+}
+
+// How often each value came up, indexed by value
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Histogram(pub Vec<usize>);
+
+impl Histogram {
+    fn add(&mut self, value: usize) {
+        if self.0.len() <= value {
+            self.0.resize(value + 1, 0);
+        }
+        self.0[value] += 1;
+    }
+
+    pub fn total(&self) -> usize {
+        self.0.iter().sum()
+    }
+
+    pub fn mean(&self) -> f32 {
+        let sum: usize = self.0.iter().enumerate().map(|(v, n)| v * n).sum();
+        match self.total() {
+            0 => 0.0,
+            total => sum as f32 / total as f32,
+        }
+    }
+}
+
+// A bar per value from the smallest to the largest seen, scaled to the most common
+impl Display for Histogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(first) = self.0.iter().position(|&n| n > 0) else {
+            return write!(f, "  (empty)");
+        };
+        let last = self.0.iter().rposition(|&n| n > 0).unwrap_or(first);
+        let max = self.0.iter().copied().max().unwrap_or(1);
+        let total = self.total();
+        let mut lines = String::new();
+        for (value, &n) in self.0.iter().enumerate().take(last + 1).skip(first) {
+            let bar = "#".repeat((n * BAR_WIDTH).div_ceil(max));
+            writeln!(
+                lines,
+                "  {value:>3} | {bar:<BAR_WIDTH$} {n} ({:.1}%)",
+                n as f32 * 100.0 / total as f32
+            )?;
+        }
+        write!(f, "{}", lines.trim_end())
+    }
+}
+
+// Everything `db stats` reports about a set of stored puzzles
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DbStats {
+    pub puzzles: usize,
+    pub seeds: HashSet<u32>,
+    pub unseeded: usize,
+    // Per board
+    pub monsters: Histogram,
+    pub treasure: Histogram,
+    pub walls: Histogram,
+    // Per count, over every row or column
+    pub row_counts: Histogram,
+    pub column_counts: Histogram,
+    pub monster_cells: Heatmap,
+    pub treasure_cells: Heatmap,
+    // Only for boards with a known solution
+    pub wall_cells: Heatmap,
+    // Distinct boards by canonical hash then their own hash, with the seed of one of them
+    pub symmetry_groups: HashMap<u64, BTreeMap<u64, Option<u32>>>,
+}
+
+impl DbStats {
+    pub fn new<'a>(puzzles: impl IntoIterator<Item = &'a Puzzle>) -> Self {
+        let mut stats = Self::default();
+        for puzzle in puzzles {
+            stats.add(puzzle, None);
+        }
+        stats
+    }
+
+    // Count a puzzle, and where its walls go if it's been solved
+    pub fn add(&mut self, puzzle: &Puzzle, solution: Option<u64>) {
+        self.puzzles += 1;
+        match puzzle.seed {
+            Some(seed) => {
+                self.seeds.insert(seed);
+            }
+            None => self.unseeded += 1,
+        }
+
+        let count = |tile| {
+            puzzle
+                .tiles
+                .iter()
+                .flatten()
+                .filter(|&&t| t == tile)
+                .count()
+        };
+        self.monsters.add(count(Tile::Monster));
+        self.treasure.add(count(Tile::Treasure));
+        // The top and left counts both add up to every wall on the board
+        self.walls
+            .add(puzzle.top_nums.iter().map(|&n| n as usize).sum());
+        for &n in &puzzle.left_nums {
+            self.row_counts.add(n as usize);
+        }
+        for &n in &puzzle.top_nums {
+            self.column_counts.add(n as usize);
+        }
+
+        self.monster_cells
+            .add(|x, y| puzzle.tiles[y][x] == Tile::Monster);
+        self.treasure_cells
+            .add(|x, y| puzzle.tiles[y][x] == Tile::Treasure);
+        if let Some(solution) = solution {
+            self.wall_cells.add(|x, y| solution & bit(x, y) != 0);
+        }
+
+        self.symmetry_groups
+            .entry(puzzle.canonical_hash())
+            .or_default()
+            .entry(puzzle.board_hash())
+            .or_insert(puzzle.seed);
+    }
+
+    pub fn distinct_boards(&self) -> usize {
+        self.symmetry_groups.values().map(BTreeMap::len).sum()
+    }
+
+    // Seeds of different boards that are rotations or reflections of each other, largest groups
+    // first
+    pub fn symmetric_duplicates(&self) -> Vec<Vec<Option<u32>>> {
+        let mut groups: Vec<Vec<Option<u32>>> = self
+            .symmetry_groups
+            .values()
+            .filter(|boards| boards.len() > 1)
+            .map(|boards| {
+                let mut seeds: Vec<Option<u32>> = boards.values().copied().collect();
+                seeds.sort();
+                seeds
+            })
+            .collect();
+        groups.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        groups
+    }
+
+    // Unique seeds in each slice of the seed space
+    pub fn seed_coverage(&self) -> Vec<usize> {
+        let bucket_size = SEED_SPACE / SEED_BUCKETS;
+        let mut buckets = vec![0; SEED_BUCKETS as usize];
+        for &seed in &self.seeds {
+            buckets[(seed / bucket_size).min(SEED_BUCKETS - 1) as usize] += 1;
+        }
+        buckets
+    }
+
+    fn heatmaps(&self) -> Vec<(&'static str, &Heatmap)> {
+        let mut maps = vec![
+            ("monsters", &self.monster_cells),
+            ("treasure", &self.treasure_cells),
+        ];
+        if self.wall_cells.total > 0 {
+            maps.push(("walls", &self.wall_cells));
+        }
+        maps
+    }
+
+    // Write a PNG per heatmap into `dir`, returning the files written
+    pub fn save_heatmaps(&self, dir: &Path) -> Result<Vec<String>> {
+        fs::create_dir_all(dir)?;
+        let mut written = Vec::new();
+        for (name, map) in self.heatmaps() {
+            let path = dir.join(format!("{name}.png"));
+            map.to_image().save(&path)?;
+            written.push(path.display().to_string());
+        }
+        Ok(written)
+    }
+}
+
+impl Display for DbStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Puzzle database")?;
+        writeln!(f, "  count: {}", self.puzzles)?;
+        writeln!(f, "  unique: {}", self.seeds.len())?;
+        writeln!(f, "  without a seed: {}", self.unseeded)?;
+
+        for (name, histogram) in [
+            ("Monsters per board", &self.monsters),
+            ("Treasure per board", &self.treasure),
+            ("Walls per board", &self.walls),
+            ("Row wall counts", &self.row_counts),
+            ("Column wall counts", &self.column_counts),
+        ] {
+            writeln!(f, "\n{name} (mean {:.2})", histogram.mean())?;
+            writeln!(f, "{histogram}")?;
+        }
+
+        writeln!(f, "\nSeed coverage")?;
+        if let (Some(min), Some(max)) = (self.seeds.iter().min(), self.seeds.iter().max()) {
+            writeln!(f, "  range: {min} - {max}")?;
+        }
+        let bucket_size = SEED_SPACE / SEED_BUCKETS;
+        for (i, &n) in self.seed_coverage().iter().enumerate() {
+            let start = i as u32 * bucket_size;
+            writeln!(
+                f,
+                "  {start:>8} - {:>8}: {n} ({:.4}% of the slice)",
+                start + bucket_size - 1,
+                n as f32 * 100.0 / bucket_size as f32
+            )?;
+        }
+
+        for (name, map) in self.heatmaps() {
+            writeln!(f, "\nCells with {name}")?;
+            writeln!(f, "{map}")?;
+        }
+
+        writeln!(f, "\nSymmetry")?;
+        writeln!(f, "  distinct boards: {}", self.distinct_boards())?;
+        writeln!(
+            f,
+            "  up to rotation and reflection: {}",
+            self.symmetry_groups.len()
+        )?;
+        let groups = self.symmetric_duplicates();
+        for seeds in groups.iter().take(SYMMETRY_GROUPS_SHOWN) {
+            let seeds: Vec<String> = seeds
+                .iter()
+                .map(|seed| seed.map_or("no seed".into(), |seed| seed.to_string()))
+                .collect();
+            writeln!(f, "  same board: {}", seeds.join(", "))?;
+        }
+        if groups.len() > SYMMETRY_GROUPS_SHOWN {
+            writeln!(f, "  and {} more", groups.len() - SYMMETRY_GROUPS_SHOWN)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::symmetry::Transform;
+
+    fn puzzle(
+        seed: Option<u32>,
+        monsters: &[(usize, usize)],
+        treasure: &[(usize, usize)],
+    ) -> Puzzle {
+        let mut puzzle = Puzzle {
+            top_nums: [1, 2, 0, 0, 0, 0, 0, 3],
+            left_nums: [0, 0, 0, 6, 0, 0, 0, 0],
+            seed,
+            ..Default::default()
+        };
+        for &(x, y) in monsters {
+            puzzle.tiles[y][x] = Tile::Monster;
+        }
+        for &(x, y) in treasure {
+            puzzle.tiles[y][x] = Tile::Treasure;
+        }
+        puzzle
+    }
+
+    fn stats() -> DbStats {
+        let puzzles = [
+            puzzle(Some(5), &[(0, 0), (7, 7)], &[(3, 3)]),
+            puzzle(Some(5), &[(0, 0)], &[]),
+            puzzle(None, &[(0, 0), (1, 0), (2, 0)], &[(3, 3)]),
+            puzzle(Some(99_999_999), &[], &[]),
+        ];
+        DbStats::new(&puzzles)
+    }
+
+    #[test]
+    fn counts() {
+        let stats = stats();
+        assert_eq!(stats.puzzles, 4);
+        assert_eq!(stats.seeds.len(), 2);
+        assert_eq!(stats.unseeded, 1);
+        assert_eq!(stats.monsters, Histogram(vec![1, 1, 1, 1]));
+        assert_eq!(stats.treasure, Histogram(vec![2, 2]));
+        assert_eq!(stats.walls.0[6], 4);
+        assert_eq!(stats.row_counts.0[0], 28);
+        assert_eq!(stats.row_counts.0[6], 4);
+        assert_eq!(stats.column_counts.0, vec![20, 4, 4, 4]);
+        assert_eq!(stats.seed_coverage(), [1, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    }
+
+    #[test_case(0, 0, 0.75)]
+    #[test_case(7, 7, 0.25)]
+    #[test_case(4, 4, 0.0)]
+    fn monster_frequency(x: usize, y: usize, frequency: f32) {
+        assert_eq!(stats().monster_cells.frequency(x, y), frequency);
+    }
+
+    #[test]
+    fn walls_only_from_solutions() {
+        let mut stats = stats();
+        assert_eq!(stats.heatmaps().len(), 2);
+
+        stats.add(&puzzle(None, &[], &[]), Some(1 << 63 | 1));
+        assert_eq!(stats.wall_cells.total, 1);
+        assert_eq!(stats.wall_cells.frequency(0, 0), 1.0);
+        assert_eq!(stats.wall_cells.frequency(7, 7), 1.0);
+        assert_eq!(stats.wall_cells.frequency(1, 0), 0.0);
+        assert_eq!(stats.heatmaps().len(), 3);
+    }
+
+    #[test]
+    fn report() {
+        let report = stats().to_string();
+        assert!(report.contains("  unique: 2\n"), "{report}");
+        assert!(
+            report.contains("Monsters per board (mean 1.50)"),
+            "{report}"
+        );
+        assert!(report.contains(&format!("    3 | {:<BAR_WIDTH$} 1 (25.0%)", "#".repeat(40))));
+        assert!(report.contains("  0  75.0%  25.0%  25.0%"), "{report}");
+        assert!(!report.contains("Cells with walls"));
+    }
+
+    #[test]
+    fn symmetric_duplicates() {
+        let mut stats = stats();
+        assert_eq!(stats.distinct_boards(), 4);
+        assert!(stats.symmetric_duplicates().is_empty());
+
+        let board = puzzle(Some(8), &[(0, 1), (2, 5)], &[(6, 6)]);
+        stats.add(&board, None);
+        stats.add(&board.transformed(Transform::Rotate90), None);
+        let mut flipped = board.transformed(Transform::FlipY);
+        flipped.seed = Some(1);
+        stats.add(&flipped, None);
+        assert_eq!(stats.distinct_boards(), 7);
+        assert_eq!(stats.symmetry_groups.len(), 5);
+        assert_eq!(stats.symmetric_duplicates(), [[Some(1), Some(8), Some(8)]]);
+
+        let report = stats.to_string();
+        assert!(report.contains("  same board: 1, 8, 8\n"), "{report}");
+    }
+
+    #[test]
+    fn heatmap_image() {
+        let img = stats().monster_cells.to_image();
+        let cell = |x: u32, y: u32| {
+            *img.get_pixel(
+                HEATMAP_GAP + x * (HEATMAP_CELL + HEATMAP_GAP) + 1,
+                HEATMAP_GAP + y * (HEATMAP_CELL + HEATMAP_GAP) + 1,
+            )
+        };
+        assert_eq!(cell(0, 0), Rgba([255, 255, 255, 255]));
+        assert_eq!(cell(4, 4), Rgba([0, 0, 0, 255]));
+        // A third of the busiest cell is fully red and nothing else
+        assert_eq!(cell(7, 7), Rgba([255, 0, 0, 255]));
+    }
+}
@@ -0,0 +1,321 @@
+use crate::puzzle::{bit, Puzzle, Tile};
+
+// The 8 ways to rotate or reflect a board onto itself. A board and its transforms have the
+// same solutions, transformed the same way.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Transform {
+    Identity,
+    // Clockwise
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    // Left to right
+    FlipX,
+    // Top to bottom
+    FlipY,
+    // Across the diagonal from the top left
+    Transpose,
+    // Across the diagonal from the top right
+    AntiTranspose,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Transform::Identity,
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::FlipX,
+        Transform::FlipY,
+        Transform::Transpose,
+        Transform::AntiTranspose,
+    ];
+
+    // Where the cell at (x, y) ends up
+    pub fn map(self, x: usize, y: usize) -> (usize, usize) {
+        match self {
+            Transform::Identity => (x, y),
+            Transform::Rotate90 => (7 - y, x),
+            Transform::Rotate180 => (7 - x, 7 - y),
+            Transform::Rotate270 => (y, 7 - x),
+            Transform::FlipX => (7 - x, y),
+            Transform::FlipY => (x, 7 - y),
+            Transform::Transpose => (y, x),
+            Transform::AntiTranspose => (7 - y, 7 - x),
+        }
+    }
+
+    // Rows become columns and columns rows
+    pub fn swaps_axes(self) -> bool {
+        matches!(
+            self,
+            Transform::Rotate90
+                | Transform::Rotate270
+                | Transform::Transpose
+                | Transform::AntiTranspose
+        )
+    }
+
+    pub fn inverse(self) -> Transform {
+        match self {
+            Transform::Rotate90 => Transform::Rotate270,
+            Transform::Rotate270 => Transform::Rotate90,
+            other => other,
+        }
+    }
+
+    // A solution mask, bit 63 - (y * 8 + x) for the wall at (x, y)
+    pub fn apply_mask(self, mask: u64) -> u64 {
+        let mut out = 0;
+        for y in 0..8 {
+            for x in 0..8 {
+                if mask & bit(x, y) != 0 {
+                    let (tx, ty) = self.map(x, y);
+                    out |= bit(tx, ty);
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Puzzle {
+    // The same board rotated or reflected, with the wall counts moved to match. The seed is
+    // kept, it still says where the board came from.
+    pub fn transformed(&self, transform: Transform) -> Puzzle {
+        let mut out = Puzzle {
+            seed: self.seed,
+            ..Default::default()
+        };
+        for y in 0..8 {
+            for x in 0..8 {
+                let (tx, ty) = transform.map(x, y);
+                out.tiles[ty][tx] = self.tiles[y][x];
+            }
+        }
+        // Column i lands on column tx (or row ty when the axes swap) and row i on row ty (or
+        // column tx). Mapping the diagonal cell (i, i) gives both.
+        for i in 0..8 {
+            let (tx, ty) = transform.map(i, i);
+            if transform.swaps_axes() {
+                out.left_nums[ty] = self.top_nums[i];
+                out.top_nums[tx] = self.left_nums[i];
+            } else {
+                out.top_nums[tx] = self.top_nums[i];
+                out.left_nums[ty] = self.left_nums[i];
+            }
+        }
+        out
+    }
+
+    // Tiles then top and left counts, a byte each. Boards compare by this, the seed isn't part
+    // of it.
+    fn board_key(&self) -> [u8; 80] {
+        let mut key = [0; 80];
+        for (k, tile) in key.iter_mut().zip(self.tiles.iter().flatten()) {
+            *k = match tile {
+                Tile::Empty => 0,
+                Tile::Wall => 1,
+                Tile::Treasure => 2,
+                Tile::Monster => 3,
+            };
+        }
+        key[64..72].copy_from_slice(&self.top_nums);
+        key[72..].copy_from_slice(&self.left_nums);
+        key
+    }
+
+    // FNV-1a over the board, so it's the same on every run and build, unlike std's hasher.
+    // Ignores the seed.
+    pub fn board_hash(&self) -> u64 {
+        self.board_key()
+            .iter()
+            .fold(0xcbf29ce484222325, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    // The one of the 8 equivalent boards that sorts first, and the transform that gets there
+    // from this one
+    pub fn canonical(&self) -> (Puzzle, Transform) {
+        Transform::ALL
+            .into_iter()
+            .map(|transform| (self.transformed(transform), transform))
+            .min_by_key(|(puzzle, _)| puzzle.board_key())
+            .unwrap()
+    }
+
+    // Equal for boards that are rotations or reflections of each other
+    pub fn canonical_hash(&self) -> u64 {
+        self.canonical().0.board_hash()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::puzzle::{corridor, with_walls};
+    use crate::solve::{is_valid_solution, BackTracker, Solver};
+
+    fn random_puzzle(rng: &mut StdRng) -> Puzzle {
+        let mut puzzle = Puzzle {
+            top_nums: [0; 8].map(|_| rng.gen_range(0..=8)),
+            left_nums: [0; 8].map(|_| rng.gen_range(0..=8)),
+            seed: Some(rng.gen()),
+            ..Default::default()
+        };
+        for tile in puzzle.tiles.iter_mut().flatten() {
+            *tile = match rng.gen_range(0..10) {
+                0 => Tile::Monster,
+                1 => Tile::Treasure,
+                2..=5 => Tile::Wall,
+                _ => Tile::Empty,
+            };
+        }
+        puzzle
+    }
+
+    #[test]
+    fn group() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let puzzle = random_puzzle(&mut rng);
+        for transform in Transform::ALL {
+            let there = puzzle.transformed(transform);
+            assert_eq!(
+                there.transformed(transform.inverse()),
+                puzzle,
+                "{transform:?}"
+            );
+        }
+        let twice = |t: Transform| puzzle.transformed(t).transformed(t);
+        assert_eq!(
+            twice(Transform::Rotate90),
+            puzzle.transformed(Transform::Rotate180)
+        );
+        assert_eq!(twice(Transform::FlipX), puzzle);
+        assert_eq!(
+            puzzle
+                .transformed(Transform::FlipX)
+                .transformed(Transform::Transpose),
+            puzzle.transformed(Transform::Rotate270)
+        );
+    }
+
+    #[test_case(Transform::Rotate90, [8, 7, 6, 5, 4, 3, 2, 1], [0, 1, 2, 3, 4, 5, 6, 7])]
+    #[test_case(Transform::Rotate180, [7, 6, 5, 4, 3, 2, 1, 0], [8, 7, 6, 5, 4, 3, 2, 1])]
+    #[test_case(Transform::FlipX, [7, 6, 5, 4, 3, 2, 1, 0], [1, 2, 3, 4, 5, 6, 7, 8])]
+    #[test_case(Transform::Transpose, [1, 2, 3, 4, 5, 6, 7, 8], [0, 1, 2, 3, 4, 5, 6, 7])]
+    fn counts_follow_the_board(transform: Transform, top: [u8; 8], left: [u8; 8]) {
+        let puzzle = Puzzle {
+            top_nums: [0, 1, 2, 3, 4, 5, 6, 7],
+            left_nums: [1, 2, 3, 4, 5, 6, 7, 8],
+            ..Default::default()
+        };
+        let out = puzzle.transformed(transform);
+        assert_eq!((out.top_nums, out.left_nums), (top, left));
+    }
+
+    #[test]
+    fn counts_match_walls() {
+        // Counts worked out from the walls after transforming agree with the moved counts
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..20 {
+            let mut puzzle = random_puzzle(&mut rng);
+            let walls = |p: &Puzzle, x: usize, y: usize| (p.tiles[y][x] == Tile::Wall) as u8;
+            for i in 0..8 {
+                puzzle.top_nums[i] = (0..8).map(|y| walls(&puzzle, i, y)).sum();
+                puzzle.left_nums[i] = (0..8).map(|x| walls(&puzzle, x, i)).sum();
+            }
+            for transform in Transform::ALL {
+                let out = puzzle.transformed(transform);
+                for i in 0..8 {
+                    assert_eq!(
+                        out.top_nums[i],
+                        (0..8).map(|y| walls(&out, i, y)).sum::<u8>()
+                    );
+                    assert_eq!(
+                        out.left_nums[i],
+                        (0..8).map(|x| walls(&out, x, i)).sum::<u8>()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn masks_match_tiles() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let puzzle = Puzzle::default();
+        for _ in 0..20 {
+            let mask: u64 = rng.gen();
+            for transform in Transform::ALL {
+                assert_eq!(
+                    with_walls(&puzzle, mask).transformed(transform),
+                    with_walls(&puzzle, transform.apply_mask(mask))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn canonical_form() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let puzzle = random_puzzle(&mut rng);
+        let (canonical, transform) = puzzle.canonical();
+        assert_eq!(puzzle.transformed(transform), canonical);
+        for t in Transform::ALL {
+            let other = puzzle.transformed(t);
+            assert_eq!(other.canonical().0, canonical);
+            assert_eq!(other.canonical_hash(), puzzle.canonical_hash());
+        }
+        assert_ne!(
+            random_puzzle(&mut rng).canonical_hash(),
+            puzzle.canonical_hash()
+        );
+    }
+
+    #[test]
+    fn stable_hash() {
+        // Saved overlays and reports are named by this, it mustn't change between builds
+        assert_eq!(Puzzle::default().board_hash(), 0xf14b_84b8_290b_8965);
+        let seeded = Puzzle {
+            seed: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(seeded.board_hash(), Puzzle::default().board_hash());
+    }
+
+    #[test]
+    fn validity_is_invariant() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let (corridor, solution) = corridor();
+        let mut boards = vec![with_walls(&corridor, solution)];
+        boards.extend((0..200).map(|_| random_puzzle(&mut rng)));
+        for board in boards {
+            let valid = is_valid_solution(&board, false);
+            for transform in Transform::ALL {
+                assert_eq!(
+                    is_valid_solution(&board.transformed(transform), false),
+                    valid,
+                    "{transform:?}\n{board}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn solver_is_invariant() {
+        let (puzzle, solution) = corridor();
+        assert_eq!(BackTracker.solve(&puzzle), [solution]);
+        for transform in Transform::ALL {
+            assert_eq!(
+                BackTracker.solve(&puzzle.transformed(transform)),
+                [transform.apply_mask(solution)],
+                "{transform:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use image::{GrayImage, RgbaImage};
+use std::thread;
+
+// Every COARSE_STEP-th needle pixel is used for the first pass over the haystack
+const COARSE_STEP: usize = 8;
+
+// How often (in pixels) the running error is checked against the best score so far
+const EARLY_EXIT_INTERVAL: usize = 32;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Match {
+    // Top left corner of the needle in the haystack
+    pub x: u32,
+    pub y: u32,
+    // Sum of squared RGB error over the needle's unmasked pixels. 0 is a perfect match.
+    pub score: u64,
+}
+
+// The needle pixels that take part in matching, as byte offsets into the needle and into the
+// haystack relative to where the needle's corner sits
+struct Sample {
+    needle: usize,
+    haystack: usize,
+}
+
+// A mask for ignoring the pixels of a sprite that aren't fully opaque
+pub fn alpha_mask(needle: &RgbaImage) -> GrayImage {
+    GrayImage::from_fn(needle.width(), needle.height(), |x, y| {
+        [if needle.get_pixel(x, y)[3] == 255 {
+            255
+        } else {
+            0
+        }]
+        .into()
+    })
+}
+
+// Find where needle best fits in haystack. Pixels where the mask is 0 are ignored, as is the
+// alpha channel. Every position sharing the lowest score is returned, in row order, so a
+// caller looking for something unique can check there's only one. Empty if the needle doesn't
+// fit or is entirely masked.
+//
+// Positions are first ranked by the error over a sparse subset of pixels. That's a lower bound
+// on the full error, so positions can be fully scored best first and the search stops as soon
+// as no remaining position could beat (or tie) the best.
+pub fn match_template(
+    haystack: &RgbaImage,
+    needle: &RgbaImage,
+    mask: Option<&GrayImage>,
+) -> Vec<Match> {
+    let (hw, hh) = haystack.dimensions();
+    let (nw, nh) = needle.dimensions();
+    if nw == 0 || nh == 0 || nw > hw || nh > hh {
+        return Vec::new();
+    }
+
+    let mut samples = Vec::new();
+    for y in 0..nh {
+        for x in 0..nw {
+            if mask.is_none_or(|m| m.get_pixel(x, y)[0] != 0) {
+                samples.push(Sample {
+                    needle: ((y * nw + x) * 4) as usize,
+                    haystack: ((y * hw + x) * 4) as usize,
+                });
+            }
+        }
+    }
+    if samples.is_empty() {
+        return Vec::new();
+    }
+
+    // Split off a sparse set of pixels for the coarse pass. The fine pass adds the rest on.
+    let (coarse, fine): (Vec<_>, Vec<_>) = samples
+        .into_iter()
+        .enumerate()
+        .partition(|(i, _)| i % COARSE_STEP == 0);
+    let coarse: Vec<Sample> = coarse.into_iter().map(|(_, s)| s).collect();
+    let fine: Vec<Sample> = fine.into_iter().map(|(_, s)| s).collect();
+
+    let (hay, pattern) = (haystack.as_raw(), needle.as_raw());
+    let base = |x: u32, y: u32| ((y * hw + x) * 4) as usize;
+
+    // Coarse pass over every position
+    let mut candidates: Vec<(u64, u32, u32)> = (0..=hh - nh)
+        .flat_map(|y| (0..=hw - nw).map(move |x| (x, y)))
+        .map(|(x, y)| (error(hay, pattern, base(x, y), &coarse, u64::MAX), x, y))
+        .collect();
+    candidates.sort_unstable();
+
+    // Fine pass, best lower bound first
+    let mut best = u64::MAX;
+    let mut matches = Vec::new();
+    for (lower_bound, x, y) in candidates {
+        if lower_bound > best {
+            break;
+        }
+        let score = lower_bound + error(hay, pattern, base(x, y), &fine, best - lower_bound);
+        if score < best {
+            best = score;
+            matches.clear();
+        }
+        if score == best {
+            matches.push(Match { x, y, score });
+        }
+    }
+
+    matches.sort_unstable_by_key(|m| (m.y, m.x));
+    matches
+}
+
+// Match several needles (e.g. the frames of an animation) against the same haystack, spread
+// over the available cores. Results are in the same order as the needles.
+pub fn match_frames(
+    haystack: &RgbaImage,
+    needles: &[RgbaImage],
+    masks: Option<&[GrayImage]>,
+) -> Vec<Vec<Match>> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let per_thread = needles.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = needles
+            .chunks(per_thread)
+            .enumerate()
+            .map(|(chunk, needles)| {
+                scope.spawn(move || {
+                    needles
+                        .iter()
+                        .enumerate()
+                        .map(|(i, needle)| {
+                            let mask = masks.map(|m| &m[chunk * per_thread + i]);
+                            match_template(haystack, needle, mask)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("template matching thread panicked"))
+            .collect()
+    })
+}
+
+// Sum of squared RGB error over the given samples, giving up once it passes limit
+fn error(haystack: &[u8], needle: &[u8], base: usize, samples: &[Sample], limit: u64) -> u64 {
+    let mut sum = 0u64;
+    for chunk in samples.chunks(EARLY_EXIT_INTERVAL) {
+        for sample in chunk {
+            let h = &haystack[base + sample.haystack..base + sample.haystack + 3];
+            let n = &needle[sample.needle..sample.needle + 3];
+            for (a, b) in h.iter().zip(n) {
+                let e = a.abs_diff(*b) as u64;
+                sum += e * e;
+            }
+        }
+        if sum > limit {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{imageops, GenericImageView, Rgba};
+
+    fn reference_board() -> RgbaImage {
+        image::open("monster_refs/goblin.png").unwrap().to_rgba8()
+    }
+
+    // The straightforward version, for checking against
+    fn brute_force(
+        haystack: &RgbaImage,
+        needle: &RgbaImage,
+        mask: &GrayImage,
+    ) -> (u64, Vec<(u32, u32)>) {
+        let mut best = (u64::MAX, Vec::new());
+        for y in 0..=haystack.height() - needle.height() {
+            for x in 0..=haystack.width() - needle.width() {
+                let mut score = 0;
+                for (i, j, p) in needle.enumerate_pixels() {
+                    if mask.get_pixel(i, j)[0] != 0 {
+                        let h = haystack.get_pixel(x + i, y + j);
+                        score += (0..3)
+                            .map(|c| (h[c].abs_diff(p[c]) as u64).pow(2))
+                            .sum::<u64>();
+                    }
+                }
+                if score < best.0 {
+                    best = (score, Vec::new());
+                }
+                if score == best.0 {
+                    best.1.push((x, y));
+                }
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn exact_match() {
+        let board = reference_board();
+        let needle = board.view(120, 200, 20, 16).to_image();
+        let matches = match_template(&board, &needle, None);
+        assert!(matches.contains(&Match {
+            x: 120,
+            y: 200,
+            score: 0
+        }));
+    }
+
+    #[test]
+    fn masked_match_agrees_with_brute_force() {
+        let board = reference_board();
+        let haystack = board.view(100, 180, 60, 50).to_image();
+
+        // A smudged copy of part of the board, with a hole in the middle that's masked out
+        let mut needle = board.view(118, 197, 12, 10).to_image();
+        for p in needle.pixels_mut() {
+            p[0] = p[0].saturating_add(9);
+        }
+        imageops::replace(
+            &mut needle,
+            &RgbaImage::from_pixel(4, 4, Rgba([255, 0, 255, 0])),
+            4,
+            3,
+        );
+        let mask = alpha_mask(&needle);
+
+        let (score, positions) = brute_force(&haystack, &needle, &mask);
+        let matches = match_template(&haystack, &needle, Some(&mask));
+        assert_eq!(
+            matches.iter().map(|m| (m.x, m.y)).collect::<Vec<_>>(),
+            positions
+        );
+        assert!(matches.iter().all(|m| m.score == score));
+    }
+
+    #[test]
+    fn ties_and_edges() {
+        // A needle that fits equally well everywhere, and needles that don't fit at all
+        let haystack = RgbaImage::from_pixel(5, 4, Rgba([10, 20, 30, 255]));
+        let needle = RgbaImage::from_pixel(5, 3, Rgba([10, 20, 30, 255]));
+        assert_eq!(match_template(&haystack, &needle, None).len(), 2);
+        assert!(match_template(&haystack, &RgbaImage::new(6, 1), None).is_empty());
+        assert!(match_template(&haystack, &RgbaImage::new(0, 0), None).is_empty());
+    }
+
+    #[test]
+    fn frames_in_order() {
+        let board = reference_board();
+        let haystack = board.view(40, 160, 120, 120).to_image();
+        let needles: Vec<RgbaImage> = (0..5)
+            .map(|i| haystack.view(10 + i * 17, 7 + i * 9, 16, 16).to_image())
+            .collect();
+        let results = match_frames(&haystack, &needles, None);
+        for (i, matches) in results.iter().enumerate() {
+            let i = i as u32;
+            assert!(matches.contains(&Match {
+                x: 10 + i * 17,
+                y: 7 + i * 9,
+                score: 0
+            }));
+        }
+    }
+}
@@ -1,117 +1,853 @@
 // #![allow(dead_code)]
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use anyhow::anyhow;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::Serialize;
 use std::{
-    fs::{self, File},
-    io::{Cursor, Read, Seek, SeekFrom},
+    fs,
+    io::{self, Cursor},
     path::{Path, PathBuf},
 };
+use thiserror::Error;
 
-const DATA_DIR_ROOT: &str = "/home/aaron/.local/share/Steam/steamapps/common/Last Call BBS/Content";
-const DATA_DIR_PREFIX: &str = r"/home/aaron/.local/share/Steam/steamapps/common/Last Call BBS/";
+use crate::assets::AssetLocator;
 
 const TEX_SUFFIX: &str = ".tex";
 const ARRAY_TEX_SUFFIX: &str = ".array.tex";
 
-fn parse_array_tex(src_path: &Path, dest_path: PathBuf) {
-    let path = PathBuf::from(src_path);
+// Anything bigger than this is a misread header rather than a real texture
+const MAX_DIMENSION: u32 = 16384;
+const MAX_BYTES_PER_PIXEL: usize = 4;
 
-    let mut buffer = Vec::new();
-    File::open(path).unwrap().read_to_end(&mut buffer).unwrap();
-    let mut rdr = Cursor::new(buffer);
-    let mut compressed = Vec::new();
+// The word every game texture starts with. It has to be read off a real texture (inspect prints
+// it) and none were at hand when this was written, so it's still None and any first word is
+// taken. Once it's filled in, decode_texture turns away anything else.
+pub const TEX_MAGIC: Option<u32> = None;
 
-    // skip magic number
-    rdr.seek(SeekFrom::Current(4)).unwrap();
-    rdr.seek(SeekFrom::Current(4)).unwrap();
+// Format codes written by the game. Anything else is identified by its bytes per pixel.
+const FORMAT_GRAY: u32 = 1;
+const FORMAT_RGBA: u32 = 2;
 
-    let mut i = 0;
-    while let Ok(width) = rdr.read_u32::<LittleEndian>() {
-        let height = rdr.read_u32::<LittleEndian>().unwrap();
-        rdr.seek(SeekFrom::Current(56)).unwrap();
+#[derive(Debug, Error)]
+pub enum TexError {
+    #[error("io: {0}")]
+    Io(#[from] io::Error),
+    #[error("not a texture (starts {magic:#010x}, textures start {expected:#010x})")]
+    BadMagic { magic: u32, expected: u32 },
+    #[error("frame {frame} has a bad size ({width}x{height})")]
+    BadFrame {
+        frame: usize,
+        width: u32,
+        height: u32,
+    },
+    #[error("frame {frame} is truncated: {needed} bytes needed, {available} left")]
+    Truncated {
+        frame: usize,
+        needed: usize,
+        available: usize,
+    },
+    #[error("frame {frame} has unknown pixel format {format} ({len} bytes for {width}x{height})")]
+    UnknownFormat {
+        frame: usize,
+        format: u32,
+        width: u32,
+        height: u32,
+        len: usize,
+    },
+    #[error("frame {frame} failed to decompress: {source}")]
+    Lz4 {
+        frame: usize,
+        source: lz4_flex::block::DecompressError,
+    },
+}
 
-        let payload_size = rdr.read_u32::<LittleEndian>().unwrap();
-        compressed.resize(payload_size as usize, 0);
-        rdr.read_exact(&mut compressed).unwrap();
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PixelFormat {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+}
 
-        let texture = lz4_flex::decompress(&compressed, 100000000).unwrap();
-        let img = image::RgbaImage::from_raw(width, height, texture).unwrap();
-        let img = image::imageops::flip_vertical(&img);
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Gray => 1,
+            PixelFormat::GrayAlpha => 2,
+            PixelFormat::Rgb => 3,
+            PixelFormat::Rgba => 4,
+        }
+    }
 
-        let fname = dest_path.join(format!("{i:02}.png")); // format!("{}/{i:02}.png", dest_path.to_str().unwrap());
-        img.save(fname).unwrap();
-        i += 1;
+    // Known format codes are checked against the payload size, unknown ones are inferred
+    // from it
+    fn detect(code: u32, width: u32, height: u32, len: usize) -> Option<Self> {
+        let pixels = width as usize * height as usize;
+        let format = match code {
+            FORMAT_GRAY => PixelFormat::Gray,
+            FORMAT_RGBA => PixelFormat::Rgba,
+            _ if len % pixels != 0 => return None,
+            _ => match len / pixels {
+                1 => PixelFormat::Gray,
+                2 => PixelFormat::GrayAlpha,
+                3 => PixelFormat::Rgb,
+                4 => PixelFormat::Rgba,
+                _ => return None,
+            },
+        };
+        (format.bytes_per_pixel() * pixels == len).then_some(format)
     }
 }
 
-fn parse_tex(src_path: &Path, dest_path: PathBuf) {
-    let path = PathBuf::from(src_path);
+// The 8 bytes at the start of every texture file. The first word is the magic (see TEX_MAGIC).
+// The second is called a version because the original decoder skipped it as one, but nothing
+// here depends on its value and it hasn't been compared across the game's files.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TexHeader {
+    pub magic: u32,
+    pub version: u32,
+}
 
-    // Storage for the file and compressed image data
-    let mut buffer = Vec::new();
-    let mut compressed = Vec::new();
+// Every frame starts with its size and format and ends with the compressed payload size. Width,
+// height and payload size are confirmed by the pixels decoding, the format by codes 1 and 2
+// giving grey and RGBA pixel counts. The 13 words in between are unknown: working them out
+// needs the game's textures to compare, so they're kept as they are, shown by inspect and
+// written back out by the encoder.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FrameHeader {
+    pub width: u32,
+    pub height: u32,
+    pub format_code: u32,
+    pub extra: [u32; 13],
+    pub payload_size: u32,
+}
 
-    File::open(path).unwrap().read_to_end(&mut buffer).unwrap();
+pub const FRAME_HEADER_SIZE: usize = 4 * 17;
 
-    // Cursor is used for file navigation, skip first 8 bytes
-    let mut rdr = Cursor::new(buffer);
-    rdr.seek(SeekFrom::Current(8)).unwrap();
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub header: FrameHeader,
+    pub format: PixelFormat,
+    pub uncompressed_size: usize,
+    // Flipped the right way up
+    pub image: image::DynamicImage,
+}
 
-    // Read width and height, then skip more metadata
-    let width = rdr.read_u32::<LittleEndian>().unwrap();
-    let height = rdr.read_u32::<LittleEndian>().unwrap();
-    let format = rdr.read_u32::<LittleEndian>().unwrap();
-    rdr.seek(SeekFrom::Current(52)).unwrap();
+// A .tex holds a single frame, a .array.tex holds several back to back
+#[derive(Debug, Clone)]
+pub struct DecodedTexture {
+    pub header: TexHeader,
+    pub frames: Vec<Frame>,
+}
 
-    // Read payload size, then read the payload
-    let payload_size = rdr.read_u32::<LittleEndian>().unwrap();
-    compressed.resize(payload_size as usize, 0);
-    rdr.read_exact(&mut compressed).unwrap();
+pub fn read_texture(path: &Path) -> Result<DecodedTexture, TexError> {
+    decode_texture(&fs::read(path)?)
+}
 
-    // Decompress the texture, flip it vertically, and save it
-    println!("  width: {width}, height: {height}, payload size: {payload_size}");
-    let texture = lz4_flex::decompress(&compressed, 100000000).unwrap();
-    println!("  uncompressed: {}", texture.len());
-    println!("  format: {}", format);
+fn check_magic(magic: u32, expected: Option<u32>) -> Result<(), TexError> {
+    match expected {
+        Some(expected) if magic != expected => Err(TexError::BadMagic { magic, expected }),
+        _ => Ok(()),
+    }
+}
 
-    if format == 1 {
-        let img = image::GrayImage::from_raw(width, height, texture).unwrap();
-        let img = image::imageops::flip_vertical(&img);
-        img.save(dest_path).unwrap();
-    } else if format == 2 {
-        let img = image::RgbaImage::from_raw(width, height, texture).unwrap();
-        let img = image::imageops::flip_vertical(&img);
-        img.save(dest_path).unwrap();
-    } else {
-        panic!();
+pub fn decode_texture(bytes: &[u8]) -> Result<DecodedTexture, TexError> {
+    let mut rdr = Cursor::new(bytes);
+    let magic = rdr.read_u32::<LittleEndian>()?;
+    check_magic(magic, TEX_MAGIC)?;
+    let header = TexHeader {
+        magic,
+        version: rdr.read_u32::<LittleEndian>()?,
+    };
+
+    let mut frames = Vec::new();
+    while (rdr.position() as usize) < bytes.len() {
+        let frame = frames.len();
+        let available = bytes.len() - rdr.position() as usize;
+        if available < FRAME_HEADER_SIZE {
+            return Err(TexError::Truncated {
+                frame,
+                needed: FRAME_HEADER_SIZE,
+                available,
+            });
+        }
+
+        let width = rdr.read_u32::<LittleEndian>()?;
+        let height = rdr.read_u32::<LittleEndian>()?;
+        let format_code = rdr.read_u32::<LittleEndian>()?;
+        let mut extra = [0; 13];
+        rdr.read_u32_into::<LittleEndian>(&mut extra)?;
+        let payload_size = rdr.read_u32::<LittleEndian>()?;
+        let frame_header = FrameHeader {
+            width,
+            height,
+            format_code,
+            extra,
+            payload_size,
+        };
+
+        // A nonsense size means a misread header, or a file that isn't a texture at all
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(TexError::BadFrame {
+                frame,
+                width,
+                height,
+            });
+        }
+
+        // Read the payload
+        let start = rdr.position() as usize;
+        let available = bytes.len() - start;
+        if payload_size as usize > available {
+            return Err(TexError::Truncated {
+                frame,
+                needed: payload_size as usize,
+                available,
+            });
+        }
+        let compressed = &bytes[start..start + payload_size as usize];
+        rdr.set_position((start + payload_size as usize) as u64);
+
+        // Decompress the texture and work out what's in it
+        let max_size = width as usize * height as usize * MAX_BYTES_PER_PIXEL;
+        let texture = lz4_flex::decompress(compressed, max_size)
+            .map_err(|source| TexError::Lz4 { frame, source })?;
+        let uncompressed_size = texture.len();
+        let format = PixelFormat::detect(format_code, width, height, uncompressed_size).ok_or(
+            TexError::UnknownFormat {
+                frame,
+                format: format_code,
+                width,
+                height,
+                len: uncompressed_size,
+            },
+        )?;
+
+        // The sizes were checked above so these can't fail. Textures are stored bottom up.
+        let image = match format {
+            PixelFormat::Gray => image::GrayImage::from_raw(width, height, texture).map(Into::into),
+            PixelFormat::GrayAlpha => {
+                image::GrayAlphaImage::from_raw(width, height, texture).map(Into::into)
+            }
+            PixelFormat::Rgb => image::RgbImage::from_raw(width, height, texture).map(Into::into),
+            PixelFormat::Rgba => image::RgbaImage::from_raw(width, height, texture).map(Into::into),
+        }
+        .map(|img: image::DynamicImage| img.flipv())
+        .expect("pixel buffer size was checked against the format");
+
+        frames.push(Frame {
+            header: frame_header,
+            format,
+            uncompressed_size,
+            image,
+        });
+    }
+
+    Ok(DecodedTexture { header, frames })
+}
+
+impl Frame {
+    // Wrap an image for encoding. Grey images are stored as such, everything else as RGBA.
+    // The 13 unknown header words are zeroed, as nobody knows what the game puts there, so a
+    // texture made only from new frames is only known to be readable by decode_texture. To
+    // write something the game will load, start from a decoded texture and swap its images,
+    // which keeps its header words.
+    pub fn new(image: image::DynamicImage) -> Self {
+        let (format, format_code, image) = match image {
+            image::DynamicImage::ImageLuma8(_) => (PixelFormat::Gray, FORMAT_GRAY, image),
+            _ => (PixelFormat::Rgba, FORMAT_RGBA, image.to_rgba8().into()),
+        };
+        let header = FrameHeader {
+            width: image.width(),
+            height: image.height(),
+            format_code,
+            extra: [0; 13],
+            payload_size: 0,
+        };
+        Self {
+            header,
+            format,
+            uncompressed_size: format.bytes_per_pixel() * (image.width() * image.height()) as usize,
+            image,
+        }
     }
 }
 
-use walkdir::WalkDir;
-use xcap::image;
+pub fn write_texture(path: &Path, header: TexHeader, frames: &[Frame]) -> Result<(), TexError> {
+    fs::write(path, encode_texture(header, frames)?)?;
+    Ok(())
+}
 
-pub fn decode_all_textures() {
-    let decode_dir = Path::new("tokyo");
-    if !Path::exists(decode_dir) {
-        fs::create_dir(decode_dir).unwrap();
+// The inverse of decode_texture. Sizes and payload lengths are taken from the images, the
+// format code and unknown words from each frame's header. Pass one frame for a .tex, any
+// number for a .array.tex. Frames from Frame::new have zeroed unknown words, so only frames
+// and a header taken from one of the game's textures give a file laid out like the game's.
+pub fn encode_texture(header: TexHeader, frames: &[Frame]) -> Result<Vec<u8>, TexError> {
+    let mut bytes = Vec::new();
+    bytes.write_u32::<LittleEndian>(header.magic)?;
+    bytes.write_u32::<LittleEndian>(header.version)?;
+
+    for (i, frame) in frames.iter().enumerate() {
+        let (width, height) = (frame.image.width(), frame.image.height());
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(TexError::BadFrame {
+                frame: i,
+                width,
+                height,
+            });
+        }
+
+        // Flip to bottom up and convert to the frame's pixel format before compressing
+        let image = frame.image.flipv();
+        let pixels = match frame.format {
+            PixelFormat::Gray => image.to_luma8().into_raw(),
+            PixelFormat::GrayAlpha => image.to_luma_alpha8().into_raw(),
+            PixelFormat::Rgb => image.to_rgb8().into_raw(),
+            PixelFormat::Rgba => image.to_rgba8().into_raw(),
+        };
+        let payload = lz4_flex::compress(&pixels);
+
+        bytes.write_u32::<LittleEndian>(width)?;
+        bytes.write_u32::<LittleEndian>(height)?;
+        bytes.write_u32::<LittleEndian>(frame.header.format_code)?;
+        for word in frame.header.extra {
+            bytes.write_u32::<LittleEndian>(word)?;
+        }
+        bytes.write_u32::<LittleEndian>(payload.len() as u32)?;
+        bytes.extend(payload);
+    }
+
+    Ok(bytes)
+}
+
+// Decode each frame to NN.png in the destination folder
+fn parse_array_tex(src_path: &Path, dest_path: PathBuf) -> anyhow::Result<()> {
+    let texture = read_texture(src_path)?;
+    for (i, frame) in texture.frames.iter().enumerate() {
+        frame.image.save(dest_path.join(format!("{i:02}.png")))?;
+    }
+    Ok(())
+}
+
+fn parse_tex(src_path: &Path, dest_path: PathBuf) -> anyhow::Result<()> {
+    let texture = read_texture(src_path)?;
+    println!(
+        "  magic: {:#010x}, version: {}",
+        texture.header.magic, texture.header.version
+    );
+    for frame in &texture.frames {
+        let FrameHeader {
+            width,
+            height,
+            payload_size,
+            ..
+        } = frame.header;
+        println!("  width: {width}, height: {height}, payload size: {payload_size}");
+        println!("  uncompressed: {}", frame.uncompressed_size);
+        println!("  format: {:?}", frame.format);
     }
+    texture.frames[0].image.save(dest_path)?;
+    Ok(())
+}
+
+use walkdir::WalkDir;
+
+// The file name up to its first dot, so kobold.array.tex gives kobold. A leading dot belongs to
+// the name, like .hidden.tex giving .hidden. Matches Path::file_prefix, which is nightly only.
+fn file_prefix(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    let end = name
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| c == '.')
+        .map_or(name.len(), |(i, _)| i);
+    Some(&name[..end])
+}
+
+// Array textures decode to a folder of frames next to where the texture would go, named after it
+fn array_frames_dir(decoded: &Path) -> Option<PathBuf> {
+    Some(decoded.parent()?.join(file_prefix(decoded)?))
+}
+
+pub fn decode_all_textures(assets: &AssetLocator) -> anyhow::Result<()> {
+    let content_dir = assets.content_dir()?;
+    println!(
+        "Decoding {} into {}",
+        content_dir.display(),
+        assets.output_dir().display()
+    );
+    fs::create_dir_all(assets.sprites(""))?;
 
-    for entry in WalkDir::new(DATA_DIR_ROOT).into_iter().filter_map(|e| e.ok()) {
-        let short_path = entry.path().strip_prefix(DATA_DIR_PREFIX).unwrap();
+    for entry in WalkDir::new(&content_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let short_path = assets.decoded_path(entry.path())?;
         if entry.file_type().is_dir() {
-            match fs::create_dir(short_path) {
+            match fs::create_dir(&short_path) {
                 Ok(()) => println!("Created folder: {}", short_path.display()),
                 Err(_) => println!("Folder {} already exists", short_path.display()),
             }
         } else if entry.file_type().is_file() {
             println!("  {}", entry.path().to_string_lossy());
-            if entry.path().to_str().unwrap().ends_with(ARRAY_TEX_SUFFIX) {
-                let dname = entry.path().file_prefix().unwrap();
-                fs::create_dir(short_path.parent().unwrap().join(dname)).ok();
-                parse_array_tex(entry.path(), short_path.parent().unwrap().join(dname));
+            // Report textures that fail to decode and carry on with the rest
+            let result = if entry.path().to_str().unwrap().ends_with(ARRAY_TEX_SUFFIX) {
+                let dir = array_frames_dir(&short_path).unwrap();
+                fs::create_dir(&dir).ok();
+                parse_array_tex(entry.path(), dir)
             } else if entry.path().to_str().unwrap().ends_with(TEX_SUFFIX) {
                 parse_tex(entry.path(), short_path.with_extension("png"))
+            } else {
+                Ok(())
+            };
+            if let Err(e) = result {
+                println!("  failed: {e}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Everything the inspector reports about one texture file. Files that fail to decode are still
+// listed, with the error in place of the frames.
+#[derive(Debug, Serialize)]
+pub struct TextureInfo {
+    pub path: String,
+    pub file_size: u64,
+    pub magic: Option<u32>,
+    pub version: Option<u32>,
+    pub frames: Vec<FrameInfo>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FrameInfo {
+    pub width: u32,
+    pub height: u32,
+    pub format: String,
+    pub format_code: u32,
+    pub compressed_size: u32,
+    pub uncompressed_size: usize,
+    pub extra: [u32; 13],
+}
+
+impl TextureInfo {
+    // path is only used for display, so pass it relative to the content folder
+    pub fn from_bytes(path: String, bytes: &[u8]) -> Self {
+        let mut info = Self {
+            path,
+            file_size: bytes.len() as u64,
+            magic: None,
+            version: None,
+            frames: Vec::new(),
+            error: None,
+        };
+        match decode_texture(bytes) {
+            Ok(texture) => {
+                info.magic = Some(texture.header.magic);
+                info.version = Some(texture.header.version);
+                info.frames = texture.frames.iter().map(FrameInfo::from).collect();
             }
+            Err(e) => info.error = Some(e.to_string()),
+        }
+        info
+    }
+}
+
+impl From<&Frame> for FrameInfo {
+    fn from(frame: &Frame) -> Self {
+        Self {
+            width: frame.header.width,
+            height: frame.header.height,
+            format: format!("{:?}", frame.format),
+            format_code: frame.header.format_code,
+            compressed_size: frame.header.payload_size,
+            uncompressed_size: frame.uncompressed_size,
+            extra: frame.header.extra,
         }
     }
 }
+
+// Every .tex and .array.tex under the content folder, sorted so listings are stable
+fn texture_files(content_dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(content_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .filter(|p| p.to_string_lossy().ends_with(TEX_SUFFIX))
+        .collect();
+    files.sort();
+    files
+}
+
+// The name a texture is referred to by: its path under the content folder without the
+// .tex/.array.tex suffix, with forward slashes
+fn texture_name(relative: &Path) -> String {
+    let name = relative.to_string_lossy().replace('\\', "/");
+    let name = name.strip_suffix(ARRAY_TEX_SUFFIX).unwrap_or(&name);
+    name.strip_suffix(TEX_SUFFIX).unwrap_or(name).to_string()
+}
+
+// Find a texture by name. Any trailing part of the name will do (e.g. "numbers" or
+// "fonts/numbers") as long as only one texture matches.
+fn find_texture<'a>(names: &'a [String], wanted: &str) -> anyhow::Result<&'a str> {
+    let wanted = texture_name(Path::new(wanted));
+    let matches: Vec<&String> = names
+        .iter()
+        .filter(|name| *name == &wanted || name.ends_with(&format!("/{wanted}")))
+        .collect();
+    match matches[..] {
+        [name] => Ok(name),
+        [] => Err(anyhow!("No texture named '{wanted}'")),
+        _ => Err(anyhow!(
+            "'{wanted}' is ambiguous, it matches: {}",
+            matches
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+// Lists every texture with its header fields, one row per frame. Unknown header words are
+// only shown when non-zero, as index=value.
+pub fn inspect_textures(assets: &AssetLocator, json: bool) -> anyhow::Result<()> {
+    let content_dir = assets.content_dir()?;
+    let files = texture_files(&content_dir);
+    let infos: Vec<TextureInfo> = files
+        .iter()
+        .map(|path| {
+            let relative = path.strip_prefix(&content_dir).unwrap_or(path);
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            match fs::read(path) {
+                Ok(bytes) => TextureInfo::from_bytes(relative, &bytes),
+                Err(e) => TextureInfo {
+                    path: relative,
+                    file_size: 0,
+                    magic: None,
+                    version: None,
+                    frames: Vec::new(),
+                    error: Some(e.to_string()),
+                },
+            }
+        })
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&infos)?);
+    } else {
+        print!("{}", texture_table(&infos));
+    }
+    Ok(())
+}
+
+fn texture_table(infos: &[TextureInfo]) -> String {
+    let mut table = format!(
+        "{:<48} {:>10} {:>7} {:>4} {:>11} {:<9} {:>10} {:>12}  extra\n",
+        "texture", "magic", "version", "fr", "size", "format", "compressed", "uncompressed"
+    );
+    for info in infos {
+        let (magic, version) = match (info.magic, info.version) {
+            (Some(magic), Some(version)) => (format!("{magic:#010x}"), version.to_string()),
+            _ => (String::from("-"), String::from("-")),
+        };
+        if let Some(error) = &info.error {
+            table += &format!(
+                "{:<48} {magic:>10} {version:>7}  error: {error}\n",
+                info.path
+            );
+            continue;
+        }
+        for (i, frame) in info.frames.iter().enumerate() {
+            let name = match info.frames.len() {
+                1 => info.path.clone(),
+                _ => format!("{}[{i:02}]", info.path),
+            };
+            let extra: Vec<String> = frame
+                .extra
+                .iter()
+                .enumerate()
+                .filter(|(_, &word)| word != 0)
+                .map(|(j, word)| format!("{j}={word}"))
+                .collect();
+            table += &format!(
+                "{name:<48} {magic:>10} {version:>7} {:>4} {:>11} {:<9} {:>10} {:>12}  {}\n",
+                info.frames.len(),
+                format!("{}x{}", frame.width, frame.height),
+                frame.format,
+                frame.compressed_size,
+                frame.uncompressed_size,
+                extra.join(" ")
+            );
+        }
+    }
+    table
+}
+
+// Decode a single texture (or one frame of an array texture) to a png. Without an output path
+// it's written to <name>.png, or <name>_NN.png for a frame, in the output folder.
+pub fn extract_texture(
+    assets: &AssetLocator,
+    name: &str,
+    frame: Option<usize>,
+    out: Option<&Path>,
+) -> anyhow::Result<()> {
+    let content_dir = assets.content_dir()?;
+    let files = texture_files(&content_dir);
+    let names: Vec<String> = files
+        .iter()
+        .map(|path| texture_name(path.strip_prefix(&content_dir).unwrap_or(path)))
+        .collect();
+    let name = find_texture(&names, name)?;
+    let path = &files[names.iter().position(|n| n == name).unwrap()];
+
+    let texture = read_texture(path)?;
+    let index = frame.unwrap_or(0);
+    let image = &texture
+        .frames
+        .get(index)
+        .ok_or_else(|| {
+            anyhow!(
+                "{name} has {} frame(s), there is no frame {index}",
+                texture.frames.len()
+            )
+        })?
+        .image;
+
+    let dest = match (out, frame) {
+        (Some(out), _) => out.to_path_buf(),
+        (None, Some(index)) => assets.decoded(format!("{name}_{index:02}.png")),
+        (None, None) => assets.decoded(format!("{name}.png")),
+    };
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    image.save(&dest)?;
+    println!(
+        "Wrote {} ({}x{})",
+        dest.display(),
+        image.width(),
+        image.height()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    // Lay out a texture by hand: file header, then one frame per (width, height, format, pixels)
+    fn build_texture(frames: &[(u32, u32, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u32::<LittleEndian>(0).unwrap();
+        bytes.write_u32::<LittleEndian>(1).unwrap();
+        for (width, height, format, pixels) in frames {
+            let payload = lz4_flex::compress(pixels);
+            bytes.write_u32::<LittleEndian>(*width).unwrap();
+            bytes.write_u32::<LittleEndian>(*height).unwrap();
+            bytes.write_u32::<LittleEndian>(*format).unwrap();
+            bytes.extend([0; 52]);
+            bytes
+                .write_u32::<LittleEndian>(payload.len() as u32)
+                .unwrap();
+            bytes.extend(payload);
+        }
+        bytes
+    }
+
+    #[test]
+    fn decode_frames() {
+        // 2x2 grey followed by a 1x2 RGBA, both stored bottom row first
+        let bytes = build_texture(&[
+            (2, 2, FORMAT_GRAY, vec![1, 2, 3, 4]),
+            (1, 2, FORMAT_RGBA, vec![1, 1, 1, 1, 2, 2, 2, 2]),
+        ]);
+        let texture = decode_texture(&bytes).unwrap();
+        assert_eq!(texture.frames.len(), 2);
+
+        let gray = &texture.frames[0];
+        assert_eq!(gray.format, PixelFormat::Gray);
+        assert_eq!(gray.image.as_bytes(), [3, 4, 1, 2]);
+
+        let rgba = &texture.frames[1];
+        assert_eq!(rgba.format, PixelFormat::Rgba);
+        assert_eq!(rgba.image.as_bytes(), [2, 2, 2, 2, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn detect_unknown_format() {
+        let bytes = build_texture(&[(2, 1, 7, vec![1, 2, 3, 4, 5, 6])]);
+        let texture = decode_texture(&bytes).unwrap();
+        assert_eq!(texture.frames[0].format, PixelFormat::Rgb);
+
+        let bytes = build_texture(&[(2, 1, FORMAT_RGBA, vec![1, 2, 3, 4, 5, 6])]);
+        assert!(matches!(
+            decode_texture(&bytes),
+            Err(TexError::UnknownFormat { format: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn decode_errors() {
+        let bytes = build_texture(&[(2, 2, FORMAT_GRAY, vec![1, 2, 3, 4])]);
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(matches!(
+            decode_texture(truncated),
+            Err(TexError::Truncated { frame: 0, .. })
+        ));
+
+        let mut bad_size = bytes.clone();
+        bad_size[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            decode_texture(&bad_size),
+            Err(TexError::BadFrame { frame: 0, .. })
+        ));
+
+        assert!(check_magic(0x1234, None).is_ok());
+        assert!(check_magic(0x1234, Some(0x1234)).is_ok());
+        assert!(matches!(
+            check_magic(0, Some(0x1234)),
+            Err(TexError::BadMagic {
+                magic: 0,
+                expected: 0x1234
+            })
+        ));
+
+        let mut corrupt = bytes.clone();
+        let payload_start = 8 + FRAME_HEADER_SIZE;
+        corrupt[payload_start] = 0xff;
+        assert!(matches!(
+            decode_texture(&corrupt),
+            Err(TexError::Lz4 { frame: 0, .. })
+        ));
+    }
+
+    const HEADER: TexHeader = TexHeader {
+        magic: 0x1234,
+        version: 3,
+    };
+
+    // A small image with every pixel different, so flips and channel mixups show up
+    fn gradient(width: u32, height: u32) -> image::RgbaImage {
+        image::RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([x as u8 * 16, y as u8 * 16, (x + y) as u8, 255 - x as u8])
+        })
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let rgba = gradient(5, 3);
+        let gray = image::imageops::grayscale(&gradient(4, 6));
+        let mut frame = Frame::new(gray.clone().into());
+        frame.header.extra[3] = 42;
+        let frames = [Frame::new(rgba.clone().into()), frame];
+
+        let bytes = encode_texture(HEADER, &frames).unwrap();
+        let decoded = decode_texture(&bytes).unwrap();
+
+        assert_eq!(decoded.header, HEADER);
+        assert_eq!(decoded.frames.len(), 2);
+        assert_eq!(decoded.frames[0].format, PixelFormat::Rgba);
+        assert_eq!(decoded.frames[0].image.to_rgba8(), rgba);
+        assert_eq!(decoded.frames[1].format, PixelFormat::Gray);
+        assert_eq!(decoded.frames[1].image.to_luma8(), gray);
+        assert_eq!(decoded.frames[1].header.extra[3], 42);
+
+        // Encoding what was decoded should give back identical bytes
+        assert_eq!(
+            encode_texture(decoded.header, &decoded.frames).unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn encode_round_trip_parse_tex() {
+        let dir = std::env::temp_dir().join(format!("tex_round_trip_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let rgba = gradient(7, 2);
+        let tex_path = dir.join("gradient.tex");
+        write_texture(&tex_path, HEADER, &[Frame::new(rgba.clone().into())]).unwrap();
+        parse_tex(&tex_path, dir.join("gradient.png")).unwrap();
+        let png = image::open(dir.join("gradient.png")).unwrap().to_rgba8();
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(png, rgba);
+    }
+
+    #[test]
+    fn inspect_info() {
+        let rgba = gradient(3, 2);
+        let frames = [Frame::new(rgba.clone().into()), Frame::new(rgba.into())];
+        let bytes = encode_texture(HEADER, &frames).unwrap();
+        let info = TextureInfo::from_bytes(String::from("tokyo/walk.array.tex"), &bytes);
+        assert_eq!(info.file_size, bytes.len() as u64);
+        assert_eq!(info.magic, Some(HEADER.magic));
+        assert_eq!(info.frames.len(), 2);
+        assert_eq!((info.frames[1].width, info.frames[1].height), (3, 2));
+        assert_eq!(info.frames[1].uncompressed_size, 3 * 2 * 4);
+        assert!(info.error.is_none());
+
+        let info = TextureInfo::from_bytes(String::from("bad.tex"), &bytes[..20]);
+        assert!(info.frames.is_empty());
+        assert!(info.error.is_some());
+    }
+
+    #[test]
+    fn texture_names() {
+        assert_eq!(
+            texture_name(Path::new("Packed/fonts/numbers.tex")),
+            "Packed/fonts/numbers"
+        );
+        assert_eq!(
+            texture_name(Path::new("Packed/tokyo/kobold.array.tex")),
+            "Packed/tokyo/kobold"
+        );
+
+        let names = [
+            String::from("Packed/fonts/numbers"),
+            String::from("Packed/tokyo/numbers"),
+            String::from("Packed/tokyo/kobold"),
+        ];
+        assert_eq!(
+            find_texture(&names, "kobold.array.tex").unwrap(),
+            "Packed/tokyo/kobold"
+        );
+        assert_eq!(
+            find_texture(&names, "fonts/numbers").unwrap(),
+            "Packed/fonts/numbers"
+        );
+        assert!(find_texture(&names, "numbers").is_err());
+        assert!(find_texture(&names, "bold").is_err());
+    }
+
+    // What Path::file_prefix gives for each
+    #[test_case("Packed/tokyo/kobold.array.tex", Some("kobold"))]
+    #[test_case("Packed/fonts/numbers.tex", Some("numbers"))]
+    #[test_case("dots.in.dir/name.tex", Some("name"))]
+    #[test_case("numbers", Some("numbers"))]
+    #[test_case("trailing.", Some("trailing"))]
+    #[test_case(".hidden", Some(".hidden"))]
+    #[test_case(".hidden.array.tex", Some(".hidden"))]
+    #[test_case("ümlaut.tex", Some("ümlaut"))]
+    #[test_case("..", None)]
+    #[test_case("/", None)]
+    #[test_case("", None)]
+    fn file_prefixes(path: &str, prefix: Option<&str>) {
+        assert_eq!(file_prefix(Path::new(path)), prefix);
+    }
+
+    #[test_case("out/Packed/tokyo/kobold.array.tex", Some("out/Packed/tokyo/kobold"))]
+    #[test_case("/abs/kobold.array.tex", Some("/abs/kobold"))]
+    #[test_case("kobold.array.tex", Some("kobold"))]
+    #[test_case("/", None)]
+    fn array_frame_dirs(decoded: &str, dir: Option<&str>) {
+        assert_eq!(array_frames_dir(Path::new(decoded)), dir.map(PathBuf::from));
+    }
+}
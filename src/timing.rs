@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::util::Versioned;
+
+// Written by the calibrate command, read by DungeonCrawler::new
+pub const TIMING_DIR: &str = "data";
+pub const TIMING_FILE: &str = "timing.json";
+
+pub const TIMING_VERSION: u32 = 1;
+
+// Measured latencies are padded by this much before they're used as delays
+pub const SAFETY_MARGIN: f32 = 1.5;
+
+// How long to wait for the game at each step, in milliseconds. The defaults are what the game
+// needed on the machine this was written on.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimingProfile {
+    pub version: u32,
+    // Between clicks, to stop them being dropped
+    pub click_delay_ms: u64,
+    // After clicking, before capturing the result
+    pub screenshot_delay_ms: u64,
+    // For the menu to open when resetting the board
+    pub menu_delay_ms: u64,
+    // After a board is solved, before asking for another. Not calibrated, it covers the victory
+    // screen rather than a redraw.
+    pub board_pause_ms: u64,
+}
+
+impl Default for TimingProfile {
+    fn default() -> Self {
+        Self {
+            version: TIMING_VERSION,
+            click_delay_ms: 20,
+            screenshot_delay_ms: 10,
+            menu_delay_ms: 250,
+            board_pause_ms: 2500,
+        }
+    }
+}
+
+impl Versioned for TimingProfile {
+    const VERSION: u32 = TIMING_VERSION;
+    const DIR: &'static str = TIMING_DIR;
+    const FILE: &'static str = TIMING_FILE;
+    const NAME: &'static str = "Timing profile";
+    const WRITTEN_BY: Option<&'static str> = Some("calibrate");
+
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+impl TimingProfile {
+    pub fn click_delay(&self) -> Duration {
+        Duration::from_millis(self.click_delay_ms)
+    }
+
+    pub fn screenshot_delay(&self) -> Duration {
+        Duration::from_millis(self.screenshot_delay_ms)
+    }
+
+    pub fn menu_delay(&self) -> Duration {
+        Duration::from_millis(self.menu_delay_ms)
+    }
+
+    pub fn board_pause(&self) -> Duration {
+        Duration::from_millis(self.board_pause_ms)
+    }
+}
+
+// Turn the slowest of a set of measured redraws into a delay, with some headroom
+pub fn padded_ms(samples: &[Duration]) -> u64 {
+    let slowest = samples.iter().max().copied().unwrap_or_default();
+    (slowest.as_secs_f32() * 1000.0 * SAFETY_MARGIN)
+        .ceil()
+        .max(1.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn profile_file() {
+        let path = std::env::temp_dir().join(format!("timing_{}.json", std::process::id()));
+        assert_eq!(
+            TimingProfile::load_or_default(&path).unwrap(),
+            TimingProfile::default()
+        );
+
+        let profile = TimingProfile {
+            click_delay_ms: 7,
+            ..Default::default()
+        };
+        profile.save(&path).unwrap();
+        let loaded = TimingProfile::load_or_default(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), profile);
+    }
+
+    #[test]
+    fn profile_version() {
+        let json = serde_json::to_string(&TimingProfile {
+            version: TIMING_VERSION + 1,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(TimingProfile::from_json(&json).is_err());
+    }
+
+    #[test]
+    fn padding() {
+        let samples = [4, 12, 9].map(Duration::from_millis);
+        assert_eq!(padded_ms(&samples), 18);
+        assert_eq!(padded_ms(&[]), 1);
+    }
+}
@@ -1,4 +1,35 @@
-use xcap::image::RgbaImage;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use image::RgbaImage;
+use serde::{de::DeserializeOwned, Serialize};
+
+// Set to save the images behind each screen read (the crops, the regions found, what was
+// parsed) for working out what went wrong. Off by default, since screen reads can run on
+// several threads at once and the files would be written over each other.
+pub const DEBUG_IMAGES_ENV: &str = "DUNGEON_CRAWLER_DEBUG_IMAGES";
+
+pub fn debug_images() -> bool {
+    env::var_os(DEBUG_IMAGES_ENV).is_some()
+}
+
+// Save one of those images if they're wanted. They're only ever a debugging aid, so failing to
+// write one is reported and otherwise ignored.
+pub fn save_debug_image(img: &RgbaImage, path: impl AsRef<Path>) {
+    if !debug_images() {
+        return;
+    }
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Err(e) = img.save(path) {
+        println!("Couldn't save {}: {e}", path.display());
+    }
+}
 
 pub fn draw_rect(img: &mut RgbaImage, x: u32, y: u32, w: u32, h: u32, col: [u8; 4]) {
     for j in y..y + h {
@@ -9,10 +40,80 @@ pub fn draw_rect(img: &mut RgbaImage, x: u32, y: u32, w: u32, h: u32, col: [u8;
             if da == 0 {
                 p.0 = col;
             } else {
-                for i in 0..3 {
-                    p.0[i] = ((col[i] as u32 * sa + p.0[i] as u32 * da) / (sa + da)) as u8;
+                for (c, &src) in p.0[..3].iter_mut().zip(&col) {
+                    *c = ((src as u32 * sa + *c as u32 * da) / (sa + da)) as u8;
                 }
             }
         }
     }
 }
+
+// The json files under data/ that start with a version number. Ones from another version are
+// turned away rather than misread.
+pub trait Versioned: Serialize + DeserializeOwned {
+    // Bump this when the meaning of a field changes
+    const VERSION: u32;
+    const DIR: &'static str;
+    const FILE: &'static str;
+    // What the file holds, to start error messages with
+    const NAME: &'static str;
+    // The command that writes the file, if it isn't edited by hand
+    const WRITTEN_BY: Option<&'static str> = None;
+
+    fn version(&self) -> u32;
+
+    // Anything serde can't tell is wrong with it
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn path() -> PathBuf {
+        Path::new(Self::DIR).join(Self::FILE)
+    }
+
+    fn from_json(json: &str) -> Result<Self> {
+        let value: Self = serde_json::from_str(json)?;
+        if value.version() != Self::VERSION {
+            let rerun = Self::WRITTEN_BY
+                .map(|command| format!(". Rerun the {command} command to regenerate it."))
+                .unwrap_or_default();
+            return Err(anyhow!(
+                "{} is version {}, expected {}{rerun}",
+                Self::NAME,
+                value.version(),
+                Self::VERSION
+            ));
+        }
+        value.check()?;
+        Ok(value)
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path).map_err(|e| {
+            let written_by = Self::WRITTEN_BY
+                .map(|command| format!(". It's written by the {command} command."))
+                .unwrap_or_default();
+            anyhow!("{}: {e}{written_by}", path.display())
+        })?;
+        Self::from_json(&json).map_err(|e| anyhow!("{}: {e}", path.display()))
+    }
+
+    // No file just means it hasn't been written yet, a broken one is still an error
+    fn load_or_default(path: &Path) -> Result<Self>
+    where
+        Self: Default,
+    {
+        match path.exists() {
+            true => Self::load(path),
+            false => Ok(Self::default()),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+}